@@ -0,0 +1,134 @@
+use crate::symbols;
+use addr2line::{
+    gimli::{Dwarf, EndianSlice, RunTimeEndian},
+    Context,
+};
+use clap::Parser;
+use goblin::elf::Elf;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process,
+};
+
+const ENCRYPTED_SIGNATURE: &[u8] = b"~PSP";
+
+#[derive(Parser, Debug)]
+#[command(
+    name = "cargo psp addr2line",
+    about = "Resolve addresses in a module to functions and source lines",
+    long_about = "Resolve addresses in a module to functions and source lines.\n\n\
+        Addresses are read as hex and are offsets into the module, unless --base \
+        is given. Source lines need debug info, e.g. `debug = true` in the \
+        release profile. If the PRX has none, the ELF next to it is used."
+)]
+struct Args {
+    #[arg(help = "PRX or ELF file that crashed")]
+    file: PathBuf,
+    #[arg(required = true, help = "Addresses to resolve, in hex")]
+    addresses: Vec<String>,
+    #[arg(
+        long,
+        value_name = "ADDR",
+        help = "Address the module was loaded at, subtracted from each address"
+    )]
+    base: Option<String>,
+}
+
+fn fail(msg: impl std::fmt::Display) -> ! {
+    eprintln!("error: {}", msg);
+    process::exit(1);
+}
+
+fn parse_hex(s: &str) -> Option<u32> {
+    let digits = s
+        .strip_prefix("0x")
+        .or_else(|| s.strip_prefix("0X"))
+        .unwrap_or(s);
+
+    u32::from_str_radix(digits, 16).ok()
+}
+
+fn section<'a>(elf: &Elf, bytes: &'a [u8], name: &str) -> Option<&'a [u8]> {
+    elf.section_headers
+        .iter()
+        .find(|sh| elf.shdr_strtab.get_at(sh.sh_name) == Some(name))
+        .and_then(|sh| bytes.get(sh.file_range()?))
+}
+
+type LineTable<'a> = Context<EndianSlice<'a, RunTimeEndian>>;
+
+/// Load the debug info of an ELF, or `None` if it has none.
+fn line_table<'a>(path: &Path, bytes: &'a [u8]) -> Option<LineTable<'a>> {
+    let elf = Elf::parse(bytes).ok()?;
+    section(&elf, bytes, ".debug_line")?;
+
+    let dwarf = Dwarf::load(|id| {
+        let data = section(&elf, bytes, id.name()).unwrap_or_default();
+        Ok::<_, addr2line::gimli::Error>(EndianSlice::new(data, RunTimeEndian::Little))
+    })
+    .ok()?;
+
+    Context::from_dwarf(dwarf)
+        .map_err(|e| eprintln!("warning: {}: {}", path.display(), e))
+        .ok()
+}
+
+pub fn main(args: impl Iterator<Item = String>) {
+    let args = Args::parse_from(args);
+
+    let bytes = fs::read(&args.file)
+        .unwrap_or_else(|e| fail(format!("failed to read {}: {}", args.file.display(), e)));
+
+    if bytes.starts_with(ENCRYPTED_SIGNATURE) {
+        fail("module is encrypted (~PSP header), pass the unencrypted PRX or ELF instead");
+    }
+
+    let elf = Elf::parse(&bytes).unwrap_or_else(|e| fail(format!("invalid ELF: {}", e)));
+    let functions = symbols::functions(&elf);
+
+    if functions.is_empty() {
+        eprintln!("note: {} has no function symbols", args.file.display());
+    }
+
+    // The ELF that prxgen was run on sits next to the PRX, without an
+    // extension.
+    let elf_path = args.file.with_extension("");
+    let elf_bytes = fs::read(&elf_path).unwrap_or_default();
+    let lines = line_table(&args.file, &bytes).or_else(|| line_table(&elf_path, &elf_bytes));
+
+    if lines.is_none() {
+        eprintln!(
+            "note: no debug info found, set `debug = true` in the profile to show source lines"
+        );
+    }
+
+    let base = args.base.as_deref().map_or(0, |base| {
+        parse_hex(base).unwrap_or_else(|| fail(format!("invalid base address `{}`", base)))
+    });
+
+    for address in &args.addresses {
+        let address =
+            parse_hex(address).unwrap_or_else(|| fail(format!("invalid address `{}`", address)));
+        let offset = address.wrapping_sub(base);
+
+        let function = match symbols::lookup(&functions, offset) {
+            Some(f) if offset == f.address => f.name.clone(),
+            Some(f) => format!("{}+{:#x}", f.name, offset - f.address),
+            None => "??".into(),
+        };
+
+        let location = lines
+            .as_ref()
+            .and_then(|lines| lines.find_location(offset as u64).ok().flatten())
+            .map_or("??:0".into(), |location| {
+                format!(
+                    "{}:{}",
+                    location.file.unwrap_or("??"),
+                    location.line.unwrap_or(0)
+                )
+            });
+
+        println!("{:#010x}: {} at {}", address, function, location);
+    }
+}
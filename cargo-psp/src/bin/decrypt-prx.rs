@@ -0,0 +1,182 @@
+use cargo_psp::{gzip, kirk};
+use clap::Parser;
+use std::{convert::TryInto, fs, path::PathBuf, process};
+
+const PBP_SIGNATURE: &[u8] = b"\0PBP";
+const PBP_DATA_PSP_IDX: usize = 6;
+
+const PSP_MAGIC: &[u8] = b"~PSP";
+const PSP_HEADER_SIZE: usize = 0x150;
+
+const ELF_MAGIC: &[u8] = b"\x7fELF";
+
+/// Bit of the compression attribute set for compressed modules.
+const COMP_COMPRESSED: u16 = 0x0001;
+
+/// Magic numbers of Sony's own compression formats.
+const KL4E_MAGIC: &[u8] = b"KL4E";
+const KL3E_MAGIC: &[u8] = b"KL3E";
+
+#[derive(Parser, Debug)]
+#[command(
+    name = "decrypt-prx",
+    version = "0.1",
+    about = "Decrypt and decompress ~PSP modules",
+    long_about = "Decrypt and decompress ~PSP modules.\n\n\
+        Supports untagged modules encrypted with KIRK command 1, and unencrypted ones \
+        such as the gzip compressed modules written by psp-packer. The input may also be \
+        an EBOOT.PBP.\n\n\
+        Tagged modules, which include retail modules and those signed by psp-prxsign \
+        or PRXEncrypter, need Sony's tag keys and are not supported. Neither is KL4E or \
+        KL3E compression."
+)]
+struct Args {
+    #[arg(name = "in_file", help = "Input ~PSP or PBP file")]
+    in_file: PathBuf,
+    #[arg(name = "out_file.prx", help = "Output ELF or PRX file")]
+    out_file: PathBuf,
+}
+
+fn error(message: impl std::fmt::Display) -> ! {
+    eprintln!("error: {}", message);
+    process::exit(1);
+}
+
+/// The fields of the `~PSP` header needed to extract the module.
+struct PspHeader {
+    comp_attribute: u16,
+    elf_size: usize,
+    psp_size: usize,
+    decrypt_mode: u8,
+    comp_size: usize,
+    tag: u32,
+    /// Whether the key and hash fields are all zero, as in unencrypted modules.
+    keys_empty: bool,
+}
+
+impl PspHeader {
+    fn parse(bytes: &[u8]) -> Option<Self> {
+        let bytes = bytes.get(..PSP_HEADER_SIZE)?;
+        let word = |idx: usize| u32::from_le_bytes(bytes[idx..idx + 4].try_into().unwrap());
+
+        Some(Self {
+            comp_attribute: u16::from_le_bytes([bytes[6], bytes[7]]),
+            elf_size: word(0x28) as usize,
+            psp_size: word(0x2C) as usize,
+            decrypt_mode: bytes[0x7C],
+            comp_size: word(0xB0) as usize,
+            tag: word(0xD0),
+            keys_empty: bytes[0x80..0xB0]
+                .iter()
+                .chain(&bytes[0xC0..0xD0])
+                .all(|&b| b == 0),
+        })
+    }
+}
+
+/// Get the DATA.PSP of a PBP, or the input itself for anything else.
+fn unwrap_pbp(bytes: &[u8]) -> Result<&[u8], String> {
+    if !bytes.starts_with(PBP_SIGNATURE) {
+        return Ok(bytes);
+    }
+
+    let offset = |idx: usize| {
+        bytes
+            .get(8 + idx * 4..12 + idx * 4)
+            .map(|b| u32::from_le_bytes(b.try_into().unwrap()) as usize)
+            .ok_or("truncated PBP header")
+    };
+
+    let start = offset(PBP_DATA_PSP_IDX)?;
+    let end = offset(PBP_DATA_PSP_IDX + 1)?;
+
+    bytes
+        .get(start..end)
+        .ok_or_else(|| "DATA.PSP offsets in the PBP header are out of bounds".into())
+}
+
+/// Decrypt and decompress a `~PSP` module.
+fn extract(bytes: &[u8]) -> Result<Vec<u8>, String> {
+    if bytes.starts_with(ELF_MAGIC) {
+        return Err("module is not encrypted, it can be used as is".into());
+    }
+
+    if !bytes.starts_with(PSP_MAGIC) {
+        return Err("not a ~PSP module".into());
+    }
+
+    let header = PspHeader::parse(bytes).ok_or("truncated ~PSP header")?;
+    let payload = bytes
+        .get(PSP_HEADER_SIZE..header.psp_size)
+        .ok_or("~PSP module is truncated")?;
+
+    let data = if header.tag != 0 {
+        return Err(format!(
+            "module is encrypted with tag {:#010x} (decrypt mode {}), tagged modules are not \
+             supported by decrypt-prx",
+            header.tag, header.decrypt_mode
+        ));
+    } else if kirk::is_kirk1(payload) {
+        kirk::kirk1_decrypt(payload)?
+    } else if header.keys_empty {
+        payload
+            .get(..header.comp_size)
+            .ok_or("~PSP payload is truncated")?
+            .to_vec()
+    } else {
+        return Err("unknown ~PSP encryption".into());
+    };
+
+    let data = if header.comp_attribute & COMP_COMPRESSED == 0 {
+        data
+    } else if gzip::is_gzip(&data) {
+        gzip::gunzip(&data).map_err(|e| format!("failed to decompress module: {}", e))?
+    } else if data.starts_with(KL4E_MAGIC) || data.starts_with(KL3E_MAGIC) {
+        return Err("KL4E/KL3E compressed modules are out of scope for decrypt-prx".into());
+    } else {
+        return Err(format!(
+            "unknown compression (attribute {:#06x})",
+            header.comp_attribute
+        ));
+    };
+
+    if data.len() != header.elf_size {
+        eprintln!(
+            "warning: ~PSP header declares {} bytes, but {} were extracted",
+            header.elf_size,
+            data.len()
+        );
+    }
+
+    if !data.starts_with(ELF_MAGIC) {
+        return Err("extracted data is not an ELF file".into());
+    }
+
+    Ok(data)
+}
+
+fn main() {
+    let args = Args::parse();
+
+    let bytes = fs::read(&args.in_file).unwrap_or_else(|e| {
+        error(format_args!(
+            "failed to read {}: {}",
+            args.in_file.display(),
+            e
+        ))
+    });
+
+    let elf = unwrap_pbp(&bytes)
+        .and_then(extract)
+        .unwrap_or_else(|e| error(format_args!("{}: {}", args.in_file.display(), e)));
+
+    if let Err(e) = fs::write(&args.out_file, elf) {
+        error(format_args!(
+            "failed to write {}: {}",
+            args.out_file.display(),
+            e
+        ));
+    }
+
+    println!("Saved to {:?}", args.out_file);
+}
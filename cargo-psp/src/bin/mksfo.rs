@@ -1,8 +1,12 @@
-use clap::Parser;
-use std::io::prelude::*;
-use std::io::SeekFrom;
-use std::{collections::HashMap, path::PathBuf};
-use std::{error::Error, fs::File};
+use clap::{Parser, ValueEnum};
+use std::convert::TryInto;
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    path::PathBuf,
+    process,
+};
+use std::{error::Error, fs::File, io::Write};
 
 #[repr(C, packed)]
 struct SfoHeader {
@@ -25,6 +29,18 @@ impl SfoHeader {
 
         buf
     }
+
+    fn from_le_bytes(buf: &[u8; 20]) -> Self {
+        let word = |idx: usize| u32::from_le_bytes(buf[idx..idx + 4].try_into().unwrap());
+
+        Self {
+            magic: word(0),
+            version: word(4),
+            key_offset: word(8),
+            val_offset: word(12),
+            count: word(16),
+        }
+    }
 }
 
 #[repr(C, packed)]
@@ -51,21 +67,113 @@ impl SfoEntry {
 
         buf
     }
+
+    fn from_le_bytes(buf: &[u8; 16]) -> Self {
+        let word = |idx: usize| u32::from_le_bytes(buf[idx..idx + 4].try_into().unwrap());
+
+        Self {
+            key_offset: u16::from_le_bytes([buf[0], buf[1]]),
+            alignment: buf[2],
+            type_: buf[3],
+            val_size: word(4),
+            total_size: word(8),
+            data_offset: word(12),
+        }
+    }
 }
 
 #[repr(u8)]
 #[derive(Clone, Copy, Eq, PartialEq, Debug)]
 enum EntryType {
-    // TODO this type is undocumented, unused in mksfoext
+    /// Raw bytes, used by save data entries such as `SAVEDATA_PARAMS`.
     Binary = 0,
     String = 2,
     Dword = 4,
 }
 
+/// A single PARAM.SFO value, as dumped to or read from TOML / JSON.
+///
+/// `max_len` is the space reserved for the value in the data table. Some
+/// fields (e.g. `TITLE`) are conventionally padded to a fixed size, so that
+/// they can later be patched in place.
+#[derive(serde_derive::Deserialize, serde_derive::Serialize, Clone, Debug, PartialEq)]
+#[serde(tag = "type", rename_all = "lowercase", deny_unknown_fields)]
+enum SfoValue {
+    Binary {
+        /// Hex encoded bytes.
+        value: String,
+        max_len: Option<u32>,
+    },
+    String {
+        value: String,
+        max_len: Option<u32>,
+    },
+    Dword {
+        value: u32,
+        max_len: Option<u32>,
+    },
+}
+
+impl SfoValue {
+    fn entry_type(&self) -> EntryType {
+        match self {
+            SfoValue::Binary { .. } => EntryType::Binary,
+            SfoValue::String { .. } => EntryType::String,
+            SfoValue::Dword { .. } => EntryType::Dword,
+        }
+    }
+
+    fn max_len(&self) -> Option<u32> {
+        match *self {
+            SfoValue::Binary { max_len, .. }
+            | SfoValue::String { max_len, .. }
+            | SfoValue::Dword { max_len, .. } => max_len,
+        }
+    }
+
+    /// The encoded value, including the NUL terminator for strings.
+    fn to_bytes(&self) -> Result<Vec<u8>, String> {
+        match self {
+            SfoValue::Binary { value, .. } => decode_hex(value),
+            SfoValue::String { value, .. } => {
+                let mut bytes = value.as_bytes().to_vec();
+                bytes.push(0);
+                Ok(bytes)
+            }
+            SfoValue::Dword { value, .. } => Ok(value.to_le_bytes().to_vec()),
+        }
+    }
+}
+
+fn encode_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn decode_hex(hex: &str) -> Result<Vec<u8>, String> {
+    let hex: Vec<char> = hex.chars().filter(|c| !c.is_whitespace()).collect();
+
+    if !hex.len().is_multiple_of(2) {
+        return Err("hex string has an odd number of digits".into());
+    }
+
+    hex.chunks(2)
+        .map(|pair| {
+            let digits: String = pair.iter().collect();
+            u8::from_str_radix(&digits, 16).map_err(|_| format!("invalid hex byte `{digits}`"))
+        })
+        .collect()
+}
+
 const MAX_OPTIONS: usize = 256;
 const PSF_MAGIC: u32 = 0x46535000;
 const PSF_VERSION: u32 = 0x00000101;
 
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum Format {
+    Toml,
+    Json,
+}
+
 #[derive(Parser, Debug)]
 #[command(
     name = "mksfo",
@@ -94,10 +202,42 @@ struct Args {
         help = "key=VALUE Add a new STRING value"
     )]
     string: Vec<(String, String)>,
-    #[arg(help = "Display title")]
-    title: String,
+    #[arg(
+        short, long,
+        value_parser = parse_key_val::<String, String>,
+        number_of_values = 1,
+        help = "key=HEX Add a new BINARY value"
+    )]
+    binary: Vec<(String, String)>,
+    #[arg(
+        long,
+        value_name = "PARAM.SFO",
+        conflicts_with_all = ["from_toml", "bare"],
+        help = "Dump every entry of an existing SFO file to stdout"
+    )]
+    dump: Option<PathBuf>,
+    #[arg(
+        long,
+        value_enum,
+        default_value = "toml",
+        help = "Format used by --dump"
+    )]
+    format: Format,
+    #[arg(
+        long,
+        value_name = "FILE",
+        conflicts_with = "bare",
+        help = "Build the SFO from a TOML file in the --dump format. Flags and \
+                <TITLE> override its values."
+    )]
+    from_toml: Option<PathBuf>,
+    #[arg(
+        help = "Display title",
+        required_unless_present_any = ["dump", "from_toml"]
+    )]
+    title: Option<String>,
     #[arg(help = "Output file name")]
-    output: PathBuf,
+    output: Option<PathBuf>,
 }
 
 fn parse_key_val<T, U>(s: &str) -> Result<(T, U), Box<dyn Error + Send + Sync + 'static>>
@@ -113,26 +253,243 @@ where
     Ok((s[..pos].parse()?, s[pos + 1..].parse()?))
 }
 
+/// Parse every entry of an SFO file.
+fn read_sfo(bytes: &[u8]) -> Result<BTreeMap<String, SfoValue>, String> {
+    const HEADER_SIZE: usize = core::mem::size_of::<SfoHeader>();
+    const ENTRY_SIZE: usize = core::mem::size_of::<SfoEntry>();
+
+    let header = bytes
+        .get(..HEADER_SIZE)
+        .map(|b| SfoHeader::from_le_bytes(b.try_into().unwrap()))
+        .ok_or("file is too small to be an SFO")?;
+
+    let magic = header.magic;
+    if magic != PSF_MAGIC {
+        return Err(format!("bad SFO magic {:#010x}", magic));
+    }
+
+    let mut entries = BTreeMap::new();
+
+    for i in 0..header.count as usize {
+        let start = HEADER_SIZE + i * ENTRY_SIZE;
+        let entry = bytes
+            .get(start..start + ENTRY_SIZE)
+            .map(|b| SfoEntry::from_le_bytes(b.try_into().unwrap()))
+            .ok_or_else(|| format!("entry {} is out of bounds", i))?;
+
+        let key_start = header.key_offset as usize + entry.key_offset as usize;
+        let key = bytes
+            .get(key_start..)
+            .and_then(|b| b.split(|&b| b == 0).next())
+            .ok_or_else(|| format!("key of entry {} is out of bounds", i))?;
+        let key = String::from_utf8(key.to_vec())
+            .map_err(|_| format!("key of entry {} is not valid UTF-8", i))?;
+
+        let data_start = header.val_offset as usize + entry.data_offset as usize;
+        let data = bytes
+            .get(data_start..data_start + entry.val_size as usize)
+            .ok_or_else(|| format!("value of {} is out of bounds", key))?;
+
+        let max_len = Some(entry.total_size);
+
+        let value = match entry.type_ {
+            t if t == EntryType::Binary as u8 => SfoValue::Binary {
+                value: encode_hex(data),
+                max_len,
+            },
+            t if t == EntryType::String as u8 => {
+                let data = data.split(|&b| b == 0).next().unwrap();
+                SfoValue::String {
+                    value: String::from_utf8(data.to_vec())
+                        .map_err(|_| format!("value of {} is not valid UTF-8", key))?,
+                    max_len,
+                }
+            }
+            t if t == EntryType::Dword as u8 && data.len() == 4 => SfoValue::Dword {
+                value: u32::from_le_bytes(data.try_into().unwrap()),
+                max_len,
+            },
+            t => return Err(format!("unsupported type {:#x} for {}", t, key)),
+        };
+
+        entries.insert(key, value);
+    }
+
+    Ok(entries)
+}
+
+/// Serialize entries into an SFO file. Keys are written in sorted order.
+fn write_sfo(entries: &BTreeMap<String, SfoValue>) -> Result<Vec<u8>, String> {
+    let mut header = SfoHeader {
+        magic: PSF_MAGIC,
+        version: PSF_VERSION,
+        key_offset: 0,
+        val_offset: 0,
+        count: 0,
+    };
+
+    let mut keys = Vec::new();
+    let mut data = Vec::new();
+    let mut sfo_entries: Vec<SfoEntry> = Vec::new();
+
+    for (key, value) in entries {
+        let bytes = value.to_bytes().map_err(|e| format!("{}: {}", key, e))?;
+        let val_size = bytes.len() as u32;
+        let aligned_size = (val_size + 3) & !3;
+
+        let total_size = match value.max_len() {
+            Some(max_len) if max_len < val_size => {
+                return Err(format!(
+                    "{} is {} bytes long, but its max_len is {}",
+                    key, val_size, max_len
+                ))
+            }
+            Some(max_len) => (max_len + 3) & !3,
+            None => aligned_size,
+        };
+
+        header.count += 1;
+        sfo_entries.push(SfoEntry {
+            key_offset: keys.len() as u16,
+            alignment: 4,
+            type_: value.entry_type() as u8,
+            val_size,
+            total_size,
+            data_offset: data.len() as u32,
+        });
+
+        keys.extend(key.as_bytes());
+        keys.push(0);
+
+        data.extend(&bytes);
+        data.resize(data.len() + (total_size - val_size) as usize, 0);
+    }
+
+    header.key_offset = (core::mem::size_of::<SfoHeader>()
+        + sfo_entries.len() * core::mem::size_of::<SfoEntry>()) as u32;
+
+    let aligned_val_offset = (header.key_offset + keys.len() as u32 + 3) & !3;
+    header.val_offset = aligned_val_offset;
+
+    let mut out = Vec::new();
+    out.extend(&header.to_le_bytes());
+    for sfo_entry in sfo_entries {
+        out.extend(&sfo_entry.to_le_bytes());
+    }
+    out.extend(&keys);
+    out.resize(aligned_val_offset as usize, 0);
+    out.extend(&data);
+
+    Ok(out)
+}
+
+fn dump(path: PathBuf, format: Format) {
+    let bytes = match fs::read(&path) {
+        Ok(bytes) => bytes,
+        Err(err) => panic!("failed to read {}: {}", path.display(), err),
+    };
+
+    let entries = read_sfo(&bytes).unwrap_or_else(|e| {
+        eprintln!("{}: {}", path.display(), e);
+        process::exit(1);
+    });
+
+    let output = match format {
+        Format::Toml => toml::to_string(&entries).unwrap(),
+        Format::Json => serde_json::to_string_pretty(&entries).unwrap() + "\n",
+    };
+
+    print!("{}", output);
+}
+
 fn main() {
     let args = Args::parse();
-    // TODO this type is undocumented, unused in mksfoext
-    //let mut binaries: HashMap<String, Vec<u8>> = HashMap::new();
 
-    let mut strings: HashMap<String, String> = args.string.into_iter().collect();
-    let mut dwords: HashMap<String, u32> = args.dword.into_iter().collect();
+    if let Some(path) = args.dump {
+        dump(path, args.format);
+        return;
+    }
 
-    if !args.bare {
-        strings.insert("TITLE".to_string(), args.title);
+    // With `--from-toml`, a lone positional argument is the output file.
+    let (title, output) = match (args.title, args.output) {
+        (Some(title), Some(output)) => (Some(title), output),
+        (Some(output), None) if args.from_toml.is_some() => (None, output.into()),
+        _ => {
+            eprintln!("an output file name is required");
+            process::exit(1);
+        }
+    };
+
+    let mut entries: BTreeMap<String, SfoValue> = match &args.from_toml {
+        Some(path) => {
+            let text = match fs::read_to_string(path) {
+                Ok(text) => text,
+                Err(err) => panic!("failed to read {}: {}", path.display(), err),
+            };
+
+            toml::from_str(&text).unwrap_or_else(|e| {
+                eprintln!("Failed to read {}: {}", path.display(), e);
+                process::exit(1);
+            })
+        }
+        None => BTreeMap::new(),
+    };
+
+    let string = |value: &str| SfoValue::String {
+        value: value.to_string(),
+        max_len: None,
+    };
+    let dword = |value: u32| SfoValue::Dword {
+        value,
+        max_len: None,
+    };
 
+    if !args.bare && args.from_toml.is_none() {
         // Default Values
-        strings.insert("CATEGORY".to_string(), "MG".to_string());
-        strings.insert("DISC_ID".to_string(), "UCJS10041".to_string());
-        strings.insert("DISC_VERSION".to_string(), "1.00".to_string());
-        strings.insert("PSP_SYSTEM_VER".to_string(), "1.00".to_string());
-
-        dwords.insert("BOOTABLE".to_string(), 1);
-        dwords.insert("PARENTAL_LEVEL".to_string(), 1);
-        dwords.insert("REGION".to_string(), 0x8000);
+        entries.insert("CATEGORY".to_string(), string("MG"));
+        entries.insert("DISC_ID".to_string(), string("UCJS10041"));
+        entries.insert("DISC_VERSION".to_string(), string("1.00"));
+        entries.insert("PSP_SYSTEM_VER".to_string(), string("1.00"));
+
+        entries.insert("BOOTABLE".to_string(), dword(1));
+        entries.insert("PARENTAL_LEVEL".to_string(), dword(1));
+        entries.insert("REGION".to_string(), dword(0x8000));
+    }
+
+    if !args.bare {
+        if let Some(title) = title {
+            entries.insert("TITLE".to_string(), string(&title));
+        }
+    }
+
+    // Explicit values override defaults, keeping any reserved length from the
+    // TOML file so that patched files keep their layout.
+    let overrides = args
+        .string
+        .into_iter()
+        .map(|(k, v)| (k, string(&v)))
+        .chain(args.dword.into_iter().map(|(k, v)| (k, dword(v))))
+        .chain(args.binary.into_iter().map(|(k, v)| {
+            let value = SfoValue::Binary {
+                value: v,
+                max_len: None,
+            };
+
+            (k, value)
+        }));
+
+    for (key, mut value) in overrides {
+        if let Some(old) = entries.get(&key) {
+            if old.entry_type() == value.entry_type() {
+                match &mut value {
+                    SfoValue::Binary { max_len, .. }
+                    | SfoValue::String { max_len, .. }
+                    | SfoValue::Dword { max_len, .. } => *max_len = old.max_len(),
+                }
+            }
+        }
+
+        entries.insert(key, value);
     }
 
     let valid: HashMap<&'static str, (EntryType, bool, bool, bool, bool)> = [
@@ -196,7 +553,10 @@ fn main() {
     .cloned()
     .collect();
 
-    let category = strings.get("CATEGORY").unwrap();
+    let category = match entries.get("CATEGORY") {
+        Some(SfoValue::String { value, .. }) => value.clone(),
+        _ => panic!("Key CATEGORY must be set to a STRING value"),
+    };
 
     let validate = |key: &str, entry_type: EntryType| {
         if !valid.contains_key(key) {
@@ -220,25 +580,11 @@ fn main() {
         }
     };
 
-    for key in strings.keys() {
-        validate(key, EntryType::String);
+    for (key, value) in &entries {
+        validate(key, value.entry_type());
     }
 
-    for key in dwords.keys() {
-        validate(key, EntryType::Dword);
-    }
-
-    let outpath = args.output;
-
-    let mut header = SfoHeader {
-        magic: PSF_MAGIC,
-        version: PSF_VERSION,
-        key_offset: 0,
-        val_offset: 0,
-        count: 0,
-    };
-
-    let num_options = dwords.len() + strings.len();
+    let num_options = entries.len();
     if num_options > MAX_OPTIONS {
         panic!(
             "Maximum number of options is {}, you have {}",
@@ -246,81 +592,11 @@ fn main() {
         );
     }
 
-    let mut keys = [0u8; 8192];
-    let mut data = [0u8; 8192];
-
-    let mut key_offset = 0;
-    let mut data_offset = 0;
+    let bytes = write_sfo(&entries).unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        process::exit(1);
+    });
 
-    let mut sfo_entries: Vec<SfoEntry> = Vec::new();
-
-    let mut sorted_keys: Vec<String> = Vec::new();
-    for (key, _value) in dwords.iter() {
-        sorted_keys.push(key.to_string());
-    }
-    for (key, _value) in strings.iter() {
-        sorted_keys.push(key.to_string());
-    }
-    sorted_keys.sort();
-
-    for key in sorted_keys {
-        if dwords.contains_key(&key) {
-            let value = dwords.get(&key).unwrap();
-            header.count += 1;
-            let mut sfo_entry = SfoEntry {
-                key_offset,
-                data_offset,
-                alignment: 4,
-                type_: EntryType::Dword as u8,
-                ..Default::default()
-            };
-            let idx = key_offset as usize;
-            keys[idx..idx + key.len()].copy_from_slice(key.as_bytes());
-            key_offset += key.len() as u16 + 1;
-            sfo_entry.val_size = 4;
-            sfo_entry.total_size = 4;
-            let idx = data_offset as usize;
-            data[idx..idx + 4].copy_from_slice(&value.to_le_bytes());
-            data_offset += 4;
-            sfo_entries.push(sfo_entry);
-        } else if strings.contains_key(&key) {
-            let value = strings.get(&key).unwrap();
-            header.count += 1;
-            let mut sfo_entry = SfoEntry {
-                key_offset,
-                data_offset,
-                alignment: 4,
-                type_: EntryType::String as u8,
-                ..Default::default()
-            };
-            let idx = key_offset as usize;
-            keys[idx..idx + key.len()].copy_from_slice(key.as_bytes());
-            key_offset += key.len() as u16 + 1;
-
-            let val_size = value.len() + 1;
-            let total_size = (val_size + 3) & !3;
-            sfo_entry.val_size = val_size as u32;
-            sfo_entry.total_size = total_size as u32;
-            let idx = data_offset as usize;
-            data[idx..idx + value.len()].copy_from_slice(value.as_bytes());
-            data_offset += total_size as u32;
-            sfo_entries.push(sfo_entry);
-        }
-    }
-
-    header.key_offset = (core::mem::size_of::<SfoHeader>()
-        + sfo_entries.len() * core::mem::size_of::<SfoEntry>()) as u32;
-
-    let aligned_val_offset = (header.key_offset + key_offset as u32 + 3) & !3;
-    header.val_offset = aligned_val_offset;
-
-    let mut file = File::create(outpath).unwrap();
-    file.write_all(&header.to_le_bytes()).unwrap();
-    for sfo_entry in sfo_entries {
-        file.write_all(&sfo_entry.to_le_bytes()).unwrap();
-    }
-    file.write_all(&keys[0..key_offset as usize]).unwrap();
-    file.seek(SeekFrom::Start(aligned_val_offset as u64))
-        .unwrap();
-    file.write_all(&data[0..data_offset as usize]).unwrap();
+    let mut file = File::create(output).unwrap();
+    file.write_all(&bytes).unwrap();
 }
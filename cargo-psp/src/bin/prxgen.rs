@@ -1,22 +1,100 @@
-use clap::Parser;
+use clap::{Parser, ValueEnum};
 use goblin::elf32::{
     header::Header,
-    program_header::{ProgramHeader, PT_LOAD},
-    reloc::{Rel, R_MIPS_GPREL16, R_MIPS_PC16, SIZEOF_REL},
+    program_header::{ProgramHeader, PT_LOAD, PT_LOPROC},
+    reloc::{
+        r_to_str, Rel, R_MIPS_16, R_MIPS_26, R_MIPS_32, R_MIPS_GPREL16, R_MIPS_GPREL32,
+        R_MIPS_HI16, R_MIPS_LO16, R_MIPS_NONE, R_MIPS_PC16, R_MIPS_REL32, SIZEOF_REL,
+    },
     section_header::{SectionHeader, SHF_ALLOC, SHT_LOPROC, SHT_REL, SHT_SYMTAB},
-    sym::{Sym, SIZEOF_SYM},
+    sym::{Sym, SIZEOF_SYM, STT_SECTION},
 };
 use scroll::{
     ctx::{TryFromCtx, TryIntoCtx},
     Endian,
 };
-use std::{collections::HashMap, ffi::CStr, fs, path::PathBuf};
+use std::{
+    collections::{BTreeMap, HashMap},
+    convert::TryInto,
+    ffi::CStr,
+    fmt, fs, mem,
+    path::PathBuf,
+    process,
+};
 
 const ELF_EXEC_TYPE: u16 = 0x0002;
 const ELF_MACHINE_MIPS: u16 = 0x0008;
 
 const PRX_EXEC_TYPE: u16 = 0xFFA0;
 const PRX_SHT_REL: u32 = SHT_LOPROC | 0xA0;
+const PRX_PT_REL_B: u32 = PT_LOPROC | 0xA1;
+
+/// Field widths of a type B relocation command, in bits. The command holds the
+/// flag index, then the segment, then the type index, and the remaining bits
+/// are an offset.
+const TYPE_B_FLAG_BITS: u32 = 3;
+const TYPE_B_SEG_BITS: u32 = 1;
+const TYPE_B_TYPE_BITS: u32 = 3;
+const TYPE_B_OFFSET_SHIFT: u32 = TYPE_B_FLAG_BITS + TYPE_B_SEG_BITS + TYPE_B_TYPE_BITS;
+
+/// The flag table of type B relocations. The first byte is the size of the
+/// table, which means that index 0 is never used.
+///
+/// Bit 0 distinguishes relocations from commands setting the base offset. Bits
+/// 1-2 select how the offset is stored (in the command, in the command plus 16
+/// bits, or as 32 bits), and bit 4 is set when a `lo16` value follows, needed
+/// to apply `HI16` relocations.
+const TYPE_B_FLAGS: [u8; 8] = [8, 0x00, 0x01, 0x03, 0x05, 0x11, 0x13, 0x15];
+const TYPE_B_FLAG_BASE: u16 = 1;
+const TYPE_B_FLAG_OFFSET_SHORT: u16 = 2;
+const TYPE_B_FLAG_OFFSET_LONG: u16 = 3;
+const TYPE_B_FLAG_OFFSET_ABSOLUTE: u16 = 4;
+/// Added to an offset flag index to select the same flag with a `lo16` value.
+const TYPE_B_FLAG_LO16: u16 = 3;
+
+/// The type table of type B relocations, padded to keep commands aligned.
+/// Index 0 is again the size of the table.
+const TYPE_B_TYPES: [u8; 6] = [6, 2, 3, 4, 5, 0];
+
+/// Index into `TYPE_B_TYPES` of an ELF relocation type.
+fn type_b_type(r_type: u32) -> Option<u16> {
+    match r_type {
+        R_MIPS_32 => Some(1),
+        R_MIPS_26 => Some(2),
+        R_MIPS_HI16 => Some(3),
+        R_MIPS_LO16 => Some(4),
+        _ => None,
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum RelocationFormat {
+    /// Standard ELF `REL` entries, in `SHT_PRXREL` (0x700000A0) sections.
+    A,
+    /// The compressed PSP format, in a 0x700000A1 program header.
+    B,
+}
+
+/// Why a relocation was not emitted.
+enum Dropped {
+    /// The relocation has no effect when the module is moved as a whole.
+    NotNeeded,
+    /// The relocation would be needed, but cannot be emitted.
+    Unsupported(&'static str),
+}
+
+fn fail(message: impl fmt::Display) -> ! {
+    eprintln!("error: {}", message);
+    process::exit(1);
+}
+
+fn c_str(bytes: &[u8], offset: usize) -> &str {
+    bytes
+        .get(offset..)
+        .and_then(|b| CStr::from_bytes_until_nul(b).ok())
+        .and_then(|s| s.to_str().ok())
+        .unwrap_or("<invalid>")
+}
 
 #[derive(Parser, Debug)]
 #[command(
@@ -35,12 +113,25 @@ struct Args {
         help = "Alternative name for .rodata.sceModuleInfo section"
     )]
     minfo: String,
+    #[arg(
+        long,
+        value_enum,
+        default_value = "a",
+        help = "Format of the relocations in the PRX"
+    )]
+    relocations: RelocationFormat,
+    #[arg(
+        short,
+        long,
+        help = "List every dropped relocation, not only those which may break the module"
+    )]
+    verbose: bool,
 }
 
 fn main() {
     let args = Args::parse();
     PrxBuilder::new(args.in_file, &args.minfo)
-        .modify()
+        .modify(args.relocations, args.verbose)
         .save(args.out_file);
 }
 
@@ -51,6 +142,7 @@ struct PrxBuilder<'a> {
     section_headers: Vec<SectionHeader>,
     program_headers: Vec<ProgramHeader>,
     relocations: HashMap<usize, Vec<Rel>>,
+    type_b_relocations: Option<Vec<u8>>,
 }
 
 impl<'a> PrxBuilder<'a> {
@@ -104,53 +196,125 @@ impl<'a> PrxBuilder<'a> {
             section_headers,
             program_headers,
             relocations,
+            type_b_relocations: None,
+        }
+    }
+
+    fn section_name(&self, index: usize) -> &str {
+        let sh_string_table = self.section_headers[self.header.e_shstrndx as usize];
+        let start_idx = sh_string_table.sh_offset as usize;
+        let end_idx = start_idx + sh_string_table.sh_size as usize;
+
+        match self.section_headers.get(index) {
+            Some(sh) => c_str(&self.elf_bytes[start_idx..end_idx], sh.sh_name as usize),
+            None => "<invalid>",
+        }
+    }
+
+    /// Load the symbols and string table linked from a relocation section.
+    fn symbols(&self, relocation_header: &SectionHeader) -> Option<(Vec<Sym>, &[u8])> {
+        let symbols_header = self
+            .section_headers
+            .get(relocation_header.sh_link as usize)
+            .filter(|sh| sh.sh_type == SHT_SYMTAB)?;
+
+        let start_idx = symbols_header.sh_offset as usize;
+        let end_idx = symbols_header.sh_size as usize + start_idx;
+        let symbols = self.elf_bytes[start_idx..end_idx]
+            .chunks(SIZEOF_SYM)
+            .map(|rel_bytes| Sym::try_from_ctx(rel_bytes, Endian::Little).unwrap().0)
+            .collect();
+
+        let strings_header = self.section_headers.get(symbols_header.sh_link as usize)?;
+        let start_idx = strings_header.sh_offset as usize;
+        let end_idx = strings_header.sh_size as usize + start_idx;
+
+        Some((symbols, &self.elf_bytes[start_idx..end_idx]))
+    }
+
+    fn symbol_name(&self, symbol: &Sym, strings: &[u8]) -> String {
+        if symbol.st_info & 0xf == STT_SECTION {
+            self.section_name(symbol.st_shndx as usize).to_string()
+        } else {
+            c_str(strings, symbol.st_name as usize).to_string()
         }
     }
 
     /// Modify the inner structures to create a PRX format file.
-    fn modify(mut self) -> Self {
+    fn modify(mut self, format: RelocationFormat, verbose: bool) -> Self {
         // Change ELF type, and program header count.
         self.header.e_type = PRX_EXEC_TYPE;
         self.header.e_phnum = 1;
 
-        // Change all relocation types.
-        for (i, rels) in &mut self.relocations {
-            let relocation_header = &self.section_headers[*i];
+        let mut relocations = mem::take(&mut self.relocations);
+        let mut indices = relocations.keys().copied().collect::<Vec<_>>();
+        indices.sort_unstable();
 
-            // Don't touch relocations with invalid links.
-            let Some(symbols_header) =
-                &self.section_headers.get(relocation_header.sh_link as usize)
-            else {
-                continue;
-            };
+        let mut not_needed = BTreeMap::new();
+        let mut unsupported = 0;
 
-            // Don't touch relocations without symbols.
-            if symbols_header.sh_type != SHT_SYMTAB {
-                continue;
-            }
+        // Remove relocations which cannot be emitted, reporting them.
+        for &i in &indices {
+            let rels = relocations.get_mut(&i).unwrap();
+            let relocation_header = self.section_headers[i];
+            let symbols = self.symbols(&relocation_header);
+            let target = self.section_name(relocation_header.sh_info as usize);
 
-            // Load symbols.
-            let symbols = {
-                let start_idx = symbols_header.sh_offset as usize;
-                let end_idx = symbols_header.sh_size as usize + start_idx;
-                self.elf_bytes[start_idx..end_idx]
-                    .chunks(SIZEOF_SYM)
-                    .map(|rel_bytes| Sym::try_from_ctx(rel_bytes, Endian::Little).unwrap().0)
-                    .collect::<Vec<Sym>>()
-            };
-
-            // Remove weak relocations.
             rels.retain(|rel| {
-                // 16-bit relocs are unsupported.
-                if matches!(rel.r_info & 0xFF, R_MIPS_GPREL16 | R_MIPS_PC16) {
-                    false
-                // relocs outside of section zero must be removed.
-                } else if let Some(symbol) = symbols.get((rel.r_info >> 8) as usize) {
-                    symbol.st_shndx != 0
-                // relocs with invalid symbols must be removed.
-                } else {
-                    false
+                let r_type = rel.r_info & 0xff;
+                let symbol = symbols
+                    .as_ref()
+                    .map(|(symbols, _)| symbols.get((rel.r_info >> 8) as usize));
+
+                let dropped = match (r_type, symbol) {
+                    // Position independent relocations, or no-ops.
+                    (R_MIPS_NONE | R_MIPS_GPREL16 | R_MIPS_PC16 | R_MIPS_GPREL32, _) => {
+                        Some(Dropped::NotNeeded)
+                    }
+                    (_, Some(None)) => Some(Dropped::Unsupported("invalid symbol index")),
+                    // relocs against section zero must be removed.
+                    (_, Some(Some(symbol))) if symbol.st_shndx == 0 => {
+                        Some(Dropped::Unsupported("undefined symbol"))
+                    }
+                    (R_MIPS_16 | R_MIPS_REL32, _) if format == RelocationFormat::B => Some(
+                        Dropped::Unsupported("relocation type is not supported by type B"),
+                    ),
+                    (R_MIPS_16 | R_MIPS_32 | R_MIPS_REL32 | R_MIPS_26 | R_MIPS_HI16, _) => None,
+                    (R_MIPS_LO16, _) => None,
+                    _ => Some(Dropped::Unsupported("unsupported relocation type")),
+                };
+
+                let name = || match (&symbols, symbol.flatten()) {
+                    (Some((_, strings)), Some(symbol)) => self.symbol_name(symbol, strings),
+                    _ => format!("symbol {}", rel.r_info >> 8),
+                };
+
+                let describe = || {
+                    format!(
+                        "{} relocation at {:#x} in {} against `{}`",
+                        r_to_str(r_type, ELF_MACHINE_MIPS),
+                        rel.r_offset,
+                        target,
+                        name()
+                    )
+                };
+
+                match dropped {
+                    None => return true,
+                    Some(Dropped::NotNeeded) => {
+                        *not_needed.entry(r_type).or_insert(0) += 1;
+
+                        if verbose {
+                            eprintln!("note: dropped {}: position independent", describe());
+                        }
+                    }
+                    Some(Dropped::Unsupported(reason)) => {
+                        unsupported += 1;
+                        eprintln!("warning: dropped {}: {}", describe(), reason);
+                    }
                 }
+
+                false
             });
 
             // Set upper 24 bits to 0 (OFS_BASE, ADDR_BASE).
@@ -159,10 +323,34 @@ impl<'a> PrxBuilder<'a> {
             }
         }
 
-        // Update all relocation headers.
+        self.relocations = relocations;
+
+        if !verbose {
+            for (r_type, count) in &not_needed {
+                eprintln!(
+                    "note: dropped {} {} relocations, as they are position independent",
+                    count,
+                    r_to_str(*r_type, ELF_MACHINE_MIPS)
+                );
+            }
+        }
+
+        if unsupported > 0 {
+            eprintln!(
+                "warning: {} relocations were dropped, the module may not work correctly",
+                unsupported
+            );
+        }
+
+        // Update all relocation headers. Type B relocations are stored in a
+        // program header instead, so the sections are left as plain ELF
+        // relocations which the PSP ignores.
         for (i, rels) in &mut self.relocations {
             let section_header = &mut self.section_headers[*i];
-            section_header.sh_type = PRX_SHT_REL;
+            section_header.sh_type = match format {
+                RelocationFormat::A => PRX_SHT_REL,
+                RelocationFormat::B => SHT_REL,
+            };
             section_header.sh_size = (rels.len() * SIZEOF_REL) as u32;
         }
 
@@ -182,21 +370,62 @@ impl<'a> PrxBuilder<'a> {
         .expect("failed to get module info");
 
         // Merge all `LOAD` segments, as the PSP seems to only be able to handle one.
-        // This code assumes all segments appear sequentially, and start at zero.
-        {
+        // This requires all segments to appear sequentially, start at zero,
+        // and be laid out in the file exactly as they are in memory.
+        let start_offset = {
             let load_segments = || {
                 self.program_headers
                     .iter()
                     .filter(|ph| ph.p_type == PT_LOAD)
             };
 
-            let start_vaddr = load_segments()
+            let first = load_segments()
                 .next()
-                .expect("program had no LOAD segments")
-                .p_vaddr;
-            assert_eq!(start_vaddr, 0);
+                .unwrap_or_else(|| fail("program has no LOAD segments"));
+
+            if first.p_vaddr != 0 {
+                fail(format_args!(
+                    "the first LOAD segment starts at {:#x}, but must start at 0",
+                    first.p_vaddr
+                ));
+            }
+
+            let start_offset = first.p_offset;
+
+            let segments = load_segments().collect::<Vec<_>>();
+            for (i, ph) in segments.iter().enumerate() {
+                if ph.p_offset < start_offset || ph.p_offset - start_offset != ph.p_vaddr {
+                    fail(format_args!(
+                        "LOAD segment {} at {:#x} is at file offset {:#x}, but must be at {:#x} \
+                         to merge the segments",
+                        i,
+                        ph.p_vaddr,
+                        ph.p_offset,
+                        start_offset + ph.p_vaddr
+                    ));
+                }
+
+                let Some(next) = segments.get(i + 1) else {
+                    continue;
+                };
+
+                if next.p_vaddr < ph.p_vaddr + ph.p_memsz {
+                    fail(format_args!(
+                        "LOAD segments {} and {} overlap or are out of order",
+                        i,
+                        i + 1
+                    ));
+                }
 
-            let start_offset = load_segments().next().unwrap().p_offset;
+                if ph.p_filesz != ph.p_memsz {
+                    fail(format_args!(
+                        "LOAD segment {} has {:#x} bytes of uninitialized data, but is not the \
+                         last segment",
+                        i,
+                        ph.p_memsz - ph.p_filesz
+                    ));
+                }
+            }
 
             let mem_size = load_segments()
                 .map(|ph| ph.p_offset + ph.p_memsz - start_offset)
@@ -223,15 +452,138 @@ impl<'a> PrxBuilder<'a> {
             program_header.p_memsz = mem_size;
             program_header.p_flags = 5;
             program_header.p_align = 0x10;
+
+            start_offset
+        };
+
+        if format == RelocationFormat::B {
+            if self.program_headers.len() < 2 {
+                fail("there is no room for the relocation program header");
+            }
+
+            let relocations = self.type_b_relocations(&indices, start_offset);
+            self.type_b_relocations = Some(relocations);
+            self.header.e_phnum = 2;
         }
 
         self
     }
 
+    /// Encode the relocations in the type B format.
+    ///
+    /// The module has a single segment at this point, so every relocation is
+    /// both in segment 0 and relative to it.
+    fn type_b_relocations(&self, indices: &[usize], start_offset: u32) -> Vec<u8> {
+        let mut bytes = vec![0, 0, TYPE_B_FLAG_BITS as u8, TYPE_B_TYPE_BITS as u8];
+        bytes.extend(TYPE_B_FLAGS);
+        bytes.extend(TYPE_B_TYPES);
+
+        let push = |bytes: &mut Vec<u8>, value: u16| bytes.extend(value.to_le_bytes());
+
+        // Start at offset 0 of segment 0.
+        push(&mut bytes, TYPE_B_FLAG_BASE);
+
+        let instruction = |offset: u32| {
+            let idx = (start_offset + offset) as usize;
+            u32::from_le_bytes(self.elf_bytes[idx..idx + 4].try_into().unwrap())
+        };
+
+        let short_range = 1 << (15 - TYPE_B_OFFSET_SHIFT);
+        let long_range = short_range << 16;
+        let mut base = 0i64;
+
+        for i in indices {
+            let rels = &self.relocations[i];
+
+            for (j, rel) in rels.iter().enumerate() {
+                let r_type = rel.r_info & 0xff;
+
+                // `HI16` relocations need the low half of the address, from
+                // the instruction of the `LO16` relocation which follows.
+                let lo16 = (r_type == R_MIPS_HI16).then(|| {
+                    rels[j..]
+                        .iter()
+                        .find(|r| r.r_info & 0xff == R_MIPS_LO16)
+                        .map(|r| instruction(r.r_offset) as u16)
+                        .unwrap_or_else(|| {
+                            eprintln!(
+                                "warning: R_MIPS_HI16 relocation at {:#x} has no matching \
+                                 R_MIPS_LO16 relocation",
+                                rel.r_offset
+                            );
+                            0
+                        })
+                });
+
+                let delta = rel.r_offset as i64 - base;
+                base = rel.r_offset as i64;
+
+                let offset_flag = if (-short_range..short_range).contains(&delta) {
+                    TYPE_B_FLAG_OFFSET_SHORT
+                } else if (-long_range..long_range).contains(&delta) {
+                    TYPE_B_FLAG_OFFSET_LONG
+                } else {
+                    TYPE_B_FLAG_OFFSET_ABSOLUTE
+                };
+
+                let flag = match lo16 {
+                    Some(_) => offset_flag + TYPE_B_FLAG_LO16,
+                    None => offset_flag,
+                };
+                let r_type = type_b_type(r_type).expect("unsupported relocation type");
+                let command = flag | (r_type << (TYPE_B_FLAG_BITS + TYPE_B_SEG_BITS));
+
+                match offset_flag {
+                    TYPE_B_FLAG_OFFSET_SHORT => {
+                        push(
+                            &mut bytes,
+                            command | ((delta as u16) << TYPE_B_OFFSET_SHIFT),
+                        );
+                    }
+                    TYPE_B_FLAG_OFFSET_LONG => {
+                        push(
+                            &mut bytes,
+                            command | (((delta >> 16) as u16) << TYPE_B_OFFSET_SHIFT),
+                        );
+                        push(&mut bytes, delta as u16);
+                    }
+                    _ => {
+                        push(&mut bytes, command);
+                        bytes.extend(rel.r_offset.to_le_bytes());
+                    }
+                }
+
+                if let Some(lo16) = lo16 {
+                    push(&mut bytes, lo16);
+                }
+            }
+        }
+
+        bytes
+    }
+
     /// Write out the changes to a file.
-    fn save(self, output: PathBuf) {
+    fn save(mut self, output: PathBuf) {
         let mut bytes = self.elf_bytes;
 
+        // Append type B relocations to the end of the file.
+        if let Some(relocations) = self.type_b_relocations {
+            bytes.resize(bytes.len().next_multiple_of(0x10), 0);
+
+            self.program_headers[1] = ProgramHeader {
+                p_type: PRX_PT_REL_B,
+                p_offset: bytes.len() as u32,
+                p_vaddr: 0,
+                p_paddr: 0,
+                p_filesz: relocations.len() as u32,
+                p_memsz: 0,
+                p_flags: 0,
+                p_align: 0x10,
+            };
+
+            bytes.extend(relocations);
+        }
+
         // Write header to buffer.
         self.header
             .try_into_ctx(&mut bytes, Endian::Little)
@@ -266,3 +618,159 @@ impl<'a> PrxBuilder<'a> {
         fs::write(output, bytes).expect("failed to write file");
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A decoded type B relocation: offset, type from the type table, and the
+    /// `lo16` value of `HI16` relocations.
+    type Decoded = (u32, u8, Option<u16>);
+
+    /// Decode type B relocations the way the module loader does, for a module
+    /// whose relocations are in its second program header.
+    fn decode(bytes: &[u8]) -> Vec<Decoded> {
+        /// Read a little endian value of `len` bytes.
+        fn read(rest: &mut &[u8], len: usize) -> u32 {
+            let (value, tail) = rest.split_at(len);
+            *rest = tail;
+            value.iter().rev().fold(0, |acc, &b| (acc << 8) | b as u32)
+        }
+
+        assert_eq!(&bytes[..2], [0, 0]);
+        let flag_bits = bytes[2] as u32;
+        let type_bits = bytes[3] as u32;
+
+        // One bit is enough to address the segments before the relocations.
+        let seg_bits = 1;
+
+        let flag_table = &bytes[4..];
+        let type_table = &flag_table[flag_table[0] as usize..];
+        let mut rest = &type_table[type_table[0] as usize..];
+
+        let mut relocations = Vec::new();
+        let mut offset = 0u32;
+
+        while !rest.is_empty() {
+            let command = read(&mut rest, 2) as u16;
+            let field = |shift: u32, bits: u32| (command >> shift) & ((1 << bits) - 1);
+
+            let flag = flag_table[field(0, flag_bits) as usize];
+            assert_eq!(field(flag_bits, seg_bits), 0, "segment");
+            let shift = flag_bits + seg_bits;
+
+            if flag & 0x01 == 0 {
+                match flag & 0x06 {
+                    0 => offset = (command >> shift) as u32,
+                    4 => offset = read(&mut rest, 4),
+                    _ => panic!("invalid base flag {:#x}", flag),
+                }
+                continue;
+            }
+
+            let r_type = type_table[field(shift, type_bits) as usize];
+            let high = (command as i16 >> (shift + type_bits)) as i32;
+
+            match flag & 0x06 {
+                0 => offset = offset.wrapping_add(high as u32),
+                2 => offset = offset.wrapping_add(((high << 16) as u32) | read(&mut rest, 2)),
+                4 => offset = read(&mut rest, 4),
+                _ => panic!("invalid offset flag {:#x}", flag),
+            }
+
+            let lo16 = match flag & 0x38 {
+                0x00 => None,
+                0x10 => Some(read(&mut rest, 2) as u16),
+                _ => panic!("invalid lo16 flag {:#x}", flag),
+            };
+
+            relocations.push((offset, r_type, lo16));
+        }
+
+        relocations
+    }
+
+    fn rel(r_offset: u32, r_type: u32) -> Rel {
+        Rel {
+            r_offset,
+            r_info: r_type,
+        }
+    }
+
+    #[test]
+    fn type_b_relocations_round_trip() {
+        // The segment starts at file offset 0x10, with an instruction whose
+        // low half is 0x8004 at offset 0x20.
+        let start_offset = 0x10;
+        let mut elf_bytes = vec![0; 0x40];
+        elf_bytes[0x30..0x34].copy_from_slice(&0x2484_8004u32.to_le_bytes());
+
+        // Offsets are stored relative to the previous one, in the command
+        // itself, in one more halfword, or as an absolute value.
+        let text = vec![
+            rel(0x0, R_MIPS_32),
+            rel(0x1c, R_MIPS_HI16),
+            rel(0x24, R_MIPS_HI16),
+            rel(0x20, R_MIPS_LO16),
+            rel(0x8, R_MIPS_26),
+            rel(0x4000, R_MIPS_32),
+            rel(0x10, R_MIPS_26),
+            rel(0x0200_0000, R_MIPS_32),
+        ];
+        let data = vec![rel(0x0200_0004, R_MIPS_32), rel(0xFC, R_MIPS_HI16)];
+
+        let builder = PrxBuilder {
+            mod_info_sh_name: ".rodata.sceModuleInfo",
+            elf_bytes,
+            header: Header::default(),
+            section_headers: Vec::new(),
+            program_headers: Vec::new(),
+            relocations: HashMap::from([(3, text), (5, data)]),
+            type_b_relocations: None,
+        };
+
+        let bytes = builder.type_b_relocations(&[3, 5], start_offset);
+
+        assert_eq!(&bytes[4..4 + TYPE_B_FLAGS.len()], TYPE_B_FLAGS);
+        assert_eq!(bytes.len() % 2, 0);
+        assert_eq!(
+            decode(&bytes),
+            [
+                (0x0, 2, None),
+                (0x1c, 4, Some(0x8004)),
+                (0x24, 4, Some(0x8004)),
+                (0x20, 5, None),
+                (0x8, 3, None),
+                (0x4000, 2, None),
+                (0x10, 3, None),
+                (0x0200_0000, 2, None),
+                (0x0200_0004, 2, None),
+                // No `LO16` relocation follows.
+                (0xFC, 4, Some(0)),
+            ]
+        );
+    }
+
+    #[test]
+    fn type_b_offsets_use_the_smallest_encoding() {
+        let builder = |offsets: &[u32]| PrxBuilder {
+            mod_info_sh_name: "",
+            elf_bytes: Vec::new(),
+            header: Header::default(),
+            section_headers: Vec::new(),
+            program_headers: Vec::new(),
+            relocations: HashMap::from([(0, offsets.iter().map(|&o| rel(o, R_MIPS_32)).collect())]),
+            type_b_relocations: None,
+        };
+
+        let header_len = 4 + TYPE_B_FLAGS.len() + TYPE_B_TYPES.len() + 2;
+        let len = |offsets: &[u32]| builder(offsets).type_b_relocations(&[0], 0).len() - header_len;
+
+        // The command has 9 bits for a signed offset.
+        assert_eq!(len(&[0xFC]), 2);
+        assert_eq!(len(&[0x100]), 4);
+        assert_eq!(len(&[0x100, 0x0]), 6);
+        assert_eq!(len(&[0x00FF_FFFC]), 4);
+        assert_eq!(len(&[0x0100_0000]), 6);
+    }
+}
@@ -155,7 +155,7 @@ fn main() {
         if align > 0 {
             // Add padding.
             let padding = (align - ((body.len() + DATA_OFFSET) & (align - 1))) & (align - 1);
-            let padding = iter::repeat(0).take(padding);
+            let padding = iter::repeat_n(0, padding);
             body.extend(padding);
         }
 
@@ -0,0 +1,178 @@
+use clap::Parser;
+use std::{convert::TryInto, fs, mem, path::PathBuf, process};
+
+const SIGNATURE: [u8; 4] = *b"\0PBP";
+const VERSION: u32 = 0x1_0000;
+
+/// File names of the PBP components, in the order they appear in the header.
+const FILE_NAMES: [&str; 8] = [
+    "PARAM.SFO",
+    "ICON0.PNG",
+    "ICON1.PMF",
+    "PIC0.PNG",
+    "PIC1.PNG",
+    "SND0.AT3",
+    "DATA.PSP",
+    "DATA.PSAR",
+];
+
+struct PbpHeader {
+    signature: [u8; 4],
+    version: u32,
+    offsets: [u32; 8],
+}
+
+impl PbpHeader {
+    fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        let bytes = bytes.get(..mem::size_of::<Self>())?;
+        let word = |idx: usize| u32::from_le_bytes(bytes[idx..idx + 4].try_into().unwrap());
+
+        let mut offsets = [0; 8];
+        for (i, offset) in offsets.iter_mut().enumerate() {
+            *offset = word(i * 4 + 8);
+        }
+
+        Some(Self {
+            signature: bytes[0..4].try_into().unwrap(),
+            version: word(4),
+            offsets,
+        })
+    }
+}
+
+#[derive(Parser, Debug)]
+#[command(
+    name = "unpack-pbp",
+    author = "Marko Mijalkovic <marko.mijalkovic97@gmail.com>",
+    version = "0.1",
+    about = "Extract Sony PSP packages"
+)]
+struct Args {
+    #[arg(name = "input.pbp", help = "Input PBP file")]
+    input: PathBuf,
+    #[arg(
+        name = "output_dir",
+        default_value = ".",
+        help = "Directory to extract the components into"
+    )]
+    output_dir: PathBuf,
+}
+
+fn main() {
+    let args = Args::parse();
+
+    let bytes = match fs::read(&args.input) {
+        Ok(bytes) => bytes,
+        Err(err) => panic!("failed to read {}: {}", args.input.display(), err),
+    };
+
+    let header = match PbpHeader::from_bytes(&bytes) {
+        Some(header) => header,
+        None => {
+            eprintln!("{} is too small to be a PBP file", args.input.display());
+            process::exit(1);
+        }
+    };
+
+    if header.signature != SIGNATURE {
+        eprintln!(
+            "{} is not a PBP file (bad signature {:02x?})",
+            args.input.display(),
+            header.signature
+        );
+        process::exit(1);
+    }
+
+    if header.version != VERSION {
+        eprintln!(
+            "unsupported PBP version {:#x}, expected {:#x}",
+            header.version, VERSION
+        );
+        process::exit(1);
+    }
+
+    let file_len = bytes.len() as u32;
+    let header_len = mem::size_of::<PbpHeader>() as u32;
+    let mut consistent = true;
+
+    // `pack-pbp` places the first component directly after the header.
+    if header.offsets[0] != header_len {
+        eprintln!(
+            "warning: {} starts at {:#x}, expected {:#x} (directly after the header)",
+            FILE_NAMES[0], header.offsets[0], header_len
+        );
+        consistent = false;
+    }
+
+    // Start and end offsets of each component, clamped to sane values.
+    let mut ranges = [(0, 0); 8];
+
+    for (i, name) in FILE_NAMES.iter().enumerate() {
+        let start = header.offsets[i];
+        let end = header.offsets.get(i + 1).copied().unwrap_or(file_len);
+
+        if start < header_len {
+            eprintln!("warning: {name} offset {start:#x} points into the PBP header");
+            consistent = false;
+        }
+
+        if start > file_len {
+            eprintln!(
+                "warning: {name} offset {start:#x} is past the end of the file ({file_len:#x})"
+            );
+            consistent = false;
+        }
+
+        if end < start {
+            eprintln!(
+                "warning: {name} ends at {end:#x}, before its start offset {start:#x}; \
+                 treating it as empty"
+            );
+            consistent = false;
+        }
+
+        let start = start.clamp(header_len, file_len);
+        let end = end.clamp(start, file_len);
+        ranges[i] = (start as usize, end as usize);
+    }
+
+    if let Err(err) = fs::create_dir_all(&args.output_dir) {
+        panic!("couldn't create {}: {}", args.output_dir.display(), err);
+    }
+
+    let mut pack_args = Vec::new();
+
+    for (name, (start, end)) in FILE_NAMES.iter().zip(ranges) {
+        if start == end {
+            pack_args.push("NULL".to_string());
+            continue;
+        }
+
+        let path = args.output_dir.join(name);
+
+        if let Err(err) = fs::write(&path, &bytes[start..end]) {
+            panic!("couldn't write to {}: {}", path.display(), err);
+        }
+
+        println!("{name:>10}: {:#010x} {:>10} bytes", start, end - start);
+        pack_args.push(path.display().to_string());
+    }
+
+    if consistent {
+        // Use a new name, so that repacking into the current directory does
+        // not overwrite the input.
+        let stem = args.input.file_stem().unwrap_or("EBOOT".as_ref());
+        let output = args
+            .output_dir
+            .join(format!("{}.repacked.PBP", stem.to_string_lossy()));
+
+        println!(
+            "Repack with: pack-pbp {} {}",
+            output.display(),
+            pack_args.join(" ")
+        );
+    } else {
+        eprintln!("PBP offsets are inconsistent, repacking will not be byte-for-byte identical");
+        process::exit(1);
+    }
+}
@@ -0,0 +1,461 @@
+use std::{collections::HashMap, convert::TryFrom, fmt, fs, io::ErrorKind, process};
+
+pub const CONFIG_NAME: &str = "Psp.toml";
+
+/// Dimensions of the XMB icon (`ICON0.PNG`).
+const ICON_SIZE: (u32, u32) = (144, 80);
+
+/// Dimensions of the XMB backgrounds (`PIC0.PNG` and `PIC1.PNG`).
+const BACKGROUND_SIZE: (u32, u32) = (480, 272);
+
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+#[derive(serde_derive::Deserialize, Default, Clone)]
+pub struct PspConfig {
+    /// Title shown in the XMB menu.
+    pub title: Option<String>,
+
+    /// Path to 24bit 144x80 PNG icon shown in the XMB menu.
+    pub xmb_icon_png: Option<String>,
+
+    /// Path to animated icon shown in the XMB menu.
+    ///
+    /// The PSP expects a 29.97fps 144x80 PMF video file (custom Sony format).
+    pub xmb_icon_pmf: Option<String>,
+
+    /// Path to 24bit 480x272 PNG background shown in the XMB menu.
+    pub xmb_background_png: Option<String>,
+
+    /// Overlay background shown in the XMB menu.
+    ///
+    /// Exactly like `xmb_background_png`, but it is overlayed on top.
+    pub xmb_background_overlay_png: Option<String>,
+
+    /// Path to ATRAC3 audio file played in the XMB menu.
+    ///
+    /// Must be 66kbps, under 500KB and under 55 seconds.
+    pub xmb_music_at3: Option<String>,
+
+    /// Path to associated PSAR data stored in the EBOOT.
+    pub psar: Option<String>,
+
+    /// Product number of the game, in the format `ABCD-12345`.
+    ///
+    /// The dash is optional, and is removed when writing PARAM.SFO.
+    ///
+    /// Example: UCJS-10001
+    pub disc_id: Option<String>,
+
+    /// Version of the game, e.g. "1.00".
+    pub disc_version: Option<String>,
+
+    /// Language of the game.
+    pub language: Option<Language>,
+
+    /// Parental Control level needed to access the file.
+    pub parental_level: Option<ParentalLevel>,
+
+    /// PSP Firmware Version required by the game (e.g. "6.61").
+    pub psp_system_ver: Option<String>,
+
+    /// Allowed regions. Either `"all"`, or a raw bitmask.
+    pub region: Option<Region>,
+
+    /// Japanese localized title.
+    pub title_jp: Option<String>,
+
+    /// French localized title.
+    pub title_fr: Option<String>,
+
+    /// Spanish localized title.
+    pub title_es: Option<String>,
+
+    /// German localized title.
+    pub title_de: Option<String>,
+
+    /// Italian localized title.
+    pub title_it: Option<String>,
+
+    /// Dutch localized title.
+    pub title_nl: Option<String>,
+
+    /// Portugese localized title.
+    pub title_pt: Option<String>,
+
+    /// Russian localized title.
+    pub title_ru: Option<String>,
+
+    /// Used by the firmware updater to denote the firmware version it updates to.
+    pub updater_version: Option<String>,
+
+    /// Per-executable overrides, in `[bin.<name>]` sections.
+    ///
+    /// Any key set here takes precedence over the top level value when
+    /// packing the executable named `<name>`.
+    #[serde(default)]
+    pub bin: HashMap<String, PspConfig>,
+
+    /// Keys that are not recognized, reported as warnings when loading.
+    #[serde(flatten)]
+    unknown: HashMap<String, toml::Value>,
+}
+
+/// Language of the game, as stored in the `LANGUAGE` SFO key.
+///
+/// "JP" indicates Japanese, even though this is not the proper ISO 639 code...
+#[derive(serde_derive::Deserialize, Clone, Copy, Debug)]
+pub enum Language {
+    #[serde(rename = "JP")]
+    Japanese,
+    #[serde(rename = "EN")]
+    English,
+    #[serde(rename = "FR")]
+    French,
+    #[serde(rename = "ES")]
+    Spanish,
+    #[serde(rename = "DE")]
+    German,
+    #[serde(rename = "IT")]
+    Italian,
+    #[serde(rename = "NL")]
+    Dutch,
+    #[serde(rename = "PT")]
+    Portuguese,
+    #[serde(rename = "RU")]
+    Russian,
+    #[serde(rename = "KO")]
+    Korean,
+    #[serde(rename = "CH")]
+    ChineseTraditional,
+    #[serde(rename = "ZH")]
+    ChineseSimplified,
+}
+
+impl Language {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Language::Japanese => "JP",
+            Language::English => "EN",
+            Language::French => "FR",
+            Language::Spanish => "ES",
+            Language::German => "DE",
+            Language::Italian => "IT",
+            Language::Dutch => "NL",
+            Language::Portuguese => "PT",
+            Language::Russian => "RU",
+            Language::Korean => "KO",
+            Language::ChineseTraditional => "CH",
+            Language::ChineseSimplified => "ZH",
+        }
+    }
+}
+
+/// Regions the game may run in, as stored in the `REGION` SFO key.
+#[derive(serde_derive::Deserialize, Clone, Copy, Debug)]
+#[serde(untagged)]
+pub enum Region {
+    Named(RegionName),
+
+    /// A raw region bitmask.
+    Mask(u32),
+}
+
+#[derive(serde_derive::Deserialize, Clone, Copy, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum RegionName {
+    /// No region restrictions (`0x8000`).
+    All,
+}
+
+impl Region {
+    pub fn mask(self) -> u32 {
+        match self {
+            Region::Named(RegionName::All) => 0x8000,
+            Region::Mask(mask) => mask,
+        }
+    }
+}
+
+/// Parental Control level needed to access the file. 1-11
+/// - 1 = General audience
+/// - 5 = 12 year old
+/// - 7 = 15 year old
+/// - 9 = 18 year old
+#[derive(serde_derive::Deserialize, Clone, Copy, Debug)]
+#[serde(try_from = "u32")]
+pub struct ParentalLevel(u32);
+
+impl ParentalLevel {
+    pub fn level(self) -> u32 {
+        self.0
+    }
+}
+
+impl TryFrom<u32> for ParentalLevel {
+    type Error = String;
+
+    fn try_from(level: u32) -> Result<Self, Self::Error> {
+        if (1..=11).contains(&level) {
+            Ok(Self(level))
+        } else {
+            Err(format!(
+                "invalid parental level {}, expected 1 (general audience) to 11",
+                level
+            ))
+        }
+    }
+}
+
+/// A problem found while validating the configuration.
+pub struct ConfigError {
+    section: String,
+    key: &'static str,
+    message: String,
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}{}: {}", self.section, self.key, self.message)
+    }
+}
+
+/// Read the width and height of a PNG file.
+fn png_dimensions(path: &str) -> Result<(u32, u32), String> {
+    let bytes = fs::read(path).map_err(|e| format!("failed to read {}: {}", path, e))?;
+
+    // The IHDR chunk always comes first, directly after the signature.
+    if !bytes.starts_with(PNG_SIGNATURE) || bytes.get(12..16) != Some(b"IHDR") {
+        return Err(format!("{} is not a PNG file", path));
+    }
+
+    let word = |idx: usize| {
+        u32::from_be_bytes([bytes[idx], bytes[idx + 1], bytes[idx + 2], bytes[idx + 3]])
+    };
+
+    bytes
+        .get(16..24)
+        .map(|_| (word(16), word(20)))
+        .ok_or_else(|| format!("{} is truncated", path))
+}
+
+/// Check whether a disc ID is in the `ABCD-12345` or `ABCD12345` format.
+fn valid_disc_id(id: &str) -> bool {
+    let bytes = id.as_bytes();
+
+    let (letters, digits) = match bytes.len() {
+        9 => (&bytes[..4], &bytes[4..]),
+        10 if bytes[4] == b'-' => (&bytes[..4], &bytes[5..]),
+        _ => return false,
+    };
+
+    letters.iter().all(u8::is_ascii_uppercase) && digits.iter().all(u8::is_ascii_digit)
+}
+
+impl PspConfig {
+    /// Load `Psp.toml` from the current directory, exiting with an error
+    /// message if it is malformed.
+    pub fn load() -> Self {
+        match fs::read_to_string(CONFIG_NAME) {
+            Ok(value) => match toml::from_str::<PspConfig>(&value) {
+                Ok(config) => {
+                    config.warn_unknown_keys();
+                    config
+                }
+                Err(e) => {
+                    println!("Failed to read Psp.toml: {}", e);
+                    println!("Please ensure that it is formatted correctly.");
+                    process::exit(1);
+                }
+            },
+            Err(e) if e.kind() == ErrorKind::NotFound => PspConfig::default(),
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    /// Print a warning for every key that is not recognized, which is most
+    /// likely a typo.
+    fn warn_unknown_keys(&self) {
+        let mut sections = vec![(String::new(), self)];
+        sections.extend(
+            self.bin
+                .iter()
+                .map(|(name, c)| (format!("[bin.{}] ", name), c)),
+        );
+        sections.sort_by(|a, b| a.0.cmp(&b.0));
+
+        for (section, config) in sections {
+            let mut keys = config.unknown.keys().collect::<Vec<_>>();
+            keys.sort();
+
+            for key in keys {
+                eprintln!(
+                    "warning: {}{}: unknown key in {}",
+                    section, key, CONFIG_NAME
+                );
+            }
+        }
+    }
+
+    /// The configuration for a single executable, with its `[bin.<name>]`
+    /// overrides applied.
+    pub fn for_bin(&self, name: &str) -> PspConfig {
+        let over = match self.bin.get(name) {
+            Some(over) => over,
+            None => {
+                return PspConfig {
+                    bin: HashMap::new(),
+                    unknown: HashMap::new(),
+                    ..self.clone()
+                }
+            }
+        };
+
+        macro_rules! merge {
+            ($($field:ident),* $(,)?) => {
+                PspConfig {
+                    $($field: over.$field.clone().or_else(|| self.$field.clone()),)*
+                    bin: HashMap::new(),
+                    unknown: HashMap::new(),
+                }
+            };
+        }
+
+        merge! {
+            title,
+            xmb_icon_png,
+            xmb_icon_pmf,
+            xmb_background_png,
+            xmb_background_overlay_png,
+            xmb_music_at3,
+            psar,
+            disc_id,
+            disc_version,
+            language,
+            parental_level,
+            psp_system_ver,
+            region,
+            title_jp,
+            title_fr,
+            title_es,
+            title_de,
+            title_it,
+            title_nl,
+            title_pt,
+            title_ru,
+            updater_version,
+        }
+    }
+
+    /// The disc ID as stored in PARAM.SFO, without the dash.
+    pub fn sfo_disc_id(&self) -> Option<String> {
+        self.disc_id.as_ref().map(|id| id.replacen('-', "", 1))
+    }
+
+    /// Validate this configuration and every `[bin.<name>]` section.
+    pub fn validate(&self) -> Vec<ConfigError> {
+        let mut errors = self.validate_section("");
+
+        let mut names = self.bin.keys().collect::<Vec<_>>();
+        names.sort();
+
+        for name in names {
+            let section = format!("[bin.{}] ", name);
+
+            if !self.bin[name].bin.is_empty() {
+                errors.push(ConfigError {
+                    section: section.clone(),
+                    key: "bin",
+                    message: "[bin] sections cannot be nested".into(),
+                });
+            }
+
+            errors.extend(self.bin[name].validate_section(&section));
+        }
+
+        errors
+    }
+
+    fn validate_section(&self, section: &str) -> Vec<ConfigError> {
+        let mut errors = Vec::new();
+        let mut error = |key, message| {
+            errors.push(ConfigError {
+                section: section.to_string(),
+                key,
+                message,
+            })
+        };
+
+        if let Some(id) = &self.disc_id {
+            if !valid_disc_id(id) {
+                error(
+                    "disc_id",
+                    format!("`{}` is not in the `ABCD-12345` format", id),
+                );
+            }
+        }
+
+        let images = [
+            ("xmb_icon_png", &self.xmb_icon_png, ICON_SIZE),
+            (
+                "xmb_background_png",
+                &self.xmb_background_png,
+                BACKGROUND_SIZE,
+            ),
+            (
+                "xmb_background_overlay_png",
+                &self.xmb_background_overlay_png,
+                BACKGROUND_SIZE,
+            ),
+        ];
+
+        for (key, path, expected) in images {
+            let Some(path) = path else { continue };
+
+            match png_dimensions(path) {
+                Ok(size) if size != expected => error(
+                    key,
+                    format!(
+                        "{} is {}x{}, but must be {}x{}",
+                        path, size.0, size.1, expected.0, expected.1
+                    ),
+                ),
+                Ok(_) => {}
+                Err(e) => error(key, e),
+            }
+        }
+
+        let files = [
+            ("xmb_icon_pmf", &self.xmb_icon_pmf),
+            ("xmb_music_at3", &self.xmb_music_at3),
+            ("psar", &self.psar),
+        ];
+
+        for (key, path) in files {
+            if let Some(path) = path {
+                if let Err(e) = fs::metadata(path) {
+                    error(key, format!("failed to read {}: {}", path, e));
+                }
+            }
+        }
+
+        errors
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unknown_keys_are_collected() {
+        let config: PspConfig =
+            toml::from_str("title = \"a\"\ntitel = \"b\"\n[bin.x]\nfoo = 1\ntitle = \"c\"\n")
+                .unwrap();
+
+        assert_eq!(config.title.as_deref(), Some("a"));
+        assert!(config.unknown.contains_key("titel"));
+        assert!(config.bin["x"].unknown.contains_key("foo"));
+        assert_eq!(config.for_bin("x").title.as_deref(), Some("c"));
+        assert!(config.for_bin("x").unknown.is_empty());
+    }
+}
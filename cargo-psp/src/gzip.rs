@@ -0,0 +1,118 @@
+//! gzip (RFC 1952) decompression, used for compressed `~PSP` modules.
+
+use miniz_oxide::inflate::{
+    core::{
+        decompress, inflate_flags::TINFL_FLAG_USING_NON_WRAPPING_OUTPUT_BUF, DecompressorOxide,
+    },
+    TINFLStatus,
+};
+use std::convert::TryInto;
+
+const GZIP_MAGIC: [u8; 2] = [0x1F, 0x8B];
+const GZIP_METHOD_DEFLATE: u8 = 8;
+
+const FLAG_HCRC: u8 = 0x02;
+const FLAG_EXTRA: u8 = 0x04;
+const FLAG_NAME: u8 = 0x08;
+const FLAG_COMMENT: u8 = 0x10;
+
+pub fn is_gzip(bytes: &[u8]) -> bool {
+    bytes.starts_with(&GZIP_MAGIC)
+}
+
+/// Decompress a gzip member, checking its CRC and size.
+pub fn gunzip(bytes: &[u8]) -> Result<Vec<u8>, String> {
+    let header = bytes.get(..10).ok_or("truncated gzip header")?;
+
+    if !is_gzip(header) || header[2] != GZIP_METHOD_DEFLATE {
+        return Err("not a gzip stream".into());
+    }
+
+    let flags = header[3];
+    let mut pos = 10;
+
+    let skip_string = |pos: usize| -> Result<usize, String> {
+        let len = bytes
+            .get(pos..)
+            .and_then(|b| b.iter().position(|&b| b == 0))
+            .ok_or("truncated gzip header")?;
+        Ok(pos + len + 1)
+    };
+
+    if flags & FLAG_EXTRA != 0 {
+        let len = bytes.get(pos..pos + 2).ok_or("truncated gzip header")?;
+        pos += 2 + u16::from_le_bytes([len[0], len[1]]) as usize;
+    }
+    if flags & FLAG_NAME != 0 {
+        pos = skip_string(pos)?;
+    }
+    if flags & FLAG_COMMENT != 0 {
+        pos = skip_string(pos)?;
+    }
+    if flags & FLAG_HCRC != 0 {
+        pos += 2;
+    }
+
+    let body = bytes.get(pos..).ok_or("truncated gzip header")?;
+    let (output, used) = inflate(body)?;
+
+    let trailer = body.get(used..used + 8).ok_or("truncated gzip trailer")?;
+    let word = |idx: usize| u32::from_le_bytes(trailer[idx..idx + 4].try_into().unwrap());
+
+    if word(0) != crc32(&output) {
+        return Err("gzip CRC mismatch".into());
+    }
+
+    if word(4) != output.len() as u32 {
+        return Err("gzip size mismatch".into());
+    }
+
+    Ok(output)
+}
+
+/// Decompress a raw DEFLATE stream, returning the output and the number of
+/// bytes used, so that the gzip trailer can be found.
+fn inflate(bytes: &[u8]) -> Result<(Vec<u8>, usize), String> {
+    let mut decompressor = DecompressorOxide::new();
+    let mut output = vec![0; (bytes.len() * 4).max(1024)];
+    let (mut read, mut written) = (0, 0);
+
+    loop {
+        let (status, in_used, out_used) = decompress(
+            &mut decompressor,
+            &bytes[read..],
+            &mut output,
+            written,
+            TINFL_FLAG_USING_NON_WRAPPING_OUTPUT_BUF,
+        );
+        read += in_used;
+        written += out_used;
+
+        match status {
+            TINFLStatus::Done => break,
+            TINFLStatus::HasMoreOutput => output.resize(output.len() * 2, 0),
+            TINFLStatus::NeedsMoreInput | TINFLStatus::FailedCannotMakeProgress => {
+                return Err("unexpected end of data".into())
+            }
+            _ => return Err("invalid DEFLATE data".into()),
+        }
+    }
+
+    output.truncate(written);
+
+    Ok((output, read))
+}
+
+/// The CRC-32 of `bytes`, as stored in the gzip trailer.
+pub fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+
+    for &byte in bytes {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = (crc >> 1) ^ (0xEDB8_8320 & (crc & 1).wrapping_neg());
+        }
+    }
+
+    !crc
+}
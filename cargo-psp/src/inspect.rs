@@ -0,0 +1,427 @@
+use crate::nid_db;
+use clap::Parser;
+use goblin::elf::{program_header::PT_LOAD, Elf};
+use std::{collections::HashMap, convert::TryInto, fs, path::PathBuf, process};
+
+const PBP_SIGNATURE: &[u8] = b"\0PBP";
+const PBP_DATA_PSP_IDX: usize = 6;
+const ENCRYPTED_SIGNATURE: &[u8] = b"~PSP";
+
+const MODULE_INFO_SECTION: &str = ".rodata.sceModuleInfo";
+const NID_SECTION: &str = ".rodata.sceNid";
+
+/// NIDs of the special exports found in the unnamed system library.
+const SYSLIB_NIDS: [(u32, &str); 8] = [
+    (0xD632ACDB, "module_start"),
+    (0xCEE8593C, "module_stop"),
+    (0xF01D73A7, "module_info"),
+    (0xD3744BE0, "module_bootstart"),
+    (0x2F064FA6, "module_reboot_before"),
+    (0x0F7C276C, "module_start_thread_parameter"),
+    (0xCF0CC697, "module_stop_thread_parameter"),
+    (0x11B97506, "module_sdk_version"),
+];
+
+/// Module attribute bits, see `psp::sys::ModuleInfoAttr`.
+const MODULE_ATTRIBUTES: [(u16, &str); 4] = [
+    (0x0001, "no_stop"),
+    (0x0002, "single_load"),
+    (0x0004, "single_start"),
+    (0x1000, "kernel"),
+];
+
+#[derive(Parser, Debug)]
+#[command(
+    name = "cargo psp inspect",
+    about = "Print the module info, exports and imports of a PSP module"
+)]
+struct Args {
+    #[arg(help = "ELF, PRX or PBP file to inspect")]
+    file: PathBuf,
+    #[arg(
+        long,
+        value_name = "DIR",
+        help = "Path to `psp/src/sys`, used to name NIDs (default: found with `cargo metadata`)"
+    )]
+    psp_sys: Option<PathBuf>,
+}
+
+/// An ELF image, with helpers to read data at virtual addresses.
+struct Image<'a> {
+    bytes: &'a [u8],
+    elf: Elf<'a>,
+}
+
+impl<'a> Image<'a> {
+    fn offset_of(&self, addr: u32) -> Option<usize> {
+        self.elf
+            .program_headers
+            .iter()
+            .filter(|ph| ph.p_type == PT_LOAD)
+            .find(|ph| ph.p_vaddr <= addr as u64 && (addr as u64) < ph.p_vaddr + ph.p_filesz)
+            .map(|ph| (ph.p_offset + addr as u64 - ph.p_vaddr) as usize)
+    }
+
+    fn read(&self, addr: u32, len: usize) -> Option<&'a [u8]> {
+        let offset = self.offset_of(addr)?;
+        self.bytes.get(offset..offset + len)
+    }
+
+    fn read_u32(&self, addr: u32) -> Option<u32> {
+        self.read(addr, 4)
+            .map(|b| u32::from_le_bytes(b.try_into().unwrap()))
+    }
+
+    fn read_str(&self, addr: u32) -> Option<String> {
+        let offset = self.offset_of(addr)?;
+        let bytes = self.bytes.get(offset..)?;
+        let bytes = bytes.split(|&b| b == 0).next()?;
+
+        Some(String::from_utf8_lossy(bytes).into_owned())
+    }
+
+    fn section(&self, name: &str) -> Option<&goblin::elf::SectionHeader> {
+        self.elf
+            .section_headers
+            .iter()
+            .find(|sh| self.elf.shdr_strtab.get_at(sh.sh_name) == Some(name))
+    }
+}
+
+/// A cross-platform copy of `psp::sys::SceModuleInfo`.
+struct ModuleInfo {
+    attribute: u16,
+    version: [u8; 2],
+    name: String,
+    gp_value: u32,
+    ent_top: u32,
+    ent_end: u32,
+    stub_top: u32,
+    stub_end: u32,
+}
+
+impl ModuleInfo {
+    const SIZE: usize = 52;
+
+    fn parse(bytes: &[u8]) -> Self {
+        let word = |idx: usize| u32::from_le_bytes(bytes[idx..idx + 4].try_into().unwrap());
+        let name = bytes[4..31].split(|&b| b == 0).next().unwrap();
+
+        Self {
+            attribute: u16::from_le_bytes([bytes[0], bytes[1]]),
+            version: [bytes[2], bytes[3]],
+            name: String::from_utf8_lossy(name).into_owned(),
+            gp_value: word(32),
+            ent_top: word(36),
+            ent_end: word(40),
+            stub_top: word(44),
+            stub_end: word(48),
+        }
+    }
+}
+
+/// A cross-platform copy of `psp::sys::SceLibraryEntry`.
+struct LibraryEntry {
+    name: u32,
+    version: [u8; 2],
+    attribute: u16,
+    entry_len: u8,
+    var_count: u8,
+    func_count: u16,
+    entry_table: u32,
+}
+
+impl LibraryEntry {
+    fn parse(bytes: &[u8]) -> Self {
+        let word = |idx: usize| u32::from_le_bytes(bytes[idx..idx + 4].try_into().unwrap());
+
+        Self {
+            name: word(0),
+            version: [bytes[4], bytes[5]],
+            attribute: u16::from_le_bytes([bytes[6], bytes[7]]),
+            entry_len: bytes[8],
+            var_count: bytes[9],
+            func_count: u16::from_le_bytes([bytes[10], bytes[11]]),
+            entry_table: word(12),
+        }
+    }
+}
+
+/// A cross-platform copy of `psp::sys::SceStubLibraryEntry`.
+struct StubLibraryEntry {
+    name: u32,
+    version: [u8; 2],
+    flags: u16,
+    len: u8,
+    stub_count: u16,
+    nid_table: u32,
+}
+
+impl StubLibraryEntry {
+    fn parse(bytes: &[u8]) -> Self {
+        let word = |idx: usize| u32::from_le_bytes(bytes[idx..idx + 4].try_into().unwrap());
+
+        Self {
+            name: word(0),
+            version: [bytes[4], bytes[5]],
+            flags: u16::from_le_bytes([bytes[6], bytes[7]]),
+            len: bytes[8],
+            stub_count: u16::from_le_bytes([bytes[10], bytes[11]]),
+            nid_table: word(12),
+        }
+    }
+}
+
+/// Print an error and exit.
+fn fail(msg: impl std::fmt::Display) -> ! {
+    eprintln!("error: {}", msg);
+    process::exit(1);
+}
+
+/// Find the embedded DATA.PSP if this is a PBP file.
+fn unwrap_pbp(bytes: &[u8]) -> &[u8] {
+    if !bytes.starts_with(PBP_SIGNATURE) {
+        return bytes;
+    }
+
+    let offset = |i: usize| {
+        bytes
+            .get(8 + i * 4..12 + i * 4)
+            .map(|b| u32::from_le_bytes(b.try_into().unwrap()) as usize)
+            .unwrap_or_else(|| fail("truncated PBP header"))
+    };
+
+    let start = offset(PBP_DATA_PSP_IDX);
+    let end = offset(PBP_DATA_PSP_IDX + 1);
+
+    bytes
+        .get(start..end)
+        .unwrap_or_else(|| fail("DATA.PSP offsets in the PBP header are out of bounds"))
+}
+
+fn attribute_names(attribute: u16) -> String {
+    let names = MODULE_ATTRIBUTES
+        .iter()
+        .filter(|(bit, _)| attribute & bit != 0)
+        .map(|(_, name)| *name)
+        .collect::<Vec<_>>();
+
+    if names.is_empty() {
+        "user".into()
+    } else {
+        names.join(", ")
+    }
+}
+
+pub fn main(args: impl Iterator<Item = String>) {
+    let args = Args::parse_from(args);
+
+    let bytes = fs::read(&args.file)
+        .unwrap_or_else(|e| fail(format!("failed to read {}: {}", args.file.display(), e)));
+    let bytes = unwrap_pbp(&bytes);
+
+    if bytes.starts_with(ENCRYPTED_SIGNATURE) {
+        fail("module is encrypted (~PSP header), decrypt it with `decrypt-prx` before inspecting");
+    }
+
+    let elf = Elf::parse(bytes).unwrap_or_else(|e| fail(format!("invalid ELF: {}", e)));
+    let image = Image { bytes, elf };
+
+    let libraries = args
+        .psp_sys
+        .or_else(nid_db::locate_sys_dir)
+        .and_then(|dir| nid_db::parse_dir(&dir).ok())
+        .unwrap_or_default();
+
+    if libraries.is_empty() {
+        eprintln!("note: psp crate sources not found, NIDs will not be named");
+    }
+
+    let nids = nid_db::nid_map(&libraries);
+
+    // Prefer the section, but fall back to `p_paddr` which holds the file
+    // offset of the module info in PRX files (with the kernel bit).
+    let module_info = match image.section(MODULE_INFO_SECTION) {
+        Some(sh) => image.bytes.get(sh.sh_offset as usize..),
+        None => image
+            .elf
+            .program_headers
+            .first()
+            .and_then(|ph| image.bytes.get((ph.p_paddr & 0x7fff_ffff) as usize..)),
+    }
+    .and_then(|b| b.get(..ModuleInfo::SIZE))
+    .map(ModuleInfo::parse)
+    .unwrap_or_else(|| fail("could not find module info"));
+
+    println!("Module:     {}", module_info.name);
+    println!(
+        "Version:    {}.{}",
+        module_info.version[0], module_info.version[1]
+    );
+    println!(
+        "Attributes: {:#06x} ({})",
+        module_info.attribute,
+        attribute_names(module_info.attribute)
+    );
+    println!("GP value:   {:#010x}", module_info.gp_value);
+
+    print_exports(&image, &module_info);
+    let warnings = print_imports(&image, &module_info, &nids);
+
+    if warnings > 0 {
+        process::exit(1);
+    }
+}
+
+fn print_exports(image: &Image, module_info: &ModuleInfo) {
+    println!();
+    println!("Exports:");
+
+    let mut addr = module_info.ent_top;
+
+    while addr < module_info.ent_end {
+        let Some(entry) = image.read(addr, 16).map(LibraryEntry::parse) else {
+            eprintln!("warning: export entry at {:#010x} is out of bounds", addr);
+            break;
+        };
+
+        if entry.entry_len < 4 {
+            eprintln!(
+                "warning: export entry at {:#010x} has invalid length {}",
+                addr, entry.entry_len
+            );
+            break;
+        }
+
+        let name = match entry.name {
+            0 => "<syslib>".to_string(),
+            name => image.read_str(name).unwrap_or_else(|| "<invalid>".into()),
+        };
+
+        println!(
+            "  {} v{}.{} attr {:#06x}: {} functions, {} variables",
+            name,
+            entry.version[1],
+            entry.version[0],
+            entry.attribute,
+            entry.func_count,
+            entry.var_count
+        );
+
+        // The table holds all NIDs, followed by all addresses.
+        let count = entry.func_count as u32 + entry.var_count as u32;
+
+        for i in 0..count {
+            let nid = image.read_u32(entry.entry_table + i * 4);
+            let value = image.read_u32(entry.entry_table + (count + i) * 4);
+
+            let (Some(nid), Some(value)) = (nid, value) else {
+                eprintln!("warning: export table of {} is out of bounds", name);
+                break;
+            };
+
+            let known = SYSLIB_NIDS
+                .iter()
+                .find(|(n, _)| *n == nid)
+                .filter(|_| entry.name == 0)
+                .map(|(_, name)| *name)
+                .unwrap_or("");
+
+            let kind = if i < entry.func_count as u32 {
+                "func"
+            } else {
+                "var "
+            };
+
+            let line = format!("    {:#010x} {} {:#010x} {}", nid, kind, value, known);
+            println!("{}", line.trim_end());
+        }
+
+        addr += entry.entry_len as u32 * 4;
+    }
+}
+
+/// Print every imported library, returning the number of problems found.
+fn print_imports(
+    image: &Image,
+    module_info: &ModuleInfo,
+    nids: &HashMap<(&str, u32), &str>,
+) -> usize {
+    println!();
+    println!("Imports:");
+
+    let mut stubs = Vec::new();
+    let mut addr = module_info.stub_top;
+
+    while addr < module_info.stub_end {
+        let Some(stub) = image.read(addr, 20).map(StubLibraryEntry::parse) else {
+            eprintln!("warning: import entry at {:#010x} is out of bounds", addr);
+            break;
+        };
+
+        if stub.len < 5 {
+            eprintln!(
+                "warning: import entry at {:#010x} has invalid length {}",
+                addr, stub.len
+            );
+            break;
+        }
+
+        addr += stub.len as u32 * 4;
+        stubs.push(stub);
+    }
+
+    // The expected stub count is derived the same way `fix_imports` does it,
+    // from the distance between consecutive NID tables.
+    let nid_end = image
+        .section(NID_SECTION)
+        .map(|sh| (sh.sh_addr + sh.sh_size) as u32);
+    let mut nid_tables = stubs.iter().map(|s| s.nid_table).collect::<Vec<_>>();
+    nid_tables.sort_unstable();
+
+    let mut warnings = 0;
+
+    for stub in &stubs {
+        let name = image
+            .read_str(stub.name)
+            .unwrap_or_else(|| "<invalid>".into());
+
+        println!(
+            "  {} v{}.{} flags {:#06x}: {} stubs",
+            name, stub.version[1], stub.version[0], stub.flags, stub.stub_count
+        );
+
+        let expected = nid_tables
+            .iter()
+            .find(|&&t| t > stub.nid_table)
+            .copied()
+            .or(nid_end)
+            .map(|end| end.saturating_sub(stub.nid_table) / 4);
+
+        if let Some(expected) = expected.filter(|&e| e != stub.stub_count as u32) {
+            eprintln!(
+                "warning: {} declares {} stubs, but its NID table holds {}",
+                name, stub.stub_count, expected
+            );
+            warnings += 1;
+        }
+
+        for i in 0..stub.stub_count as u32 {
+            let Some(nid) = image.read_u32(stub.nid_table + i * 4) else {
+                eprintln!("warning: NID table of {} is out of bounds", name);
+                warnings += 1;
+                break;
+            };
+
+            let function = match nids.get(&(name.as_str(), nid)) {
+                Some(function) => function,
+                None if nids.is_empty() => "",
+                None => "<unknown>",
+            };
+            let line = format!("    {:#010x} {}", nid, function);
+            println!("{}", line.trim_end());
+        }
+    }
+
+    warnings
+}
@@ -0,0 +1,210 @@
+//! The parts of the PSP's KIRK crypto engine used by `~PSP` modules, on top
+//! of the `aes`, `cbc` and `cmac` crates.
+
+use aes::Aes128;
+use cbc::cipher::{block_padding::NoPadding, BlockDecryptMut, BlockEncryptMut, KeyIvInit};
+use cmac::{Cmac, Mac};
+use std::convert::TryInto;
+
+/// The AES key used by KIRK command 1 to encrypt the per-module keys.
+pub const KIRK1_KEY: [u8; 16] = [
+    0x98, 0xC9, 0x40, 0x97, 0x5C, 0x1D, 0x10, 0xE8, 0x7F, 0xE6, 0x0E, 0xA3, 0xFD, 0x03, 0xA8, 0xBA,
+];
+
+/// Size of the header preceding the data of a KIRK command 1 block.
+pub const KIRK1_HEADER_SIZE: usize = 0x90;
+
+/// Mode value of a KIRK command 1 header.
+const KIRK_MODE_CMD1: u32 = 1;
+
+const BLOCK_SIZE: usize = 16;
+
+/// Encrypt `data` in place with AES-128-CBC and a zero IV, as KIRK does.
+///
+/// The length of `data` must be a multiple of 16.
+pub fn cbc_encrypt(key: &[u8; 16], data: &mut [u8]) {
+    let len = data.len();
+
+    cbc::Encryptor::<Aes128>::new(key.into(), &[0; 16].into())
+        .encrypt_padded_mut::<NoPadding>(data, len)
+        .expect("data is not a multiple of the block size");
+}
+
+/// Decrypt `data` in place with AES-128-CBC and a zero IV.
+///
+/// The length of `data` must be a multiple of 16.
+pub fn cbc_decrypt(key: &[u8; 16], data: &mut [u8]) {
+    cbc::Decryptor::<Aes128>::new(key.into(), &[0; 16].into())
+        .decrypt_padded_mut::<NoPadding>(data)
+        .expect("data is not a multiple of the block size");
+}
+
+/// Calculate the AES-CMAC (RFC 4493) of `data`.
+pub fn cmac(key: &[u8; 16], data: &[u8]) -> [u8; 16] {
+    let mut mac = <Cmac<Aes128> as Mac>::new(key.into());
+    mac.update(data);
+    mac.finalize().into_bytes().into()
+}
+
+/// Build a KIRK command 1 block: a 0x90 byte header followed by `data`,
+/// encrypted with `aes_key` and authenticated with `cmac_key`.
+///
+/// Both keys are stored in the header, encrypted with [`KIRK1_KEY`]. The
+/// header is laid out as follows:
+///
+/// | Offset | Size | Contents                                      |
+/// |--------|------|-----------------------------------------------|
+/// | 0x00   | 0x10 | AES key (encrypted)                           |
+/// | 0x10   | 0x10 | CMAC key (encrypted)                          |
+/// | 0x20   | 0x10 | CMAC of header bytes 0x60..0x90               |
+/// | 0x30   | 0x10 | CMAC of header bytes 0x60.. and the data      |
+/// | 0x60   | 4    | Mode, always 1                                |
+/// | 0x64   | 1    | ECDSA flag, always 0                          |
+/// | 0x70   | 4    | Data size                                     |
+/// | 0x74   | 4    | Data offset, always 0                         |
+pub fn kirk1_encrypt(data: &[u8], aes_key: &[u8; 16], cmac_key: &[u8; 16]) -> Vec<u8> {
+    let padded_len = data.len().div_ceil(BLOCK_SIZE) * BLOCK_SIZE;
+    let mut block = vec![0; KIRK1_HEADER_SIZE + padded_len];
+
+    block[0x00..0x10].copy_from_slice(aes_key);
+    block[0x10..0x20].copy_from_slice(cmac_key);
+    block[0x60..0x64].copy_from_slice(&KIRK_MODE_CMD1.to_le_bytes());
+    block[0x70..0x74].copy_from_slice(&(data.len() as u32).to_le_bytes());
+    block[KIRK1_HEADER_SIZE..][..data.len()].copy_from_slice(data);
+
+    cbc_encrypt(aes_key, &mut block[KIRK1_HEADER_SIZE..]);
+
+    let header_hash = cmac(cmac_key, &block[0x60..KIRK1_HEADER_SIZE]);
+    let data_hash = cmac(cmac_key, &block[0x60..]);
+    block[0x20..0x30].copy_from_slice(&header_hash);
+    block[0x30..0x40].copy_from_slice(&data_hash);
+
+    cbc_encrypt(&KIRK1_KEY, &mut block[..0x20]);
+
+    block
+}
+
+/// Check whether `block` starts with a KIRK command 1 header.
+pub fn is_kirk1(block: &[u8]) -> bool {
+    block.len() >= KIRK1_HEADER_SIZE
+        && block[0x60..0x64] == KIRK_MODE_CMD1.to_le_bytes()
+        && block[0x64] == 0
+}
+
+/// Verify and decrypt a KIRK command 1 block, as built by [`kirk1_encrypt`].
+///
+/// Only blocks signed with CMAC are supported, not those signed with ECDSA.
+pub fn kirk1_decrypt(block: &[u8]) -> Result<Vec<u8>, String> {
+    if !is_kirk1(block) {
+        return Err("not a CMAC signed KIRK command 1 block".into());
+    }
+
+    let word = |idx: usize| u32::from_le_bytes(block[idx..idx + 4].try_into().unwrap()) as usize;
+    let data_size = word(0x70);
+    let data_offset = word(0x74);
+
+    let start = KIRK1_HEADER_SIZE + data_offset;
+    let end = start + data_size.div_ceil(BLOCK_SIZE) * BLOCK_SIZE;
+    let encrypted = block.get(..end).ok_or("KIRK block is truncated")?;
+
+    let mut keys: [u8; 32] = block[..0x20].try_into().unwrap();
+    cbc_decrypt(&KIRK1_KEY, &mut keys);
+    let aes_key: [u8; 16] = keys[..16].try_into().unwrap();
+    let cmac_key: [u8; 16] = keys[16..].try_into().unwrap();
+
+    if cmac(&cmac_key, &block[0x60..KIRK1_HEADER_SIZE]) != block[0x20..0x30] {
+        return Err("KIRK header hash mismatch".into());
+    }
+
+    if cmac(&cmac_key, &encrypted[0x60..]) != block[0x30..0x40] {
+        return Err("KIRK data hash mismatch".into());
+    }
+
+    let mut data = encrypted[start..].to_vec();
+    cbc_decrypt(&aes_key, &mut data);
+    data.truncate(data_size);
+
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hex(s: &str) -> Vec<u8> {
+        (0..s.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    fn key(s: &str) -> [u8; 16] {
+        hex(s).try_into().unwrap()
+    }
+
+    /// RFC 4493 and SP 800-38A use the same key.
+    const NIST_KEY: &str = "2b7e151628aed2a6abf7158809cf4f3c";
+
+    #[test]
+    fn cbc_sp800_38a() {
+        // F.2.1, with the IV folded into the first block, as KIRK uses a
+        // zero IV.
+        let plain = hex("6bc0bce12a459991e134741a7f9e1925ae2d8a571e03ac9c9eb76fac45af8e51");
+        let cipher = hex("7649abac8119b246cee98e9b12e9197d5086cb9b507219ee95db113a917678b2");
+
+        let mut data = plain.clone();
+        cbc_encrypt(&key(NIST_KEY), &mut data);
+        assert_eq!(data, cipher);
+
+        cbc_decrypt(&key(NIST_KEY), &mut data);
+        assert_eq!(data, plain);
+    }
+
+    #[test]
+    fn cmac_rfc_4493() {
+        let message = hex(
+            "6bc1bee22e409f96e93d7e117393172aae2d8a571e03ac9c9eb76fac45af8e51\
+             30c81c46a35ce411e5fbc1191a0a52eff69f2445df4f9b17ad2b417be66c3710",
+        );
+        let cases = [
+            (0, "bb1d6929e95937287fa37d129b756746"),
+            (16, "070a16b46b4d4144f79bdd9dd04a287c"),
+            (40, "dfa66747de9ae63030ca32611497c827"),
+            (64, "51f0bebf7e3b9d92fc49741779363cfe"),
+        ];
+
+        for (len, mac) in cases {
+            assert_eq!(cmac(&key(NIST_KEY), &message[..len]), key(mac), "{}", len);
+        }
+    }
+
+    #[test]
+    fn kirk1_round_trip() {
+        let data = (0..100).map(|i| i as u8).collect::<Vec<_>>();
+        let block = kirk1_encrypt(&data, &[1; 16], &[2; 16]);
+
+        assert!(is_kirk1(&block));
+        assert_eq!(block.len(), KIRK1_HEADER_SIZE + 112);
+        assert_eq!(kirk1_decrypt(&block), Ok(data));
+
+        // The keys are stored encrypted with the public KIRK key.
+        let mut keys = block[..0x20].to_vec();
+        cbc_decrypt(&KIRK1_KEY, &mut keys);
+        assert_eq!(keys, [[1; 16], [2; 16]].concat());
+    }
+
+    #[test]
+    fn kirk1_rejects_tampering() {
+        let block = kirk1_encrypt(b"module", &[1; 16], &[2; 16]);
+
+        let mut header = block.clone();
+        header[0x70] ^= 1;
+        assert!(kirk1_decrypt(&header).is_err());
+
+        let mut data = block.clone();
+        data[KIRK1_HEADER_SIZE] ^= 1;
+        assert_eq!(kirk1_decrypt(&data), Err("KIRK data hash mismatch".into()));
+
+        assert!(kirk1_decrypt(&block[..KIRK1_HEADER_SIZE]).is_err());
+    }
+}
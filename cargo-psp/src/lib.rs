@@ -0,0 +1,4 @@
+//! Code shared by the `cargo-psp` binaries.
+
+pub mod gzip;
+pub mod kirk;
@@ -2,102 +2,23 @@ use cargo_metadata::{
     semver::{BuildMetadata, Prerelease},
     Message as CargoMessage, MetadataCommand,
 };
+use config::PspConfig;
 use rustc_version::{Channel, Version};
 use std::{
     collections::HashSet,
-    env, fmt, fs,
-    io::ErrorKind,
+    env, fmt,
+    path::PathBuf,
     process::{self, Command, Stdio},
 };
 
+mod addr2line;
+mod config;
 mod fix_imports;
-
-const CONFIG_NAME: &str = "Psp.toml";
-
-#[derive(serde_derive::Deserialize, Default)]
-struct PspConfig {
-    /// Title shown in the XMB menu.
-    title: Option<String>,
-
-    /// Path to 24bit 144x80 PNG icon shown in the XMB menu.
-    xmb_icon_png: Option<String>,
-
-    /// Path to animated icon shown in the XMB menu.
-    ///
-    /// The PSP expects a 29.97fps 144x80 PMF video file (custom Sony format).
-    xmb_icon_pmf: Option<String>,
-
-    /// Path to 24bit 480x272 PNG background shown in the XMB menu.
-    xmb_background_png: Option<String>,
-
-    /// Overlay background shown in the XMB menu.
-    ///
-    /// Exactly like `xmb_background_png`, but it is overlayed on top.
-    xmb_background_overlay_png: Option<String>,
-
-    /// Path to ATRAC3 audio file played in the XMB menu.
-    ///
-    /// Must be 66kbps, under 500KB and under 55 seconds.
-    xmb_music_at3: Option<String>,
-
-    /// Path to associated PSAR data stored in the EBOOT.
-    psar: Option<String>,
-
-    /// Product number of the game, in the format `ABCD-12345`.
-    ///
-    /// Example: UCJS-10001
-    disc_id: Option<String>,
-
-    /// Version of the game, e.g. "1.00".
-    disc_version: Option<String>,
-
-    // TODO: enum
-    /// Language of the game. "JP" indicates Japanese, even though this is not
-    /// the proper ISO 639 code...
-    language: Option<String>,
-
-    // TODO: enum
-    /// Parental Control level needed to access the file. 1-11
-    /// - 1 = General audience
-    /// - 5 = 12 year old
-    /// - 7 = 15 year old
-    /// - 9 = 18 year old
-    parental_level: Option<u32>,
-
-    /// PSP Firmware Version required by the game (e.g. "6.61").
-    psp_system_ver: Option<String>,
-
-    // TODO: document values
-    /// Bitmask of allowed regions. (0x8000 is region 2?)
-    region: Option<u32>,
-
-    /// Japanese localized title.
-    title_jp: Option<String>,
-
-    /// French localized title.
-    title_fr: Option<String>,
-
-    /// Spanish localized title.
-    title_es: Option<String>,
-
-    /// German localized title.
-    title_de: Option<String>,
-
-    /// Italian localized title.
-    title_it: Option<String>,
-
-    /// Dutch localized title.
-    title_nl: Option<String>,
-
-    /// Portugese localized title.
-    title_pt: Option<String>,
-
-    /// Russian localized title.
-    title_ru: Option<String>,
-
-    /// Used by the firmware updater to denote the firmware version it updates to.
-    updater_version: Option<String>,
-}
+mod inspect;
+mod new;
+mod nid_db;
+mod run;
+mod symbols;
 
 #[derive(Ord, PartialOrd, PartialEq, Eq, Debug)]
 struct CommitDate {
@@ -141,7 +62,7 @@ impl core::ops::Add<CommitDate> for CommitDate {
 // contains the previous days' nightly rustc.
 const MINIMUM_COMMIT_DATE: CommitDate = CommitDate {
     year: 2026,
-    month: 05,
+    month: 5,
     day: 29,
 };
 const MINIMUM_RUSTC_VERSION: Version = Version {
@@ -153,6 +74,31 @@ const MINIMUM_RUSTC_VERSION: Version = Version {
 };
 
 fn main() {
+    // Subcommands which do not build anything.
+    // Skip `cargo`, leaving the subcommand name as the binary name.
+    match env::args().nth(2).as_deref() {
+        Some("addr2line") => return addr2line::main(env::args().skip(2)),
+        Some("inspect") => return inspect::main(env::args().skip(2)),
+        Some("new") => return new::main(env::args().skip(2)),
+        Some("nids") => return nid_db::main(env::args().skip(2)),
+        Some("run") => return run::main(run::Mode::Run, env::args().skip(3)),
+        Some("test") => return run::main(run::Mode::Test, env::args().skip(3)),
+        _ => {}
+    }
+
+    // Skip `cargo psp`
+    build(env::args().skip(2));
+}
+
+/// The files produced for a single executable.
+pub struct Artifact {
+    pub prx: PathBuf,
+    pub pbp: PathBuf,
+}
+
+/// Run `cargo build` with the given arguments, then package every executable
+/// that was built.
+pub fn build(args: impl Iterator<Item = String>) -> Vec<Artifact> {
     let rustc_version = rustc_version::version_meta().unwrap();
 
     if rustc_version.channel > Channel::Nightly {
@@ -194,21 +140,18 @@ fn main() {
         process::exit(1);
     }
 
-    let config = match fs::read_to_string(CONFIG_NAME) {
-        Ok(value) => match toml::from_str(&value) {
-            Ok(config) => config,
-            Err(e) => {
-                println!("Failed to read Psp.toml: {}", e);
-                println!("Please ensure that it is formatted correctly.");
-                process::exit(1);
-            }
-        },
-        Err(e) if e.kind() == ErrorKind::NotFound => PspConfig::default(),
-        Err(e) => panic!("{}", e),
-    };
+    let config = PspConfig::load();
+    let errors = config.validate();
 
-    // Skip `cargo psp`
-    let args = env::args().skip(2);
+    if !errors.is_empty() {
+        println!("Invalid {}:", config::CONFIG_NAME);
+
+        for error in errors {
+            println!("  {}", error);
+        }
+
+        process::exit(1);
+    }
 
     let build_std_flag = match env::var("RUST_PSP_BUILD_STD") {
         Ok(_) => {
@@ -280,9 +223,26 @@ fn main() {
         process::exit(status.code().unwrap_or(1));
     }
 
+    for name in config.bin.keys() {
+        if !built_executables
+            .iter()
+            .any(|p| p.file_stem() == Some(name.as_str()))
+        {
+            eprintln!(
+                "[NOTE]: {} has a [bin.{}] section, but no such executable was built.",
+                config::CONFIG_NAME,
+                name
+            );
+        }
+    }
+
+    let mut artifacts = Vec::new();
+
     // TODO: Error if no bin is ever found.
     for elf_path in built_executables {
+        let config = config.for_bin(elf_path.file_stem().unwrap_or_default());
         let prx_path = elf_path.with_extension("prx");
+        let sym_path = elf_path.with_extension("sym");
 
         let [sfo_path, pbp_path] = ["PARAM.SFO", "EBOOT.PBP"].map(|e| {
             if lone {
@@ -302,17 +262,25 @@ fn main() {
 
         assert!(status.success(), "prxgen failed: {}", status);
 
+        if let Err(e) = symbols::write_map(elf_path.as_std_path(), sym_path.as_std_path()) {
+            eprintln!("warning: failed to write symbol map {}: {}", sym_path, e);
+        }
+
         let config_args = vec![
-            ("-s", "DISC_ID", config.disc_id.clone()),
+            ("-s", "DISC_ID", config.sfo_disc_id()),
             ("-s", "DISC_VERSION", config.disc_version.clone()),
-            ("-s", "LANGUAGE", config.language.clone()),
+            (
+                "-s",
+                "LANGUAGE",
+                config.language.map(|l| l.as_str().to_string()),
+            ),
             (
                 "-d",
                 "PARENTAL_LEVEL",
-                config.parental_level.as_ref().map(u32::to_string),
+                config.parental_level.map(|l| l.level().to_string()),
             ),
             ("-s", "PSP_SYSTEM_VER", config.psp_system_ver.clone()),
-            ("-d", "REGION", config.region.as_ref().map(u32::to_string)),
+            ("-d", "REGION", config.region.map(|r| r.mask().to_string())),
             ("-s", "TITLE_0", config.title_jp.clone()),
             ("-s", "TITLE_2", config.title_fr.clone()),
             ("-s", "TITLE_3", config.title_es.clone()),
@@ -367,5 +335,12 @@ fn main() {
             .expect("failed to run pack-pbp");
 
         assert!(status.success(), "pack-pbp failed: {}", status);
+
+        artifacts.push(Artifact {
+            prx: prx_path.into(),
+            pbp: pbp_path.into(),
+        });
     }
+
+    artifacts
 }
@@ -0,0 +1,223 @@
+use clap::{Parser, ValueEnum};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process,
+};
+
+/// Maximum length of a module name, not including the nul terminator.
+const MODULE_NAME_MAX: usize = 27;
+
+const PSP_TOML: &str = include_str!("../templates/Psp.toml");
+const GITIGNORE: &str = "target\n";
+
+/// Repository of the `psp` dependency in generated projects.
+///
+/// The templates use APIs which are not in a crates.io release yet, so
+/// projects depend on the git repository until one is published.
+const PSP_GIT: &str = "https://github.com/overdrivenpotato/rust-psp";
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum Template {
+    /// A no_std EBOOT which prints to the debug screen.
+    Minimal,
+    /// A spinning cube, rendered with the GU.
+    #[value(name = "gu-3d")]
+    Gu3d,
+    /// 2D drawing with the `embedded-graphics` crate.
+    EmbeddedGraphics,
+    /// A PRX plugin which keeps running in the background.
+    PrxPlugin,
+    /// An EBOOT using the standard library.
+    Std,
+}
+
+impl Template {
+    fn main_rs(self) -> &'static str {
+        match self {
+            Template::Minimal => include_str!("../templates/minimal/main.rs"),
+            Template::Gu3d => include_str!("../templates/gu-3d/main.rs"),
+            Template::EmbeddedGraphics => include_str!("../templates/embedded-graphics/main.rs"),
+            Template::PrxPlugin => include_str!("../templates/prx-plugin/main.rs"),
+            Template::Std => include_str!("../templates/std/main.rs"),
+        }
+    }
+
+    /// `psp` crate features needed by the template.
+    fn psp_features(self) -> &'static [&'static str] {
+        match self {
+            Template::EmbeddedGraphics => &["embedded-graphics"],
+            Template::Std => &["std"],
+            _ => &[],
+        }
+    }
+
+    /// Extra `[dependencies]` lines.
+    fn dependencies(self) -> &'static str {
+        match self {
+            Template::EmbeddedGraphics => {
+                "embedded-graphics = { version = \"0.8.1\", features = [\"fixed_point\"] }\n"
+            }
+            _ => "",
+        }
+    }
+}
+
+#[derive(Parser, Debug)]
+#[command(
+    name = "cargo psp new",
+    about = "Create a new PSP project from a template"
+)]
+struct Args {
+    #[arg(help = "Directory to create the project in")]
+    path: PathBuf,
+    #[arg(long, help = "Package name (default: the directory name)")]
+    name: Option<String>,
+    #[arg(long, value_enum, default_value = "minimal", help = "Project template")]
+    template: Template,
+    #[arg(
+        long,
+        value_name = "DIR",
+        help = "Depend on a local checkout of the psp crate instead of its git repository"
+    )]
+    psp_path: Option<PathBuf>,
+}
+
+/// Check that the package name is something cargo will accept.
+fn valid_package_name(name: &str) -> bool {
+    !name.is_empty()
+        && !name.starts_with(|c: char| c.is_ascii_digit())
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+/// Derive the module name passed to `psp::module!` from the package name.
+fn module_name(name: &str) -> String {
+    let mut name = name.replace('-', "_");
+    name.truncate(MODULE_NAME_MAX);
+    name
+}
+
+fn psp_dependency(template: Template, psp_path: Option<&Path>) -> String {
+    let source = match psp_path {
+        Some(path) => format!("path = {:?}", path.display().to_string()),
+        None => format!("git = {:?}", PSP_GIT),
+    };
+
+    let features = template.psp_features();
+
+    if features.is_empty() {
+        format!("psp = {{ {} }}\n", source)
+    } else {
+        let features = features
+            .iter()
+            .map(|f| format!("{:?}", f))
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        format!("psp = {{ {}, features = [{}] }}\n", source, features)
+    }
+}
+
+fn cargo_toml(name: &str, template: Template, psp_path: Option<&Path>) -> String {
+    format!(
+        "[package]\n\
+         name = \"{}\"\n\
+         version = \"0.1.0\"\n\
+         edition = \"2021\"\n\
+         \n\
+         [dependencies]\n\
+         {}{}",
+        name,
+        psp_dependency(template, psp_path),
+        template.dependencies(),
+    )
+}
+
+fn render(template: &str, name: &str) -> String {
+    template
+        .replace("{{name}}", name)
+        .replace("{{module_name}}", &module_name(name))
+}
+
+fn write(path: &Path, contents: &str) {
+    if let Some(parent) = path.parent() {
+        if let Err(e) = fs::create_dir_all(parent) {
+            eprintln!("error: failed to create {}: {}", parent.display(), e);
+            process::exit(1);
+        }
+    }
+
+    if let Err(e) = fs::write(path, contents) {
+        eprintln!("error: failed to write {}: {}", path.display(), e);
+        process::exit(1);
+    }
+}
+
+pub fn main(args: impl Iterator<Item = String>) {
+    let args = Args::parse_from(args);
+
+    let name = match &args.name {
+        Some(name) => name.clone(),
+        None => match args.path.file_name() {
+            Some(name) => name.to_string_lossy().into_owned(),
+            None => {
+                eprintln!(
+                    "error: cannot infer a package name from {}",
+                    args.path.display()
+                );
+                process::exit(1);
+            }
+        },
+    };
+
+    if !valid_package_name(&name) {
+        eprintln!(
+            "error: `{}` is not a valid package name, use --name to set one",
+            name
+        );
+        process::exit(1);
+    }
+
+    if args.path.exists() {
+        eprintln!(
+            "error: destination `{}` already exists",
+            args.path.display()
+        );
+        process::exit(1);
+    }
+
+    // Relative paths would be resolved from the new project by cargo.
+    let psp_path = args.psp_path.as_deref().map(|path| {
+        std::path::absolute(path).unwrap_or_else(|err| {
+            eprintln!("error: invalid path {}: {}", path.display(), err);
+            process::exit(1);
+        })
+    });
+
+    let dir = &args.path;
+    let psp_path = psp_path.as_deref();
+
+    write(
+        &dir.join("Cargo.toml"),
+        &cargo_toml(&name, args.template, psp_path),
+    );
+    write(&dir.join(".gitignore"), GITIGNORE);
+    write(
+        &dir.join(crate::config::CONFIG_NAME),
+        &render(PSP_TOML, &name),
+    );
+    write(
+        &dir.join("src").join("main.rs"),
+        &render(args.template.main_rs(), &name),
+    );
+
+    println!("Created `{}` in {}", name, dir.display());
+
+    if let Template::Std = args.template {
+        println!(
+            "note: `std` support is experimental, build with `RUST_PSP_BUILD_STD=1 cargo psp`"
+        );
+    }
+}
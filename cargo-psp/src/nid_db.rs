@@ -0,0 +1,245 @@
+use clap::{Parser, ValueEnum};
+use serde::Serializer;
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    process::{self, Command, Stdio},
+};
+
+/// A system library declared with `psp_extern!` in the `psp` crate.
+#[derive(serde_derive::Serialize, Debug)]
+pub struct Library {
+    pub name: String,
+    #[serde(serialize_with = "hex")]
+    pub flags: u16,
+    /// `(major, minor)`
+    pub version: (u8, u8),
+    pub functions: Vec<Function>,
+}
+
+/// A single function binding, identified by its NID.
+#[derive(serde_derive::Serialize, Debug)]
+pub struct Function {
+    pub name: String,
+    #[serde(serialize_with = "hex")]
+    pub nid: u32,
+}
+
+/// The exported database. This is a struct, rather than a plain list, as TOML
+/// requires a table at the top level.
+#[derive(serde_derive::Serialize)]
+struct NidDatabase<'a> {
+    library: &'a [Library],
+}
+
+/// NIDs and flags are conventionally written in hex, e.g. `0x6A2774F3`.
+fn hex<T: Into<u32> + Copy, S: Serializer>(value: &T, s: S) -> Result<S::Ok, S::Error> {
+    let digits = std::mem::size_of::<T>() * 2;
+    s.serialize_str(&format!("0x{:0digits$X}", (*value).into()))
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum Format {
+    Json,
+    Toml,
+}
+
+#[derive(Parser, Debug)]
+#[command(
+    name = "cargo psp nids",
+    about = "Export the NID of every `psp_extern!` binding in the psp crate"
+)]
+struct Args {
+    #[arg(long, value_enum, default_value = "json", help = "Output format")]
+    format: Format,
+    #[arg(
+        short,
+        long,
+        value_name = "FILE",
+        help = "Write to a file instead of stdout"
+    )]
+    output: Option<PathBuf>,
+    #[arg(
+        long,
+        value_name = "DIR",
+        help = "Path to `psp/src/sys` (default: found with `cargo metadata`)"
+    )]
+    psp_sys: Option<PathBuf>,
+}
+
+/// Parse an integer literal as found in `psp_extern!` attributes, e.g. `0x4001`
+/// or `17`.
+fn parse_int(s: &str) -> Option<u32> {
+    let s = s.trim().replace('_', "");
+
+    match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        Some(hex) => u32::from_str_radix(hex, 16).ok(),
+        None => s.parse().ok(),
+    }
+}
+
+/// Extract the text between `#![<key> = ` and the closing `]`.
+fn inner_attr<'a>(line: &'a str, key: &str) -> Option<&'a str> {
+    line.strip_prefix("#![")?
+        .strip_prefix(key)?
+        .trim_start()
+        .strip_prefix('=')?
+        .trim()
+        .strip_suffix(']')
+}
+
+/// Parse every `psp_extern!` block in a source file.
+///
+/// This is a line based parser which relies on the formatting used throughout
+/// `psp::sys`. Anything it does not understand is skipped.
+pub fn parse_source(src: &str) -> Vec<Library> {
+    let mut libraries = Vec::new();
+    let mut current: Option<Library> = None;
+    let mut pending_nid = None;
+
+    for line in src.lines().map(str::trim) {
+        if line.starts_with("psp_extern!") {
+            libraries.extend(current.take());
+            current = Some(Library {
+                name: String::new(),
+                flags: 0,
+                version: (0, 0),
+                functions: Vec::new(),
+            });
+            continue;
+        }
+
+        let Some(lib) = current.as_mut() else {
+            continue;
+        };
+
+        if let Some(name) = inner_attr(line, "name") {
+            lib.name = name.trim_matches('"').to_string();
+        } else if let Some(flags) = inner_attr(line, "flags") {
+            lib.flags = parse_int(flags).unwrap_or(0) as u16;
+        } else if let Some(version) = inner_attr(line, "version") {
+            let version = version.trim_start_matches('(').trim_end_matches(')');
+            let mut parts = version.split(',').map(parse_int);
+
+            if let (Some(Some(major)), Some(Some(minor))) = (parts.next(), parts.next()) {
+                lib.version = (major as u8, minor as u8);
+            }
+        } else if let Some(attr) = line.strip_prefix("#[psp(") {
+            // The NID may be followed by an ABI mapper, e.g. `#[psp(0x..., i6)]`.
+            pending_nid = attr.split([',', ')']).next().and_then(parse_int);
+        } else if let Some(rest) = line.strip_prefix("pub fn ") {
+            if let Some(nid) = pending_nid.take() {
+                let name = rest
+                    .split(|c: char| !(c.is_alphanumeric() || c == '_'))
+                    .next()
+                    .unwrap_or_default();
+
+                lib.functions.push(Function {
+                    name: name.to_string(),
+                    nid,
+                });
+            }
+        }
+    }
+
+    libraries.extend(current);
+    libraries.retain(|lib| !lib.name.is_empty());
+    libraries
+}
+
+/// Parse every `psp_extern!` block under a directory, recursively.
+///
+/// This is usually `psp/src/sys`.
+pub fn parse_dir(dir: &Path) -> std::io::Result<Vec<Library>> {
+    let mut entries = fs::read_dir(dir)?
+        .map(|e| e.map(|e| e.path()))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // Keep the output stable between runs.
+    entries.sort();
+
+    let mut libraries = Vec::new();
+
+    for path in entries {
+        if path.is_dir() {
+            libraries.extend(parse_dir(&path)?);
+        } else if path.extension().is_some_and(|e| e == "rs") {
+            libraries.extend(parse_source(&fs::read_to_string(&path)?));
+        }
+    }
+
+    Ok(libraries)
+}
+
+/// Locate the `psp/src/sys` directory of the `psp` crate used by the cargo
+/// project in the current directory.
+pub fn locate_sys_dir() -> Option<PathBuf> {
+    let cargo = std::env::var_os("CARGO").unwrap_or_else(|| "cargo".into());
+    let output = Command::new(cargo)
+        .arg("metadata")
+        .arg("--format-version=1")
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+
+    if !output.status.success() {
+        return None;
+    }
+
+    let metadata =
+        cargo_metadata::MetadataCommand::parse(std::str::from_utf8(&output.stdout).ok()?).ok()?;
+
+    let psp = metadata.packages.iter().find(|p| p.name == "psp")?;
+    let dir = psp.manifest_path.parent()?.join("src").join("sys");
+
+    dir.is_dir().then(|| dir.into())
+}
+
+/// Build a map of (library name, NID) -> function name.
+pub fn nid_map(libraries: &[Library]) -> HashMap<(&str, u32), &str> {
+    libraries
+        .iter()
+        .flat_map(|lib| {
+            lib.functions
+                .iter()
+                .map(move |f| ((lib.name.as_str(), f.nid), f.name.as_str()))
+        })
+        .collect()
+}
+
+pub fn main(args: impl Iterator<Item = String>) {
+    let args = Args::parse_from(args);
+
+    let Some(dir) = args.psp_sys.or_else(locate_sys_dir) else {
+        eprintln!("error: could not find the psp crate sources, try passing --psp-sys");
+        process::exit(1);
+    };
+
+    let libraries = match parse_dir(&dir) {
+        Ok(libraries) => libraries,
+        Err(e) => {
+            eprintln!("error: failed to read {}: {}", dir.display(), e);
+            process::exit(1);
+        }
+    };
+
+    let db = NidDatabase {
+        library: &libraries,
+    };
+
+    let output = match args.format {
+        Format::Json => serde_json::to_string_pretty(&db).unwrap() + "\n",
+        Format::Toml => toml::to_string(&db).unwrap(),
+    };
+
+    match args.output {
+        Some(path) => {
+            if let Err(e) = fs::write(&path, output) {
+                eprintln!("error: couldn't write to {}: {}", path.display(), e);
+                process::exit(1);
+            }
+        }
+        None => print!("{}", output),
+    }
+}
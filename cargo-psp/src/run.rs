@@ -0,0 +1,368 @@
+use crate::Artifact;
+use std::{
+    env,
+    fs::{self, File},
+    io::{self, ErrorKind, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    process::{self, Command},
+    thread,
+    time::{Duration, Instant},
+};
+
+// These must match the constants in `psp::test_runner`.
+const OUTPUT_FILENAME: &str = "psp_output_file.log";
+const STARTING_TOKEN: &str = "STARTING_TESTS";
+const SUCCESS_TOKEN: &str = "FINAL_SUCCESS";
+const FAILURE_TOKEN: &str = "FINAL_FAILURE";
+
+/// Environment variable used to configure the emulator binary.
+const EMULATOR_ENV: &str = "PSP_EMULATOR";
+
+/// Default `cargo psp test` timeout, in seconds.
+const DEFAULT_TEST_TIMEOUT: u32 = 60;
+
+/// Extra time given to the emulator to exit by itself, on top of the timeout.
+const KILL_GRACE: Duration = Duration::from_secs(5);
+
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Exit code of `cargo psp test` when the test runner did not report a result,
+/// e.g. because the module crashed or the emulator timed out.
+const EXIT_INCOMPLETE: i32 = 2;
+
+const USAGE: &str = "\
+Build, then launch the EBOOT in an emulator
+
+Usage: cargo psp {run|test} [OPTIONS] [CARGO BUILD ARGS]... [-- EMULATOR ARGS...]
+
+Options:
+      --emulator <PATH>  Emulator binary (default: $PSP_EMULATOR, or PPSSPP found in PATH)
+      --timeout <SECS>   Stop the emulator after this many seconds (test default: 60)
+      --prx              Launch the PRX instead of the EBOOT.PBP
+  -h, --help             Print help
+
+Any other arguments, e.g. `--release` or `--bin <NAME>`, are passed to `cargo build`.
+
+`cargo psp test` expects the module to use `psp::test_runner` with a file runner.
+The emulator must accept PPSSPPHeadless style `-r <DIR>` and `--timeout=<SECS>`
+arguments. Exit status is 0 if the tests passed, 1 if they failed, and 2 if no
+result was reported.
+";
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    Run,
+    Test,
+}
+
+impl Mode {
+    /// Emulator binaries searched for in `PATH`, in order of preference.
+    fn emulators(self) -> &'static [&'static str] {
+        match self {
+            Mode::Run => &[
+                "PPSSPPSDL",
+                "PPSSPPQt",
+                "PPSSPP",
+                "ppsspp",
+                "PPSSPPHeadless",
+            ],
+            Mode::Test => &["PPSSPPHeadless"],
+        }
+    }
+}
+
+#[derive(Default)]
+struct Options {
+    emulator: Option<PathBuf>,
+    timeout: Option<u32>,
+    prx: bool,
+    build_args: Vec<String>,
+    emulator_args: Vec<String>,
+}
+
+fn error(message: impl std::fmt::Display) -> ! {
+    eprintln!("error: {}", message);
+    process::exit(1);
+}
+
+/// Split our own options from the ones forwarded to `cargo build` and the
+/// emulator.
+fn parse_args(mut args: impl Iterator<Item = String>) -> Options {
+    let mut options = Options::default();
+
+    while let Some(arg) = args.next() {
+        let (flag, inline) = match arg.split_once('=') {
+            Some((flag, value)) if flag.starts_with("--") => (flag, Some(value.to_string())),
+            _ => (arg.as_str(), None),
+        };
+
+        let mut value = |name| {
+            inline
+                .clone()
+                .or_else(|| args.next())
+                .unwrap_or_else(|| error(format_args!("{} requires a value", name)))
+        };
+
+        match flag {
+            "--" => {
+                options.emulator_args.extend(args.by_ref());
+            }
+            "-h" | "--help" => {
+                print!("{}", USAGE);
+                process::exit(0);
+            }
+            "--emulator" => options.emulator = Some(value("--emulator").into()),
+            "--timeout" => {
+                let timeout = value("--timeout");
+                options.timeout = Some(
+                    timeout
+                        .parse()
+                        .unwrap_or_else(|_| error(format_args!("invalid timeout `{}`", timeout))),
+                );
+            }
+            "--prx" => options.prx = true,
+            _ => options.build_args.push(arg),
+        }
+    }
+
+    options
+}
+
+/// Find an executable in `PATH`.
+fn find_in_path(name: &str) -> Option<PathBuf> {
+    let file_name = format!("{}{}", name, env::consts::EXE_SUFFIX);
+
+    env::split_paths(&env::var_os("PATH")?)
+        .map(|dir| dir.join(&file_name))
+        .find(|path| path.is_file())
+}
+
+fn locate_emulator(mode: Mode, emulator: Option<PathBuf>) -> PathBuf {
+    if let Some(emulator) = emulator {
+        return emulator;
+    }
+
+    if let Some(emulator) = env::var_os(EMULATOR_ENV).filter(|e| !e.is_empty()) {
+        return emulator.into();
+    }
+
+    mode.emulators()
+        .iter()
+        .find_map(|name| find_in_path(name))
+        .unwrap_or_else(|| {
+            error(format_args!(
+                "could not find an emulator, set {} or pass --emulator (tried: {})",
+                EMULATOR_ENV,
+                mode.emulators().join(", ")
+            ))
+        })
+}
+
+fn is_headless(emulator: &Path) -> bool {
+    emulator
+        .file_stem()
+        .is_some_and(|s| s.to_string_lossy().to_lowercase().contains("headless"))
+}
+
+/// Output of a `psp::test_runner` file runner, read while it is being written.
+struct TestLog {
+    path: PathBuf,
+    offset: u64,
+    line: Vec<u8>,
+    started: bool,
+    passed: usize,
+    failed: usize,
+    result: Option<bool>,
+}
+
+impl TestLog {
+    fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            offset: 0,
+            line: Vec::new(),
+            started: false,
+            passed: 0,
+            failed: 0,
+            result: None,
+        }
+    }
+
+    /// Print and parse anything written since the last call.
+    fn poll(&mut self) -> io::Result<()> {
+        let mut file = match File::open(&self.path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(e),
+        };
+
+        let mut new = Vec::new();
+        file.seek(SeekFrom::Start(self.offset))?;
+        file.read_to_end(&mut new)?;
+        self.offset += new.len() as u64;
+
+        let mut stdout = io::stdout();
+        stdout.write_all(&new)?;
+        stdout.flush()?;
+
+        for byte in new {
+            if byte == b'\n' {
+                let line = String::from_utf8_lossy(&self.line).into_owned();
+                self.parse_line(&line);
+                self.line.clear();
+            } else {
+                self.line.push(byte);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Parse the last line, even if it was not terminated.
+    fn finish(&mut self) -> io::Result<()> {
+        self.poll()?;
+
+        let line = String::from_utf8_lossy(&self.line).into_owned();
+        self.parse_line(&line);
+        self.line.clear();
+
+        Ok(())
+    }
+
+    fn parse_line(&mut self, line: &str) {
+        match line.trim() {
+            STARTING_TOKEN => self.started = true,
+            SUCCESS_TOKEN => self.result = Some(true),
+            FAILURE_TOKEN => self.result = Some(false),
+            l if l.starts_with("[PASS]") => self.passed += 1,
+            l if l.starts_with("[FAIL]") => self.failed += 1,
+            _ => {}
+        }
+    }
+}
+
+pub fn main(mode: Mode, args: impl Iterator<Item = String>) {
+    let options = parse_args(args);
+    let emulator = locate_emulator(mode, options.emulator);
+    let headless = is_headless(&emulator);
+
+    let artifacts = crate::build(options.build_args.into_iter());
+
+    let artifact = match &artifacts[..] {
+        [artifact] => artifact,
+        [] => error("no executable was built"),
+        _ => error("multiple executables were built, select one with `--bin <NAME>`"),
+    };
+
+    let Artifact { prx, pbp } = artifact;
+    let file = if options.prx { prx } else { pbp };
+    let dir = file.parent().unwrap_or(Path::new("."));
+
+    let timeout = match mode {
+        Mode::Run => options.timeout,
+        Mode::Test => Some(options.timeout.unwrap_or(DEFAULT_TEST_TIMEOUT)),
+    };
+
+    let mut command = Command::new(&emulator);
+    command.args(&options.emulator_args);
+
+    if headless {
+        // Map `host0:` to the output directory, where the test runner writes.
+        command.arg("-r").arg(dir);
+
+        if let Some(timeout) = timeout {
+            command.arg(format!("--timeout={}", timeout));
+        }
+    }
+
+    command.arg(file);
+
+    let log_path = dir.join(OUTPUT_FILENAME);
+
+    if mode == Mode::Test {
+        if let Err(e) = fs::remove_file(&log_path) {
+            if e.kind() != ErrorKind::NotFound {
+                error(format_args!(
+                    "failed to remove {}: {}",
+                    log_path.display(),
+                    e
+                ));
+            }
+        }
+    }
+
+    eprintln!(
+        "[NOTE]: Running `{} {}`",
+        emulator.display(),
+        command
+            .get_args()
+            .map(|a| a.to_string_lossy())
+            .collect::<Vec<_>>()
+            .join(" ")
+    );
+
+    let mut child = command.spawn().unwrap_or_else(|e| {
+        error(format_args!(
+            "failed to launch {}: {}",
+            emulator.display(),
+            e
+        ))
+    });
+
+    if mode == Mode::Run {
+        let status = child.wait().expect("failed to wait for the emulator");
+        process::exit(status.code().unwrap_or(1));
+    }
+
+    let mut log = TestLog::new(log_path);
+    let deadline = timeout.map(|t| Instant::now() + Duration::from_secs(t.into()) + KILL_GRACE);
+
+    let status = loop {
+        if let Err(e) = log.poll() {
+            error(format_args!("failed to read {}: {}", log.path.display(), e));
+        }
+
+        if let Some(status) = child.try_wait().expect("failed to wait for the emulator") {
+            break Some(status);
+        }
+
+        if deadline.is_some_and(|d| Instant::now() > d) {
+            let _ = child.kill();
+            let _ = child.wait();
+            break None;
+        }
+
+        thread::sleep(POLL_INTERVAL);
+    };
+
+    if let Err(e) = log.finish() {
+        error(format_args!("failed to read {}: {}", log.path.display(), e));
+    }
+
+    println!();
+
+    let summary = format!("{} passed; {} failed", log.passed, log.failed);
+
+    match log.result {
+        Some(true) => println!("test result: ok. {}", summary),
+        Some(false) => {
+            println!("test result: FAILED. {}", summary);
+            process::exit(1);
+        }
+        None => {
+            let reason = match status {
+                None => "the emulator was killed after timing out".into(),
+                Some(_) if !log.started => {
+                    format!("the test runner never printed `{}`", STARTING_TOKEN)
+                }
+                Some(status) => {
+                    format!("the emulator exited ({}) before the tests finished", status)
+                }
+            };
+
+            println!("test result: INCOMPLETE. {}", summary);
+            eprintln!("error: no test result was reported: {}", reason);
+            process::exit(EXIT_INCOMPLETE);
+        }
+    }
+}
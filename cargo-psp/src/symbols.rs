@@ -0,0 +1,82 @@
+//! Function symbols of a built executable, written next to the PRX as a
+//! `.sym` map and used by `cargo psp addr2line`.
+
+use goblin::elf::{sym::STT_FUNC, Elf};
+use std::{fmt::Write, fs, io, path::Path};
+
+/// A function, at an offset from the start of the module.
+pub struct Function {
+    pub address: u32,
+    pub size: u32,
+    pub name: String,
+}
+
+/// Collect the defined functions of an ELF or PRX, sorted by address.
+///
+/// Executables are linked at address 0 and loaded at an arbitrary base, so
+/// addresses are offsets into the module.
+pub fn functions(elf: &Elf) -> Vec<Function> {
+    let mut functions: Vec<_> = elf
+        .syms
+        .iter()
+        .filter(|sym| sym.st_type() == STT_FUNC && sym.st_shndx != 0 && sym.st_size > 0)
+        .filter_map(|sym| {
+            Some(Function {
+                address: sym.st_value as u32,
+                size: sym.st_size as u32,
+                name: demangle(elf.strtab.get_at(sym.st_name)?),
+            })
+        })
+        .collect();
+
+    functions.sort_by(|a, b| a.address.cmp(&b.address).then(b.size.cmp(&a.size)));
+    functions.dedup_by_key(|f| f.address);
+
+    functions
+}
+
+/// Demangle a Rust symbol, without its hash, e.g.
+/// `_ZN3app4main17h0123456789abcdefE` becomes `app::main`. Other symbols are
+/// returned unchanged.
+fn demangle(symbol: &str) -> String {
+    format!("{:#}", rustc_demangle::demangle(symbol))
+}
+
+/// Find the function containing an offset.
+pub fn lookup(functions: &[Function], address: u32) -> Option<&Function> {
+    let i = functions.partition_point(|f| f.address <= address);
+    let function = functions.get(i.checked_sub(1)?)?;
+
+    (address - function.address < function.size).then_some(function)
+}
+
+/// Write the function map of an executable, one `offset size name` line per
+/// function, in hex.
+pub fn write_map(elf_path: &Path, map_path: &Path) -> io::Result<()> {
+    let bytes = fs::read(elf_path)?;
+    let elf = Elf::parse(&bytes).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+    let mut map = String::new();
+    for function in functions(&elf) {
+        writeln!(
+            map,
+            "{:08x} {:08x} {}",
+            function.address, function.size, function.name
+        )
+        .unwrap();
+    }
+
+    fs::write(map_path, map)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn demangle_drops_hashes() {
+        assert_eq!(demangle("_ZN3app4main17h0123456789abcdefE"), "app::main");
+        assert_eq!(demangle("_RNvCs1234_3app4main"), "app::main");
+        assert_eq!(demangle("memcpy"), "memcpy");
+    }
+}
@@ -0,0 +1,44 @@
+#![no_std]
+#![no_main]
+
+use embedded_graphics::mono_font::{ascii::FONT_6X12, MonoTextStyle};
+use embedded_graphics::pixelcolor::Rgb888;
+use embedded_graphics::prelude::*;
+use embedded_graphics::primitives::*;
+use embedded_graphics::text::Text;
+
+use psp::embedded_graphics::Framebuffer;
+
+psp::module!("{{module_name}}", 1, 0);
+
+fn psp_main() {
+    psp::enable_home_button();
+    let mut disp = Framebuffer::new();
+
+    let backdrop = PrimitiveStyleBuilder::new()
+        .fill_color(Rgb888::BLACK)
+        .build();
+    Rectangle::new(Point::new(0, 0), Size::new(480, 272))
+        .into_styled(backdrop)
+        .draw(&mut disp)
+        .unwrap();
+
+    Circle::new(Point::new(216, 112), 48)
+        .into_styled(
+            PrimitiveStyleBuilder::new()
+                .stroke_color(Rgb888::BLUE)
+                .stroke_width(2)
+                .build(),
+        )
+        .draw(&mut disp)
+        .unwrap();
+
+    let rust = Rgb888::new(0xff, 0x07, 0x00);
+    Text::new(
+        "Hello Rust!",
+        Point::new(207, 180),
+        MonoTextStyle::new(&FONT_6X12, rust),
+    )
+    .draw(&mut disp)
+    .unwrap();
+}
@@ -0,0 +1,137 @@
+#![no_std]
+#![no_main]
+
+use core::{f32::consts::PI, ptr};
+use psp::sys::{
+    self, ClearBuffer, DepthFunc, DisplayPixelFormat, FrontFaceDirection, GuContextType,
+    GuPrimitive, GuState, GuSyncBehavior, GuSyncMode, ScePspFVector3, ShadingModel,
+    TexturePixelFormat, VertexType,
+};
+use psp::vram_alloc::get_vram_allocator;
+use psp::Align16;
+use psp::{BUF_WIDTH, SCREEN_HEIGHT, SCREEN_WIDTH};
+
+psp::module!("{{module_name}}", 1, 0);
+
+static mut LIST: Align16<[u32; 0x40000]> = Align16([0; 0x40000]);
+
+// The field order must match the `VertexType` flags passed to the draw call:
+// color first, then position.
+#[repr(C, align(4))]
+struct Vertex {
+    color: u32,
+    x: f32,
+    y: f32,
+    z: f32,
+}
+
+const fn v(color: u32, x: f32, y: f32, z: f32) -> Vertex {
+    Vertex { color, x, y, z }
+}
+
+const RED: u32 = 0xff0000ff;
+const GREEN: u32 = 0xff00ff00;
+const BLUE: u32 = 0xffff0000;
+
+static VERTICES: Align16<[Vertex; 12 * 3]> = Align16([
+    // Front
+    v(RED, -1.0, -1.0, 1.0), v(RED, -1.0, 1.0, 1.0), v(RED, 1.0, 1.0, 1.0),
+    v(RED, -1.0, -1.0, 1.0), v(RED, 1.0, 1.0, 1.0), v(RED, 1.0, -1.0, 1.0),
+    // Back
+    v(RED, -1.0, -1.0, -1.0), v(RED, 1.0, -1.0, -1.0), v(RED, 1.0, 1.0, -1.0),
+    v(RED, -1.0, -1.0, -1.0), v(RED, 1.0, 1.0, -1.0), v(RED, -1.0, 1.0, -1.0),
+    // Right
+    v(GREEN, 1.0, -1.0, -1.0), v(GREEN, 1.0, -1.0, 1.0), v(GREEN, 1.0, 1.0, 1.0),
+    v(GREEN, 1.0, -1.0, -1.0), v(GREEN, 1.0, 1.0, 1.0), v(GREEN, 1.0, 1.0, -1.0),
+    // Left
+    v(GREEN, -1.0, -1.0, -1.0), v(GREEN, -1.0, 1.0, -1.0), v(GREEN, -1.0, 1.0, 1.0),
+    v(GREEN, -1.0, -1.0, -1.0), v(GREEN, -1.0, 1.0, 1.0), v(GREEN, -1.0, -1.0, 1.0),
+    // Top
+    v(BLUE, -1.0, 1.0, -1.0), v(BLUE, 1.0, 1.0, -1.0), v(BLUE, 1.0, 1.0, 1.0),
+    v(BLUE, -1.0, 1.0, -1.0), v(BLUE, 1.0, 1.0, 1.0), v(BLUE, -1.0, 1.0, 1.0),
+    // Bottom
+    v(BLUE, -1.0, -1.0, -1.0), v(BLUE, -1.0, -1.0, 1.0), v(BLUE, 1.0, -1.0, 1.0),
+    v(BLUE, -1.0, -1.0, -1.0), v(BLUE, 1.0, -1.0, 1.0), v(BLUE, 1.0, -1.0, -1.0),
+]);
+
+fn psp_main() {
+    unsafe { psp_main_inner() }
+}
+
+unsafe fn psp_main_inner() {
+    psp::enable_home_button();
+
+    let allocator = get_vram_allocator().unwrap();
+    let fbp0 = allocator.alloc_framebuffer(BUF_WIDTH, SCREEN_HEIGHT, TexturePixelFormat::Psm8888).unwrap();
+    let fbp1 = allocator.alloc_framebuffer(BUF_WIDTH, SCREEN_HEIGHT, TexturePixelFormat::Psm8888).unwrap();
+    let zbp = allocator.alloc_framebuffer(BUF_WIDTH, SCREEN_HEIGHT, TexturePixelFormat::Psm4444).unwrap();
+
+    sys::sceGumLoadIdentity();
+    sys::sceGuInit();
+
+    sys::sceGuStart(GuContextType::Direct, &raw mut LIST.0 as *mut _);
+    sys::sceGuDrawBuffer(DisplayPixelFormat::Psm8888, fbp0.as_mut_ptr_from_zero() as _, BUF_WIDTH as i32);
+    sys::sceGuDispBuffer(SCREEN_WIDTH as i32, SCREEN_HEIGHT as i32, fbp1.as_mut_ptr_from_zero() as _, BUF_WIDTH as i32);
+    sys::sceGuDepthBuffer(zbp.as_mut_ptr_from_zero() as _, BUF_WIDTH as i32);
+    sys::sceGuOffset(2048 - (SCREEN_WIDTH / 2), 2048 - (SCREEN_HEIGHT / 2));
+    sys::sceGuViewport(2048, 2048, SCREEN_WIDTH as i32, SCREEN_HEIGHT as i32);
+    sys::sceGuDepthRange(65535, 0);
+    sys::sceGuScissor(0, 0, SCREEN_WIDTH as i32, SCREEN_HEIGHT as i32);
+    sys::sceGuEnable(GuState::ScissorTest);
+    sys::sceGuDepthFunc(DepthFunc::GreaterOrEqual);
+    sys::sceGuEnable(GuState::DepthTest);
+    sys::sceGuFrontFace(FrontFaceDirection::Clockwise);
+    sys::sceGuShadeModel(ShadingModel::Smooth);
+    sys::sceGuEnable(GuState::CullFace);
+    sys::sceGuEnable(GuState::ClipPlanes);
+    sys::sceGuFinish();
+    sys::sceGuSync(GuSyncMode::Finish, GuSyncBehavior::Wait);
+
+    sys::sceDisplayWaitVblankStart();
+    sys::sceGuDisplay(true);
+
+    let mut val = 0.0;
+
+    loop {
+        sys::sceGuStart(GuContextType::Direct, &raw mut LIST.0 as *mut _);
+
+        sys::sceGuClearColor(0xff554433);
+        sys::sceGuClearDepth(0);
+        sys::sceGuClear(ClearBuffer::COLOR_BUFFER_BIT | ClearBuffer::DEPTH_BUFFER_BIT);
+
+        sys::sceGumMatrixMode(sys::MatrixMode::Projection);
+        sys::sceGumLoadIdentity();
+        sys::sceGumPerspective(75.0, 16.0 / 9.0, 0.5, 1000.0);
+
+        sys::sceGumMatrixMode(sys::MatrixMode::View);
+        sys::sceGumLoadIdentity();
+
+        sys::sceGumMatrixMode(sys::MatrixMode::Model);
+        sys::sceGumLoadIdentity();
+
+        let pos = ScePspFVector3 { x: 0.0, y: 0.0, z: -3.0 };
+        let rot = ScePspFVector3 {
+            x: val * 0.79 * (PI / 180.0),
+            y: val * 0.98 * (PI / 180.0),
+            z: val * 1.32 * (PI / 180.0),
+        };
+        sys::sceGumTranslate(&pos);
+        sys::sceGumRotateXYZ(&rot);
+
+        sys::sceGumDrawArray(
+            GuPrimitive::Triangles,
+            VertexType::COLOR_8888 | VertexType::VERTEX_32BITF | VertexType::TRANSFORM_3D,
+            12 * 3,
+            ptr::null_mut(),
+            &VERTICES as *const Align16<_> as *const _,
+        );
+
+        sys::sceGuFinish();
+        sys::sceGuSync(GuSyncMode::Finish, GuSyncBehavior::Wait);
+
+        sys::sceDisplayWaitVblankStart();
+        sys::sceGuSwapBuffers();
+
+        val += 1.0;
+    }
+}
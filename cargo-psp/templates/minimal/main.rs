@@ -0,0 +1,9 @@
+#![no_std]
+#![no_main]
+
+psp::module!("{{module_name}}", 1, 0);
+
+fn psp_main() {
+    psp::enable_home_button();
+    psp::dprintln!("Hello PSP from rust!");
+}
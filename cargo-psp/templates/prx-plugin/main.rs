@@ -0,0 +1,28 @@
+#![no_std]
+#![no_main]
+
+//! A PRX plugin.
+//!
+//! `cargo psp` builds `{{name}}.prx` next to the EBOOT under
+//! `target/mipsel-sony-psp/`. Load it with e.g. PSPLink or a plugin manager.
+
+use core::sync::atomic::{AtomicBool, Ordering};
+use psp::sys;
+
+psp::module!("{{module_name}}", 1, 0, stop = psp_stop);
+
+static RUNNING: AtomicBool = AtomicBool::new(true);
+
+fn psp_main() {
+    psp::dprintln!("{{module_name}} loaded");
+
+    while RUNNING.load(Ordering::Relaxed) {
+        // Do the plugin's work here, then yield to the rest of the system.
+        unsafe { sys::sceKernelDelayThread(100_000) };
+    }
+}
+
+/// Called when the plugin is stopped or unloaded.
+fn psp_stop() {
+    RUNNING.store(false, Ordering::Relaxed);
+}
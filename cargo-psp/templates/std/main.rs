@@ -0,0 +1,8 @@
+psp::module!("{{module_name}}", 1, 0);
+
+fn main() {
+    psp::enable_home_button();
+
+    let greeting = String::from("Hello PSP from the standard library!");
+    psp::dprintln!("{}", greeting);
+}
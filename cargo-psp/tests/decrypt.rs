@@ -0,0 +1,177 @@
+//! Extracts modules with the `decrypt-prx` binary.
+
+use cargo_psp::{gzip, kirk};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::{Command, Output},
+};
+
+const MODULE_NAME: &[u8] = b"test_module";
+
+/// Build a minimal PRX, as written by prxgen: an ELF with one loadable
+/// segment, whose `p_paddr` is the file offset of the module info.
+fn prx() -> Vec<u8> {
+    const DATA_OFFSET: u32 = 0x60;
+
+    let mut data = vec![0; 0x40];
+    data[2..4].copy_from_slice(&[1, 1]);
+    data[4..4 + MODULE_NAME.len()].copy_from_slice(MODULE_NAME);
+    data.extend((0..200).map(|i| i as u8));
+
+    let mut elf = vec![0; DATA_OFFSET as usize];
+    elf[..8].copy_from_slice(b"\x7fELF\x01\x01\x01\x00");
+
+    let mut put = |offset: usize, bytes: &[u8]| {
+        elf[offset..offset + bytes.len()].copy_from_slice(bytes);
+    };
+
+    put(0x10, &0xFFA0u16.to_le_bytes()); // e_type
+    put(0x12, &8u16.to_le_bytes()); // e_machine: MIPS
+    put(0x14, &1u32.to_le_bytes()); // e_version
+    put(0x18, &0x40u32.to_le_bytes()); // e_entry
+    put(0x1C, &52u32.to_le_bytes()); // e_phoff
+    put(0x28, &52u16.to_le_bytes()); // e_ehsize
+    put(0x2A, &32u16.to_le_bytes()); // e_phentsize
+    put(0x2C, &1u16.to_le_bytes()); // e_phnum
+    put(0x2E, &40u16.to_le_bytes()); // e_shentsize
+
+    // Program header.
+    put(52, &1u32.to_le_bytes()); // PT_LOAD
+    put(56, &DATA_OFFSET.to_le_bytes());
+    put(64, &DATA_OFFSET.to_le_bytes()); // p_paddr: module info
+    put(68, &(data.len() as u32).to_le_bytes());
+    put(72, &(data.len() as u32 + 0x100).to_le_bytes());
+    put(76, &5u32.to_le_bytes());
+    put(80, &16u32.to_le_bytes());
+
+    elf.extend(data);
+    elf
+}
+
+/// A fresh scratch directory for one test.
+fn scratch(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("cargo-psp-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn run(bin: &str, input: &Path, output: &Path) -> Output {
+    Command::new(bin).arg(input).arg(output).output().unwrap()
+}
+
+/// Wrap `payload` in a `~PSP` header.
+fn container(payload: &[u8], elf_size: usize, compressed: bool) -> Vec<u8> {
+    let mut module = vec![0; 0x150];
+    module[..4].copy_from_slice(b"~PSP");
+    module[6] = compressed as u8;
+    module[0x28..0x2C].copy_from_slice(&(elf_size as u32).to_le_bytes());
+    module[0x2C..0x30].copy_from_slice(&(0x150 + payload.len() as u32).to_le_bytes());
+    module[0xB0..0xB4].copy_from_slice(&(payload.len() as u32).to_le_bytes());
+    module.extend(payload);
+    module
+}
+
+/// Encrypt `prx` with KIRK command 1, with the keys in the header.
+fn encrypt(prx: &[u8]) -> Vec<u8> {
+    container(
+        &kirk::kirk1_encrypt(prx, &[1; 16], &[2; 16]),
+        prx.len(),
+        false,
+    )
+}
+
+/// Decrypt `module`, returning the ELF or the error message.
+fn decrypt(dir: &Path, module: &[u8]) -> Result<Vec<u8>, String> {
+    fs::write(dir.join("module"), module).unwrap();
+
+    let output = run(
+        env!("CARGO_BIN_EXE_decrypt-prx"),
+        &dir.join("module"),
+        &dir.join("out.prx"),
+    );
+
+    if output.status.success() {
+        Ok(fs::read(dir.join("out.prx")).unwrap())
+    } else {
+        Err(String::from_utf8_lossy(&output.stderr).into_owned())
+    }
+}
+
+#[test]
+fn encrypted_module_is_decrypted() {
+    let dir = scratch("encrypted");
+    let prx = prx();
+
+    assert_eq!(decrypt(&dir, &encrypt(&prx)), Ok(prx));
+}
+
+#[test]
+fn unencrypted_module_is_rejected() {
+    let dir = scratch("plain");
+
+    let error = decrypt(&dir, &prx()).unwrap_err();
+    assert!(error.contains("not encrypted"), "{}", error);
+}
+
+#[test]
+fn module_in_pbp_is_decrypted() {
+    let dir = scratch("pbp");
+    let prx = prx();
+    let signed = encrypt(&prx);
+
+    // Every component but DATA.PSP is empty.
+    let start = 0x28u32;
+    let end = start + signed.len() as u32;
+    let mut pbp = b"\0PBP".to_vec();
+    pbp.extend(0x1_0000u32.to_le_bytes());
+    for offset in [start, start, start, start, start, start, start, end] {
+        pbp.extend(offset.to_le_bytes());
+    }
+    pbp.extend(&signed);
+
+    assert_eq!(decrypt(&dir, &pbp), Ok(prx));
+}
+
+#[test]
+fn tampered_module_is_rejected() {
+    let dir = scratch("tampered");
+    let mut signed = encrypt(&prx());
+
+    let last = signed.len() - 1;
+    signed[last] ^= 1;
+
+    let error = decrypt(&dir, &signed).unwrap_err();
+    assert!(error.contains("hash mismatch"), "{}", error);
+}
+
+#[test]
+fn gzip_module_is_decompressed() {
+    let dir = scratch("gzip");
+    let prx = prx();
+
+    let mut compressed = vec![0x1F, 0x8B, 8, 0, 0, 0, 0, 0, 0, 0xFF];
+    compressed.extend(miniz_oxide::deflate::compress_to_vec(&prx, 6));
+    compressed.extend(gzip::crc32(&prx).to_le_bytes());
+    compressed.extend((prx.len() as u32).to_le_bytes());
+
+    let mut module = container(&compressed, prx.len(), true);
+
+    assert_eq!(decrypt(&dir, &module), Ok(prx));
+
+    let last = module.len() - 5;
+    module[last] ^= 1;
+    let error = decrypt(&dir, &module).unwrap_err();
+    assert!(error.contains("gzip"), "{}", error);
+}
+
+#[test]
+fn tagged_module_is_out_of_scope() {
+    let dir = scratch("tagged");
+    let mut signed = encrypt(&prx());
+    signed[0xD0..0xD4].copy_from_slice(&0x4C94_10F0u32.to_le_bytes());
+
+    let error = decrypt(&dir, &signed).unwrap_err();
+    assert!(error.contains("0x4c9410f0"), "{}", error);
+}
@@ -0,0 +1,77 @@
+//! Round trips packages through the `pack-pbp` and `unpack-pbp` binaries.
+
+use std::{fs, path::PathBuf, process::Command};
+
+/// A fresh scratch directory for one test.
+fn scratch(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("cargo-psp-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[test]
+fn unpacked_pbp_repacks_identically() {
+    let dir = scratch("repack");
+
+    // PARAM.SFO, ICON0.PNG, PIC1.PNG and DATA.PSP, with the others empty.
+    let components = [
+        Some(&b"\0PSF sfo"[..]),
+        Some(b"icon0 png data"),
+        None,
+        None,
+        Some(b"pic1"),
+        None,
+        Some(b"\x7fELF module contents"),
+        None,
+    ];
+
+    let mut pack_args = Vec::new();
+    for (i, component) in components.iter().enumerate() {
+        match component {
+            Some(bytes) => {
+                let path = dir.join(format!("component{}", i));
+                fs::write(&path, bytes).unwrap();
+                pack_args.push(path.display().to_string());
+            }
+            None => pack_args.push("NULL".to_string()),
+        }
+    }
+
+    let eboot = dir.join("EBOOT.PBP");
+    let output = Command::new(env!("CARGO_BIN_EXE_pack-pbp"))
+        .arg(&eboot)
+        .args(&pack_args)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    let packed = fs::read(&eboot).unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_unpack-pbp"))
+        .arg(&eboot)
+        .arg(&dir)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(fs::read(dir.join("PIC1.PNG")).unwrap(), b"pic1");
+    assert!(!dir.join("ICON1.PMF").exists());
+
+    // Run the suggested command, which must not overwrite the input.
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let hint = stdout
+        .lines()
+        .find_map(|line| line.strip_prefix("Repack with: pack-pbp "))
+        .expect("no repack hint");
+    let repack_args: Vec<_> = hint.split(' ').collect();
+    let repacked = dir.join("EBOOT.repacked.PBP");
+    assert_eq!(repack_args[0], repacked.display().to_string());
+
+    let output = Command::new(env!("CARGO_BIN_EXE_pack-pbp"))
+        .args(&repack_args)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+
+    assert_eq!(fs::read(&eboot).unwrap(), packed);
+    assert_eq!(fs::read(&repacked).unwrap(), packed);
+}
@@ -0,0 +1,279 @@
+//! Converts hand written ELF files with the `prxgen` binary.
+
+use std::{
+    convert::TryInto,
+    fs,
+    path::PathBuf,
+    process::{Command, Output},
+};
+
+const SHT_PROGBITS: u32 = 1;
+const SHT_SYMTAB: u32 = 2;
+const SHT_STRTAB: u32 = 3;
+const SHT_REL: u32 = 9;
+const SHF_ALLOC: u32 = 2;
+
+const R_MIPS_16: u32 = 1;
+const R_MIPS_32: u32 = 2;
+const R_MIPS_HI16: u32 = 5;
+const R_MIPS_LO16: u32 = 6;
+const R_MIPS_GPREL16: u32 = 7;
+
+/// Symbol 1 is defined in `.text`, symbol 2 is undefined.
+const DEFINED: u32 = 1 << 8;
+const UNDEFINED: u32 = 2 << 8;
+
+/// A fresh scratch directory for one test.
+fn scratch(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("cargo-psp-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn put(bytes: &mut Vec<u8>, offset: usize, values: &[u32]) {
+    let end = offset + values.len() * 4;
+    if bytes.len() < end {
+        bytes.resize(end, 0);
+    }
+
+    for (i, value) in values.iter().enumerate() {
+        bytes[offset + i * 4..][..4].copy_from_slice(&value.to_le_bytes());
+    }
+}
+
+/// Build an executable with `.text` and the module info in one LOAD segment,
+/// the given relocations against `.text`, and `phnum` program headers.
+fn elf(relocations: &[(u32, u32)], phnum: u16) -> Vec<u8> {
+    let shstrtab = b"\0.text\0.rodata.sceModuleInfo\0.rel.text\0.symtab\0.strtab\0.shstrtab\0";
+    let strtab = b"\0defined\0missing\0";
+
+    let mut elf = vec![0; 0x100];
+    elf[..8].copy_from_slice(b"\x7fELF\x01\x01\x01\x00");
+    elf[0x10..0x14].copy_from_slice(&[2, 0, 8, 0]); // ET_EXEC, EM_MIPS
+    put(&mut elf, 0x14, &[1, 0, 52]); // e_version, e_entry, e_phoff
+    elf[0x28..0x34].copy_from_slice(&[52, 0, 32, 0, phnum as u8, 0, 40, 0, 7, 0, 6, 0]);
+
+    // The LOAD segment, then a program header which prxgen can replace.
+    put(&mut elf, 52, &[1, 0x100, 0, 0, 0x74, 0x74, 5, 0x10]);
+    put(&mut elf, 84, &[0x6474_E551, 0, 0, 0, 0, 0, 6, 0x10]);
+
+    // `.text`, with a `lui` and `addiu` pair, and the module info.
+    put(&mut elf, 0x100, &[0; 16]);
+    put(&mut elf, 0x108, &[0x3C04_0000, 0x2484_0010]);
+    put(&mut elf, 0x140, &[0x0101_0000]);
+    elf.extend(b"test_module\0");
+    elf.resize(0x180, 0);
+
+    let rel_offset = elf.len() as u32;
+    for &(offset, info) in relocations {
+        let end = elf.len();
+        put(&mut elf, end, &[offset, info]);
+    }
+
+    let symtab_offset = elf.len() as u32;
+    let end = elf.len();
+    put(
+        &mut elf,
+        end,
+        &[0, 0, 0, 0, 1, 0x10, 0, 0x0001_0012, 9, 0, 0, 0x0000_0010],
+    );
+
+    let strtab_offset = elf.len() as u32;
+    elf.extend(strtab);
+    let shstrtab_offset = elf.len() as u32;
+    elf.extend(shstrtab);
+    elf.resize(elf.len().next_multiple_of(4), 0);
+
+    let shoff = elf.len() as u32;
+    put(&mut elf, 0x20, &[shoff]);
+
+    let sections: [[u32; 10]; 7] = [
+        [0; 10],
+        [1, SHT_PROGBITS, SHF_ALLOC | 4, 0, 0x100, 0x40, 0, 0, 16, 0],
+        [7, SHT_PROGBITS, SHF_ALLOC, 0x40, 0x140, 0x34, 0, 0, 4, 0],
+        [
+            29,
+            SHT_REL,
+            0,
+            0,
+            rel_offset,
+            relocations.len() as u32 * 8,
+            4,
+            1,
+            4,
+            8,
+        ],
+        [39, SHT_SYMTAB, 0, 0, symtab_offset, 48, 5, 1, 4, 16],
+        [
+            47,
+            SHT_STRTAB,
+            0,
+            0,
+            strtab_offset,
+            strtab.len() as u32,
+            0,
+            0,
+            1,
+            0,
+        ],
+        [
+            55,
+            SHT_STRTAB,
+            0,
+            0,
+            shstrtab_offset,
+            shstrtab.len() as u32,
+            0,
+            0,
+            1,
+            0,
+        ],
+    ];
+
+    for section in sections {
+        let end = elf.len();
+        put(&mut elf, end, &section);
+    }
+
+    elf
+}
+
+fn prxgen(name: &str, elf: &[u8], args: &[&str]) -> (Output, Option<Vec<u8>>) {
+    let dir = scratch(name);
+    fs::write(dir.join("in.elf"), elf).unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_prxgen"))
+        .arg(dir.join("in.elf"))
+        .arg(dir.join("out.prx"))
+        .args(args)
+        .output()
+        .unwrap();
+
+    let prx = fs::read(dir.join("out.prx")).ok();
+    (output, prx)
+}
+
+fn stderr(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stderr).into_owned()
+}
+
+fn word(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+}
+
+#[test]
+fn relocations_are_converted() {
+    let relocations = [
+        (0x0, R_MIPS_32 | DEFINED),
+        (0x8, R_MIPS_HI16 | DEFINED),
+        (0xC, R_MIPS_LO16 | DEFINED),
+    ];
+
+    let (output, prx) = prxgen("prxgen-a", &elf(&relocations, 2), &[]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(stderr(&output), "");
+
+    let prx = prx.unwrap();
+    assert_eq!(&prx[0x10..0x12], [0xA0, 0xFF], "e_type");
+    assert_eq!(word(&prx, 0x2C) & 0xFFFF, 1, "e_phnum");
+    assert_eq!(word(&prx, 52 + 12), 0x140, "p_paddr is the module info");
+
+    // `.rel.text` has the `SHT_PRXREL` type, and the symbols are cleared.
+    let shoff = word(&prx, 0x20) as usize;
+    assert_eq!(word(&prx, shoff + 3 * 40 + 4), 0x7000_00A0);
+    assert_eq!(word(&prx, 0x180 + 4), R_MIPS_32);
+    assert_eq!(word(&prx, 0x180 + 12), R_MIPS_HI16);
+}
+
+#[test]
+fn type_b_relocations_are_added_as_a_program_header() {
+    let relocations = [(0x0, R_MIPS_32 | DEFINED), (0x4, R_MIPS_32 | DEFINED)];
+
+    let (output, prx) = prxgen("prxgen-b", &elf(&relocations, 2), &["--relocations", "b"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    let prx = prx.unwrap();
+    assert_eq!(word(&prx, 0x2C) & 0xFFFF, 2, "e_phnum");
+    assert_eq!(word(&prx, 84), 0x7000_00A1);
+
+    let offset = word(&prx, 84 + 4) as usize;
+    let size = word(&prx, 84 + 16) as usize;
+    assert_eq!(offset % 16, 0);
+    assert_eq!(offset + size, prx.len());
+    assert_eq!(&prx[offset..offset + 4], [0, 0, 3, 3]);
+}
+
+#[test]
+fn type_b_relocations_need_a_spare_program_header() {
+    let relocations = [(0x0, R_MIPS_32 | DEFINED)];
+
+    let (output, _) = prxgen(
+        "prxgen-phnum",
+        &elf(&relocations, 1),
+        &["--relocations", "b"],
+    );
+    assert!(!output.status.success());
+    assert_eq!(
+        stderr(&output),
+        "error: there is no room for the relocation program header\n"
+    );
+}
+
+#[test]
+fn dropped_relocations_are_reported() {
+    let relocations = [
+        (0x0, R_MIPS_32 | DEFINED),
+        (0x4, R_MIPS_GPREL16 | DEFINED),
+        (0x8, R_MIPS_GPREL16 | DEFINED),
+        (0xC, R_MIPS_32 | UNDEFINED),
+        (0x10, R_MIPS_16 | DEFINED),
+        (0x14, R_MIPS_32 | (9 << 8)),
+    ];
+    let elf = elf(&relocations, 2);
+
+    let (output, _) = prxgen("prxgen-dropped", &elf, &[]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(
+        stderr(&output),
+        "warning: dropped R_MIPS_32 relocation at 0xc in .text against `missing`: undefined \
+         symbol\n\
+         warning: dropped R_MIPS_32 relocation at 0x14 in .text against `symbol 9`: invalid \
+         symbol index\n\
+         note: dropped 2 R_MIPS_GPREL16 relocations, as they are position independent\n\
+         warning: 2 relocations were dropped, the module may not work correctly\n"
+    );
+
+    // Type B has no 16-bit relocations, and `--verbose` lists every
+    // position independent relocation.
+    let (output, _) = prxgen(
+        "prxgen-dropped-b",
+        &elf,
+        &["--relocations", "b", "--verbose"],
+    );
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    let stderr = stderr(&output);
+    assert!(
+        stderr.contains(
+            "note: dropped R_MIPS_GPREL16 relocation at 0x4 in .text against `defined`: \
+             position independent\n"
+        ),
+        "{}",
+        stderr
+    );
+    assert!(
+        stderr.contains(
+            "warning: dropped R_MIPS_16 relocation at 0x10 in .text against `defined`: \
+             relocation type is not supported by type B\n"
+        ),
+        "{}",
+        stderr
+    );
+    assert!(
+        stderr
+            .ends_with("warning: 3 relocations were dropped, the module may not work correctly\n"),
+        "{}",
+        stderr
+    );
+}
@@ -0,0 +1,108 @@
+use alloc::{string::String, vec, vec::Vec};
+use core::fmt::Write;
+use psp::fs::aio::{block_on, AsyncFile};
+use psp::fs::{self, ErrorKind, File, OpenOptions, SeekFrom};
+use psp::test_runner::TestRunner;
+
+const DIR: &str = "host0:/fs_test";
+const FILE: &str = "host0:/fs_test/file.txt";
+const RENAMED: &str = "host0:/fs_test/renamed.txt";
+
+pub fn test_main(test_runner: &mut TestRunner) {
+    let _ = fs::remove_file(FILE);
+    let _ = fs::remove_file(RENAMED);
+    let _ = fs::remove_dir(DIR);
+
+    test_runner.check("create_dir", fs::create_dir(DIR), Ok(()));
+    test_runner.check(
+        "create_dir_exists",
+        fs::create_dir(DIR).map_err(|e| e.kind()),
+        Err(ErrorKind::AlreadyExists),
+    );
+    test_runner.check("write", fs::write(FILE, "hello"), Ok(()));
+
+    {
+        let mut file = OpenOptions::new().append(true).open(FILE).unwrap();
+        test_runner.check("fmt_write", write!(file, ", {}!", "world"), Ok(()));
+    }
+
+    test_runner.check(
+        "read_to_string",
+        fs::read_to_string(FILE).as_deref(),
+        Ok("hello, world!"),
+    );
+    test_runner.check(
+        "metadata_len",
+        fs::metadata(FILE).map(|m| (m.is_file(), m.len())),
+        Ok((true, 13)),
+    );
+    test_runner.check_true("metadata_dir", fs::metadata(DIR).unwrap().is_dir());
+
+    let file = File::open(FILE).unwrap();
+    let mut buf = [0; 5];
+    test_runner.check("seek", file.seek(SeekFrom::Start(7)), Ok(7));
+    test_runner.check("read_exact", file.read_exact(&mut buf), Ok(()));
+    test_runner.check("read_exact_contents", &buf, b"world");
+    test_runner.check(
+        "read_exact_eof",
+        file.read_exact(&mut buf).map_err(|e| e.kind()),
+        Err(ErrorKind::UnexpectedEof),
+    );
+    drop(file);
+
+    test_async(test_runner);
+
+    test_runner.check("rename", fs::rename(FILE, RENAMED), Ok(()));
+
+    let names: Vec<String> = fs::read_dir(DIR)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().unwrap().into())
+        .collect();
+    test_runner.check("read_dir", names, vec!["renamed.txt".into()]);
+
+    test_runner.check(
+        "open_missing",
+        File::open(FILE).map(drop).map_err(|e| e.kind()),
+        Err(ErrorKind::NotFound),
+    );
+    test_runner.check("remove_file", fs::remove_file(RENAMED), Ok(()));
+    test_runner.check("remove_dir", fs::remove_dir(DIR), Ok(()));
+}
+
+/// Reads `FILE`, which contains "hello, world!", asynchronously.
+fn test_async(test_runner: &mut TestRunner) {
+    let mut file = AsyncFile::open(FILE).unwrap();
+
+    let mut request = file.read(vec![0u8; 32]).unwrap();
+    let (buf, read) = loop {
+        if let Some(done) = request.try_complete() {
+            break done;
+        }
+    };
+    drop(request);
+    test_runner.check("aio_try_complete", read, Ok(13));
+    test_runner.check(
+        "aio_try_complete_contents",
+        &buf[..13],
+        &b"hello, world!"[..],
+    );
+
+    test_runner.check("aio_seek", file.file().seek(SeekFrom::Start(7)), Ok(7));
+    let (buf, read) = file.read(vec![0u8; 5]).unwrap().wait();
+    test_runner.check("aio_wait", read, Ok(5));
+    test_runner.check("aio_wait_contents", &buf[..], &b"world"[..]);
+
+    // Dropping a request cancels it, or waits for it to finish, so the file
+    // can start another operation right away.
+    file.file().seek(SeekFrom::Start(0)).unwrap();
+    drop(file.read(vec![0u8; 32]).unwrap());
+
+    file.file().seek(SeekFrom::Start(0)).unwrap();
+    let (_, read) = file.read(vec![0u8; 32]).unwrap().wait();
+    test_runner.check("aio_read_after_drop", read, Ok(13));
+
+    file.file().seek(SeekFrom::Start(0)).unwrap();
+    let (buf, read) = block_on(async { file.read(vec![0u8; 5]).unwrap().await });
+    test_runner.check("aio_block_on", read, Ok(5));
+    test_runner.check("aio_block_on_contents", &buf[..], &b"hello"[..]);
+}
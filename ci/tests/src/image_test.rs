@@ -0,0 +1,271 @@
+use alloc::vec;
+use alloc::vec::Vec;
+use psp::gu::TextureBuilder;
+use psp::image::{decode, Image, ImageData, ImageError};
+use psp::sys::{ClutPixelFormat, TexturePixelFormat};
+use psp::test_runner::TestRunner;
+
+/// A 1x1 RGBA PNG, compressed as a stored block. Chunk CRCs are not checked.
+const PNG: &[u8] = &[
+    0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n', //
+    0, 0, 0, 13, b'I', b'H', b'D', b'R', 0, 0, 0, 1, 0, 0, 0, 1, 8, 6, 0, 0, 0, 0, 0, 0, 0, //
+    0, 0, 0, 16, b'I', b'D', b'A', b'T', 0x78, 0x01, 0x01, 0x05, 0x00, 0xfa, 0xff, 0, 1, 2, 3, 4,
+    0x00, 0x19, 0x00, 0x0b, 0, 0, 0, 0, //
+    0, 0, 0, 0, b'I', b'E', b'N', b'D', 0, 0, 0, 0,
+];
+
+/// A 2x2 24-bit TGA, stored from the bottom row.
+const TGA: &[u8] = &[
+    0, 0, 2, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2, 0, 2, 0, 24, 0, //
+    1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12,
+];
+
+/// A 10x1 1-bit BMP with a black and white palette.
+#[rustfmt::skip]
+const BMP: &[u8] = &[
+    b'B', b'M', 66, 0, 0, 0, 0, 0, 0, 0, 62, 0, 0, 0, //
+    40, 0, 0, 0, 10, 0, 0, 0, 1, 0, 0, 0, 1, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+    0, 0, 2, 0, 0, 0, 0, 0, 0, 0, //
+    0, 0, 0, 0, 255, 255, 255, 0, //
+    0b1010_0000, 0b0100_0000, 0, 0,
+];
+
+/// A 4x4 PNG with 5 colors, of which the first 3 have alpha in tRNS. Its rows
+/// use the Sub, Up, Average and Paeth filters.
+#[rustfmt::skip]
+const PALETTED_PNG: &[u8] = &[
+    0x89, b'P', b'N', b'G', 0x0d, 0x0a, 0x1a, 0x0a, //
+    0x00, 0x00, 0x00, 0x0d, b'I', b'H', b'D', b'R', 0x00, 0x00, 0x00, 0x04, 0x00, 0x00, 0x00,
+    0x04, 0x08, 0x03, 0x00, 0x00, 0x00, 0x9e, 0x2f, 0x6e, 0x4c, //
+    0x00, 0x00, 0x00, 0x0f, b'P', b'L', b'T', b'E', 0xff, 0x00, 0x00, 0x00, 0xff, 0x00, 0x00,
+    0x00, 0xff, 0xff, 0xff, 0x00, 0x10, 0x20, 0x30, 0x5a, 0x8b, 0x0c, 0xa3, //
+    0x00, 0x00, 0x00, 0x03, b't', b'R', b'N', b'S', 0x00, 0x80, 0xff, 0xec, 0xf7, 0xb3, 0x18, //
+    0x00, 0x00, 0x00, 0x1c, b'I', b'D', b'A', b'T', 0x78, 0xda, 0x63, 0x64, 0x60, 0x64, 0x64,
+    0x64, 0x62, 0x61, 0x62, 0xf8, 0xc7, 0xfc, 0xff, 0x3f, 0x33, 0x13, 0xcb, 0x7f, 0x26, 0x86,
+    0xbf, 0x00, 0x21, 0xf6, 0x05, 0x13, 0x8a, 0x09, 0xab, 0x89, //
+    0x00, 0x00, 0x00, 0x00, b'I', b'E', b'N', b'D', 0xae, 0x42, 0x60, 0x82,
+];
+
+/// An 8x8 grayscale Adam7 PNG of the values `4 * i`. Every row uses the Up
+/// filter, which starts over at each pass.
+#[rustfmt::skip]
+const INTERLACED_PNG: &[u8] = &[
+    0x89, b'P', b'N', b'G', 0x0d, 0x0a, 0x1a, 0x0a, //
+    0x00, 0x00, 0x00, 0x0d, b'I', b'H', b'D', b'R', 0x00, 0x00, 0x00, 0x08, 0x00, 0x00, 0x00,
+    0x08, 0x08, 0x00, 0x00, 0x00, 0x01, 0x96, 0x63, 0xd1, 0xc1, //
+    0x00, 0x00, 0x00, 0x34, b'I', b'D', b'A', b'T', 0x78, 0xda, 0x63, 0x62, 0x60, 0x12, 0x60,
+    0x6a, 0x98, 0xc0, 0xc4, 0x21, 0xc1, 0xd4, 0xd0, 0xc0, 0xe4, 0xe0, 0x11, 0x10, 0x01, 0xa4,
+    0x81, 0x2c, 0x16, 0x1e, 0x11, 0x19, 0x26, 0x07, 0x20, 0x40, 0x22, 0x14, 0x54, 0x34, 0x74,
+    0x0c, 0x4c, 0x2c, 0x6c, 0xc0, 0x3c, 0xb8, 0x30, 0x0a, 0x03, 0x00, 0x73, 0x3b, 0x10, 0x3f,
+    0xc6, 0x18, 0x79, 0xe5, //
+    0x00, 0x00, 0x00, 0x00, b'I', b'E', b'N', b'D', 0xae, 0x42, 0x60, 0x82,
+];
+
+/// A 3x2 run length encoded 24-bit TGA, stored from the bottom row: a run of
+/// 4 pixels, which continues on the next row, then 2 raw pixels.
+const RLE_TGA: &[u8] = &[
+    0, 0, 10, 0, 0, 0, 0, 0, 0, 0, 0, 0, 3, 0, 2, 0, 24, 0, //
+    0x83, 1, 2, 3, //
+    0x01, 4, 5, 6, 7, 8, 9,
+];
+
+fn rgba(r: u8, g: u8, b: u8, a: u8) -> u32 {
+    u32::from_le_bytes([r, g, b, a])
+}
+
+pub fn test_main(test_runner: &mut TestRunner) {
+    let png = decode(PNG).unwrap();
+    test_runner.check(
+        "png_data",
+        png.data(),
+        &ImageData::Rgba(vec![rgba(1, 2, 3, 4)]),
+    );
+
+    let tga = decode(TGA).unwrap();
+    test_runner.check(
+        "tga_data",
+        tga.data(),
+        &ImageData::Rgba(vec![
+            rgba(9, 8, 7, 255),
+            rgba(12, 11, 10, 255),
+            rgba(3, 2, 1, 255),
+            rgba(6, 5, 4, 255),
+        ]),
+    );
+
+    let bmp = decode(BMP).unwrap();
+    test_runner.check(
+        "bmp_data",
+        bmp.data(),
+        &ImageData::Indexed {
+            indices: vec![1, 0, 1, 0, 0, 0, 0, 0, 0, 1],
+            palette: vec![rgba(0, 0, 0, 255), rgba(255, 255, 255, 255)],
+        },
+    );
+
+    test_runner.check(
+        "unknown_format",
+        decode(b"not an image").unwrap_err(),
+        ImageError::UnknownFormat,
+    );
+
+    let texture = bmp
+        .to_texture(
+            &TextureBuilder::new(16, 1, TexturePixelFormat::PsmT4),
+            false,
+        )
+        .unwrap();
+    test_runner.check(
+        "indexed_texture_clut",
+        texture.clut().map(|c| c.len()),
+        Some(2),
+    );
+
+    let texture = tga
+        .to_texture(
+            &TextureBuilder::new(4, 4, TexturePixelFormat::Psm5650),
+            true,
+        )
+        .unwrap();
+    test_runner.check(
+        "rgba_texture_size",
+        (texture.width(), texture.height()),
+        (4, 4),
+    );
+
+    test_paletted_png(test_runner);
+    test_interlaced_png(test_runner);
+    test_rle_tga(test_runner);
+    test_dither(test_runner);
+}
+
+fn test_paletted_png(test_runner: &mut TestRunner) {
+    let image = decode(PALETTED_PNG).unwrap();
+    test_runner.check(
+        "paletted_png_data",
+        image.data(),
+        &ImageData::Indexed {
+            indices: vec![0, 1, 2, 3, 4, 3, 2, 1, 1, 1, 4, 4, 0, 2, 4, 1],
+            palette: vec![
+                rgba(255, 0, 0, 0),
+                rgba(0, 255, 0, 128),
+                rgba(0, 0, 255, 255),
+                rgba(255, 255, 0, 255),
+                rgba(16, 32, 48, 255),
+            ],
+        },
+    );
+
+    let texture = image
+        .to_texture(&TextureBuilder::new(4, 4, TexturePixelFormat::PsmT8), false)
+        .unwrap();
+    test_runner.check(
+        "paletted_png_clut",
+        texture
+            .clut()
+            .map(|clut| (clut.len(), clut.format() as u32)),
+        Some((5, ClutPixelFormat::Psm8888 as u32)),
+    );
+
+    // Rows of `PsmT8` textures are at least 16 bytes.
+    let pixels = texture.level_pixels(0).unwrap();
+    test_runner.check(
+        "paletted_png_texture",
+        [
+            &pixels[..4],
+            &pixels[16..20],
+            &pixels[32..36],
+            &pixels[48..52],
+        ],
+        [
+            &[0, 1, 2, 3][..],
+            &[4, 3, 2, 1],
+            &[1, 1, 4, 4],
+            &[0, 2, 4, 1],
+        ],
+    );
+}
+
+fn test_interlaced_png(test_runner: &mut TestRunner) {
+    let image = decode(INTERLACED_PNG).unwrap();
+    let expected = (0..64)
+        .map(|i| {
+            let v = 4 * i as u8;
+            rgba(v, v, v, 255)
+        })
+        .collect();
+
+    test_runner.check(
+        "interlaced_png_size",
+        (image.width(), image.height()),
+        (8, 8),
+    );
+    test_runner.check(
+        "interlaced_png_data",
+        image.data(),
+        &ImageData::Rgba(expected),
+    );
+}
+
+fn test_rle_tga(test_runner: &mut TestRunner) {
+    let image = decode(RLE_TGA).unwrap();
+    test_runner.check(
+        "rle_tga_data",
+        image.data(),
+        &ImageData::Rgba(vec![
+            rgba(3, 2, 1, 255),
+            rgba(6, 5, 4, 255),
+            rgba(9, 8, 7, 255),
+            rgba(3, 2, 1, 255),
+            rgba(3, 2, 1, 255),
+            rgba(3, 2, 1, 255),
+        ]),
+    );
+
+    test_runner.check(
+        "rle_tga_truncated",
+        decode(&RLE_TGA[..RLE_TGA.len() - 1]).unwrap_err(),
+        ImageError::InvalidData,
+    );
+}
+
+fn test_dither(test_runner: &mut TestRunner) {
+    // Red and green are halfway between two steps of 5650, and blue an
+    // eighth of the way, so that many of the pixels are rounded up.
+    let color = rgba(0x84, 0x42, 0x21, 255);
+    let image = Image::new(4, 4, ImageData::Rgba(vec![color; 16])).unwrap();
+
+    let pixels = image.to_pixels(TexturePixelFormat::Psm5650, true).unwrap();
+    let pixels = pixels
+        .chunks_exact(2)
+        .map(|p| u16::from_le_bytes([p[0], p[1]]))
+        .collect::<Vec<_>>();
+
+    test_runner.check(
+        "dither_5650",
+        &pixels[..],
+        &[
+            0x2210, 0x2231, 0x2210, 0x2231, //
+            0x2231, 0x2210, 0x2a31, 0x2210, //
+            0x2210, 0x2231, 0x2210, 0x2231, //
+            0x2a31, 0x2210, 0x2231, 0x2210,
+        ][..],
+    );
+
+    // On average, the pixels are the original color.
+    let sum = |shift: u16, bits: u16| -> u32 {
+        let total = pixels
+            .iter()
+            .map(|&p| u32::from(p >> shift & ((1 << bits) - 1)))
+            .sum::<u32>();
+        total << (8 - bits)
+    };
+    test_runner.check(
+        "dither_5650_average",
+        [sum(0, 5), sum(5, 6), sum(11, 5)],
+        [16 * 0x84, 16 * 0x42, 16 * 0x21],
+    );
+
+    let pixels = image.to_pixels(TexturePixelFormat::Psm5650, false).unwrap();
+    test_runner.check("undithered_5650", &pixels[..], &[0x10, 0x22].repeat(16)[..]);
+}
@@ -8,7 +8,10 @@ extern crate alloc;
 use psp::test_runner::TestRunner;
 
 mod bmp_screenshot_test;
+mod fs_test;
+mod image_test;
 mod math_test;
+mod texture_test;
 mod vfpu_test;
 mod vram_test;
 
@@ -17,7 +20,10 @@ psp::module!("ci_tests", 1, 1);
 fn psp_main() {
     let tests = &[
         bmp_screenshot_test::test_main,
+        fs_test::test_main,
+        image_test::test_main,
         math_test::test_main,
+        texture_test::test_main,
         vfpu_test::test_main,
         vram_test::test_main,
     ];
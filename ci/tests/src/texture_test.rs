@@ -0,0 +1,299 @@
+use alloc::vec;
+use alloc::vec::Vec;
+use psp::gu::{Texture, TextureBuilder};
+use psp::sys::{ClutPixelFormat, TexturePixelFormat};
+use psp::test_runner::TestRunner;
+
+fn rgba(r: u8, g: u8, b: u8, a: u8) -> u32 {
+    u32::from_le_bytes([r, g, b, a])
+}
+
+fn words(bytes: &[u8]) -> Vec<u32> {
+    bytes
+        .chunks_exact(4)
+        .map(|w| u32::from_le_bytes([w[0], w[1], w[2], w[3]]))
+        .collect()
+}
+
+fn halfwords(bytes: &[u8]) -> Vec<u16> {
+    bytes
+        .chunks_exact(2)
+        .map(|h| u16::from_le_bytes([h[0], h[1]]))
+        .collect()
+}
+
+/// The offset, size, width, height and buffer width of every level.
+fn levels(texture: &Texture) -> Vec<(usize, usize, u32, u32, u32)> {
+    let base = texture.level_pixels(0).unwrap().as_ptr() as usize;
+
+    (0..=texture.mipmaps())
+        .map(|level| {
+            let pixels = texture.level_pixels(level).unwrap();
+            let (width, height, buffer_width) = texture.level_size(level).unwrap();
+
+            (
+                pixels.as_ptr() as usize - base,
+                pixels.len(),
+                width,
+                height,
+                buffer_width,
+            )
+        })
+        .collect()
+}
+
+fn test_swizzle(test_runner: &mut TestRunner) {
+    // 32 pixels of 32 bits are 8 blocks of 16 bytes, each holding 4 pixels
+    // of all 8 rows.
+    let pixels = (0..32 * 8).collect::<Vec<u32>>();
+    let texture = TextureBuilder::new(32, 8, TexturePixelFormat::Psm8888)
+        .swizzle(true)
+        .upload_rgba(&pixels)
+        .unwrap();
+    let swizzled = words(texture.level_pixels(0).unwrap());
+
+    test_runner.check(
+        "swizzle_first_block",
+        &swizzled[..12],
+        &[0, 1, 2, 3, 32, 33, 34, 35, 64, 65, 66, 67][..],
+    );
+    test_runner.check(
+        "swizzle_first_block_last_row",
+        &swizzled[28..36],
+        &[224, 225, 226, 227, 4, 5, 6, 7][..],
+    );
+    test_runner.check("swizzle_last_pixel", swizzled[255], 255);
+    test_runner.check_true(
+        "swizzle_all_pixels",
+        swizzled.iter().enumerate().all(|(i, &pixel)| {
+            let (block, row, column) = (i / 32, i / 4 % 8, i % 4);
+            pixel == (row * 32 + block * 4 + column) as u32
+        }),
+    );
+
+    // Rows of 32 4-bit pixels are a single block wide, so nothing moves.
+    let indices = (0..32 * 8 / 2).map(|i| i as u8).collect::<Vec<u8>>();
+    let texture = TextureBuilder::new(32, 8, TexturePixelFormat::PsmT4)
+        .swizzle(true)
+        .upload_raw(&indices)
+        .unwrap();
+    test_runner.check(
+        "swizzle_t4_single_block",
+        texture.level_pixels(0).unwrap(),
+        &indices[..],
+    );
+}
+
+fn test_mipmap_levels(test_runner: &mut TestRunner) {
+    // Rows are at least 16 bytes wide, whatever the level's width.
+    let cases = [
+        (
+            "mipmap_levels_t4",
+            TexturePixelFormat::PsmT4,
+            [
+                (0, 2048, 64, 64, 64),
+                (2048, 512, 32, 32, 32),
+                (2560, 256, 16, 16, 32),
+                (2816, 128, 8, 8, 32),
+            ],
+        ),
+        (
+            "mipmap_levels_t8",
+            TexturePixelFormat::PsmT8,
+            [
+                (0, 4096, 64, 64, 64),
+                (4096, 1024, 32, 32, 32),
+                (5120, 256, 16, 16, 16),
+                (5376, 128, 8, 8, 16),
+            ],
+        ),
+        (
+            "mipmap_levels_16",
+            TexturePixelFormat::Psm5650,
+            [
+                (0, 8192, 64, 64, 64),
+                (8192, 2048, 32, 32, 32),
+                (10240, 512, 16, 16, 16),
+                (10752, 128, 8, 8, 8),
+            ],
+        ),
+        (
+            "mipmap_levels_32",
+            TexturePixelFormat::Psm8888,
+            [
+                (0, 16384, 64, 64, 64),
+                (16384, 4096, 32, 32, 32),
+                (20480, 1024, 16, 16, 16),
+                (21504, 256, 8, 8, 8),
+            ],
+        ),
+    ];
+
+    for (name, format, expected) in cases {
+        let mut builder = TextureBuilder::new(64, 64, format);
+        builder.mipmaps(3);
+        let len = match format {
+            TexturePixelFormat::PsmT4 => 64 * 64 / 2,
+            TexturePixelFormat::PsmT8 => 64 * 64,
+            TexturePixelFormat::Psm5650 => 64 * 64 * 2,
+            _ => 64 * 64 * 4,
+        };
+        let texture = builder.upload_raw(&vec![0; len]).unwrap();
+
+        test_runner.check(name, &levels(&texture)[..], &expected[..]);
+    }
+
+    // Swizzled levels are at least 8 rows high.
+    let texture = TextureBuilder::new(64, 64, TexturePixelFormat::Psm8888)
+        .mipmaps(6)
+        .swizzle(true)
+        .upload_raw(&vec![0; 64 * 64 * 4])
+        .unwrap();
+    test_runner.check(
+        "mipmap_levels_swizzled",
+        &levels(&texture)[3..],
+        &[
+            (21504, 256, 8, 8, 8),
+            (21760, 128, 4, 4, 4),
+            (21888, 128, 2, 2, 4),
+            (22016, 128, 1, 1, 4),
+        ][..],
+    );
+}
+
+fn test_colors(test_runner: &mut TestRunner) {
+    let color = rgba(0x12, 0x34, 0x56, 0x78);
+    let cases = [
+        ("encode_5650", TexturePixelFormat::Psm5650, 0x51a2),
+        ("encode_5551", TexturePixelFormat::Psm5551, 0x28c2),
+        ("encode_4444", TexturePixelFormat::Psm4444, 0x7531),
+    ];
+
+    for (name, format, expected) in cases {
+        let texture = TextureBuilder::new(1, 1, format)
+            .upload_rgba(&[color])
+            .unwrap();
+        test_runner.check(
+            name,
+            halfwords(texture.level_pixels(0).unwrap())[0],
+            expected,
+        );
+    }
+
+    // Each 2x2 block of the first level holds the same pixel, so the second
+    // level is every pixel decoded and encoded again.
+    let round_trips = [
+        ("round_trip_5650", TexturePixelFormat::Psm5650),
+        ("round_trip_5551", TexturePixelFormat::Psm5551),
+        ("round_trip_4444", TexturePixelFormat::Psm4444),
+    ];
+
+    for (name, format) in round_trips {
+        let mut same = true;
+
+        for half in 0..2u32 {
+            let mut pixels = vec![0u8; 512 * 256 * 2];
+
+            for (i, pixel) in pixels.chunks_exact_mut(2).enumerate() {
+                let (x, y) = (i % 512 / 2, i / 512 / 2);
+                let value = (half << 15 | (y * 256 + x) as u32) as u16;
+                pixel.copy_from_slice(&value.to_le_bytes());
+            }
+
+            let texture = TextureBuilder::new(512, 256, format)
+                .mipmaps(1)
+                .upload_raw(&pixels)
+                .unwrap();
+            let level = halfwords(texture.level_pixels(1).unwrap());
+
+            same &= level
+                .iter()
+                .enumerate()
+                .all(|(i, &pixel)| u32::from(pixel) == half << 15 | i as u32);
+        }
+
+        test_runner.check_true(name, same);
+    }
+}
+
+fn test_downsample(test_runner: &mut TestRunner) {
+    let texture = TextureBuilder::new(2, 2, TexturePixelFormat::Psm8888)
+        .mipmaps(1)
+        .upload_rgba(&[
+            rgba(0, 0, 10, 255),
+            rgba(1, 0, 20, 255),
+            rgba(2, 0, 30, 255),
+            rgba(3, 255, 40, 255),
+        ])
+        .unwrap();
+    test_runner.check(
+        "downsample_8888",
+        words(texture.level_pixels(1).unwrap())[0],
+        rgba(2, 64, 25, 255),
+    );
+
+    // A single red pixel out of four is a quarter of the red, rounded.
+    let texture = TextureBuilder::new(2, 2, TexturePixelFormat::Psm5650)
+        .mipmaps(1)
+        .upload_raw(&[0x1f, 0, 0, 0, 0, 0, 0, 0])
+        .unwrap();
+    test_runner.check(
+        "downsample_5650",
+        halfwords(texture.level_pixels(1).unwrap())[0],
+        0x0008,
+    );
+
+    // The missing row of a texture one pixel high repeats the last one.
+    let texture = TextureBuilder::new(2, 1, TexturePixelFormat::Psm8888)
+        .mipmaps(1)
+        .upload_rgba(&[rgba(0, 0, 0, 255), rgba(255, 0, 0, 255)])
+        .unwrap();
+    test_runner.check(
+        "downsample_clamped",
+        words(texture.level_pixels(1).unwrap())[0],
+        rgba(128, 0, 0, 255),
+    );
+
+    // Indices cannot be averaged, so the top left one of each block is kept.
+    let indices = (0..16).collect::<Vec<u8>>();
+    let texture = TextureBuilder::new(4, 4, TexturePixelFormat::PsmT8)
+        .mipmaps(1)
+        .upload_indexed(&indices, &[0; 16])
+        .unwrap();
+    let level = texture.level_pixels(1).unwrap();
+    test_runner.check("downsample_indexed", [level[0], level[1]], [0, 2]);
+    test_runner.check(
+        "downsample_indexed_second_row",
+        [level[16], level[17]],
+        [8, 10],
+    );
+}
+
+fn test_clut(test_runner: &mut TestRunner) {
+    // The CLUT is loaded in blocks of 32 bytes.
+    let cases = [
+        ("clut_blocks_8888", ClutPixelFormat::Psm8888, 16, 2),
+        ("clut_blocks_5650", ClutPixelFormat::Psm5650, 16, 1),
+        ("clut_blocks_partial", ClutPixelFormat::Psm5650, 3, 1),
+        ("clut_blocks_4444", ClutPixelFormat::Psm4444, 17, 2),
+        ("clut_blocks_256", ClutPixelFormat::Psm8888, 256, 32),
+    ];
+
+    for (name, format, colors, blocks) in cases {
+        let texture = TextureBuilder::new(16, 16, TexturePixelFormat::PsmT8)
+            .clut_format(format)
+            .upload_indexed(&[0; 16 * 16], &vec![0; colors])
+            .unwrap();
+        let clut = texture.clut().unwrap();
+
+        test_runner.check(name, (clut.len(), clut.blocks()), (colors, blocks));
+    }
+}
+
+pub fn test_main(test_runner: &mut TestRunner) {
+    test_swizzle(test_runner);
+    test_mipmap_levels(test_runner);
+    test_colors(test_runner);
+    test_downsample(test_runner);
+    test_clut(test_runner);
+}
@@ -1,9 +1,13 @@
 use core::ptr::null_mut;
+use psp::gu::FrameBuffers;
+use psp::sys::TexturePixelFormat;
 use psp::test_runner::TestRunner;
-use psp::vram_alloc::get_vram_allocator;
+use psp::vram_alloc::{get_vram_allocator, VramAllocError};
+
+const FRAMEBUFFER_SIZE: u32 = 512 * 272 * 4;
 
 pub fn test_main(test_runner: &mut TestRunner) {
-    let mut alloc = get_vram_allocator().unwrap();
+    let alloc = get_vram_allocator().unwrap();
     test_runner.pass("allocator_initialization", "Received VRAM allocator.");
 
     let fake_alloc = get_vram_allocator();
@@ -18,41 +22,99 @@ pub fn test_main(test_runner: &mut TestRunner) {
         ),
     }
 
+    let total = alloc.total_mem();
+
     unsafe {
         let zero_ptr = null_mut();
+        let vram = psp::sys::sceGeEdramGetAddr();
 
-        let chunk1 = alloc.alloc_sized::<[u8; 4]>(1);
-        let chunk2 = alloc.alloc_sized::<[u8; 4]>(1);
+        let fb0 = alloc
+            .alloc_framebuffer(512, 272, TexturePixelFormat::Psm8888)
+            .unwrap();
+        let fb1 = alloc
+            .alloc_framebuffer(512, 272, TexturePixelFormat::Psm8888)
+            .unwrap();
+        let texture = alloc
+            .alloc_texture_pixels(64, 64, TexturePixelFormat::Psm8888)
+            .unwrap();
+        let data = alloc.alloc_sized::<[u8; 4]>(1).unwrap();
 
         test_runner.check_list(&[
             (
-                "first_chunk_addr_zero",
-                chunk1.as_mut_ptr_direct_to_vram(),
-                psp::sys::sceGeEdramGetAddr(),
+                "first_framebuffer_addr_direct",
+                fb0.as_mut_ptr_direct_to_vram(),
+                vram,
             ),
             (
-                "second_chunk_addr_zero",
-                chunk2.as_mut_ptr_direct_to_vram(),
-                psp::sys::sceGeEdramGetAddr().offset(4),
+                "second_framebuffer_addr_direct",
+                fb1.as_mut_ptr_direct_to_vram(),
+                vram.add(FRAMEBUFFER_SIZE as usize),
             ),
             (
-                "first_chunk_addr_direct",
-                chunk1.as_mut_ptr_from_zero(),
+                "first_framebuffer_addr_zero",
+                fb0.as_mut_ptr_from_zero(),
                 zero_ptr,
             ),
             (
-                "second_chunk_addr_direct",
-                chunk2.as_mut_ptr_from_zero(),
-                zero_ptr.offset(4),
+                "second_framebuffer_addr_zero",
+                fb1.as_mut_ptr_from_zero(),
+                zero_ptr.add(FRAMEBUFFER_SIZE as usize),
+            ),
+            (
+                "texture_addr_top",
+                texture.as_mut_ptr_from_zero(),
+                zero_ptr.add(total as usize - 64 * 64 * 4),
+            ),
+            (
+                "data_addr_aligned",
+                data.as_mut_ptr_from_zero(),
+                zero_ptr.add(total as usize - 64 * 64 * 4 - 16),
             ),
         ]);
 
-        let muh_item = alloc.move_to_vram([69u8; 16]);
+        let stats = alloc.stats();
+        test_runner.check_list(&[
+            ("stats_chunks", stats.chunks as u32, 4),
+            (
+                "stats_used",
+                stats.used,
+                2 * FRAMEBUFFER_SIZE + 64 * 64 * 4 + 4,
+            ),
+            ("stats_free", stats.free, total - stats.used),
+            (
+                "stats_largest_free",
+                stats.largest_free,
+                total - 64 * 64 * 4 - 16 - 2 * FRAMEBUFFER_SIZE,
+            ),
+        ]);
+
+        drop(texture);
+        let small_texture = alloc
+            .alloc_texture_pixels(32, 32, TexturePixelFormat::Psm8888)
+            .unwrap();
+        test_runner.check(
+            "freed_chunk_reused",
+            small_texture.as_mut_ptr_from_zero(),
+            zero_ptr.add(total as usize - 32 * 32 * 4),
+        );
+
+        test_runner.check(
+            "out_of_memory",
+            alloc.alloc(total).map(drop),
+            Err(VramAllocError::OutOfMemory),
+        );
+        test_runner.check(
+            "zero_size",
+            alloc.alloc(0).map(drop),
+            Err(VramAllocError::InvalidSize),
+        );
+
+        let mut muh_item = alloc.move_to_vram([69u8; 16]).unwrap();
 
         test_runner.check(
             "vram_moved_addr",
             muh_item.as_mut_ptr(),
-            0x4000008 as *const u8 as _,
+            vram.add(total as usize - 32 * 32 * 4 - 16),
         );
 
         test_runner.check("vram_storage_len", muh_item.len(), 16);
@@ -60,4 +122,35 @@ pub fn test_main(test_runner: &mut TestRunner) {
         muh_item[15] = 42;
         test_runner.check("vram_storage_integrity2", muh_item[15], 42);
     }
+
+    test_runner.check("all_chunks_freed", alloc.stats().chunks, 0);
+
+    let buffers = FrameBuffers::alloc(&alloc).unwrap();
+    let texture = alloc
+        .alloc_texture_pixels(64, 64, TexturePixelFormat::Psm8888)
+        .unwrap();
+    let depth = buffers.depth.as_ref().unwrap();
+
+    test_runner.check_list(&[
+        ("frame_buffers_draw", buffers.draw.len(), FRAMEBUFFER_SIZE),
+        (
+            "frame_buffers_display",
+            buffers.display.len(),
+            FRAMEBUFFER_SIZE,
+        ),
+        ("frame_buffers_depth", depth.len(), FRAMEBUFFER_SIZE / 2),
+        ("frame_buffers_chunks", alloc.stats().chunks as u32, 4),
+    ]);
+    test_runner.check_true(
+        "frame_buffers_not_shared",
+        texture.as_mut_ptr_from_zero() as usize
+            >= depth.as_mut_ptr_from_zero() as usize + depth.len() as usize,
+    );
+
+    drop(texture);
+    drop(buffers);
+    test_runner.check("frame_buffers_freed", alloc.stats().chunks, 0);
+
+    drop(alloc);
+    test_runner.check_true("allocator_retaken", get_vram_allocator().is_ok());
 }
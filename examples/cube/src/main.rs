@@ -9,6 +9,7 @@ use psp::sys::{
     FrontFaceDirection, ShadingModel, GuState, TexturePixelFormat, DepthFunc,
     VertexType, ClearBuffer, MipmapLevel,
 };
+use psp::gu::vertex::{Position, TexCoord};
 use psp::vram_alloc::get_vram_allocator;
 use psp::{BUF_WIDTH, SCREEN_WIDTH, SCREEN_HEIGHT};
 
@@ -22,63 +23,68 @@ static FERRIS: Align16<[u8; IMAGE_SIZE * IMAGE_SIZE * 4]> = Align16(*include_byt
 
 static mut LIST: Align16<[u32; 0x40000]> = Align16([0; 0x40000]);
 
-#[repr(C, align(4))]
-struct Vertex {
-    u: f32,
-    v: f32,
-    x: f32,
-    y: f32,
-    z: f32,
+psp::vertex! {
+    struct Vertex {
+        uv: TexCoord<f32>,
+        pos: Position<f32>,
+    }
+}
+
+const fn vertex(u: f32, v: f32, x: f32, y: f32, z: f32) -> Vertex {
+    Vertex {
+        uv: TexCoord { u, v },
+        pos: Position { x, y, z },
+    }
 }
 
 static VERTICES: Align16<[Vertex; 12 * 3]> = Align16([
-    Vertex { u: 0.0, v: 0.0, x: -1.0, y: -1.0, z:  1.0}, // 0
-    Vertex { u: 1.0, v: 0.0, x: -1.0, y:  1.0, z:  1.0}, // 4
-    Vertex { u: 1.0, v: 1.0, x:  1.0, y:  1.0, z:  1.0}, // 5
+    vertex(0.0, 0.0, -1.0, -1.0,  1.0), // 0
+    vertex(1.0, 0.0, -1.0,  1.0,  1.0), // 4
+    vertex(1.0, 1.0,  1.0,  1.0,  1.0), // 5
 
-    Vertex { u: 0.0, v: 0.0, x: -1.0, y: -1.0, z:  1.0}, // 0
-    Vertex { u: 1.0, v: 1.0, x:  1.0, y:  1.0, z:  1.0}, // 5
-    Vertex { u: 0.0, v: 1.0, x:  1.0, y: -1.0, z:  1.0}, // 1
+    vertex(0.0, 0.0, -1.0, -1.0,  1.0), // 0
+    vertex(1.0, 1.0,  1.0,  1.0,  1.0), // 5
+    vertex(0.0, 1.0,  1.0, -1.0,  1.0), // 1
 
-    Vertex { u: 0.0, v: 0.0, x: -1.0, y: -1.0, z: -1.0}, // 3
-    Vertex { u: 1.0, v: 0.0, x:  1.0, y: -1.0, z: -1.0}, // 2
-    Vertex { u: 1.0, v: 1.0, x:  1.0, y:  1.0, z: -1.0}, // 6
+    vertex(0.0, 0.0, -1.0, -1.0, -1.0), // 3
+    vertex(1.0, 0.0,  1.0, -1.0, -1.0), // 2
+    vertex(1.0, 1.0,  1.0,  1.0, -1.0), // 6
 
-    Vertex { u: 0.0, v: 0.0, x: -1.0, y: -1.0, z: -1.0}, // 3
-    Vertex { u: 1.0, v: 1.0, x:  1.0, y:  1.0, z: -1.0}, // 6
-    Vertex { u: 0.0, v: 1.0, x: -1.0, y:  1.0, z: -1.0}, // 7
+    vertex(0.0, 0.0, -1.0, -1.0, -1.0), // 3
+    vertex(1.0, 1.0,  1.0,  1.0, -1.0), // 6
+    vertex(0.0, 1.0, -1.0,  1.0, -1.0), // 7
 
-    Vertex { u: 0.0, v: 0.0, x:  1.0, y: -1.0, z: -1.0}, // 0
-    Vertex { u: 1.0, v: 0.0, x:  1.0, y: -1.0, z:  1.0}, // 3
-    Vertex { u: 1.0, v: 1.0, x:  1.0, y:  1.0, z:  1.0}, // 7
+    vertex(0.0, 0.0,  1.0, -1.0, -1.0), // 0
+    vertex(1.0, 0.0,  1.0, -1.0,  1.0), // 3
+    vertex(1.0, 1.0,  1.0,  1.0,  1.0), // 7
 
-    Vertex { u: 0.0, v: 0.0, x:  1.0, y: -1.0, z: -1.0}, // 0
-    Vertex { u: 1.0, v: 1.0, x:  1.0, y:  1.0, z:  1.0}, // 7
-    Vertex { u: 0.0, v: 1.0, x:  1.0, y:  1.0, z: -1.0}, // 4
+    vertex(0.0, 0.0,  1.0, -1.0, -1.0), // 0
+    vertex(1.0, 1.0,  1.0,  1.0,  1.0), // 7
+    vertex(0.0, 1.0,  1.0,  1.0, -1.0), // 4
 
-    Vertex { u: 0.0, v: 0.0, x: -1.0, y: -1.0, z: -1.0}, // 0
-    Vertex { u: 1.0, v: 0.0, x: -1.0, y:  1.0, z: -1.0}, // 3
-    Vertex { u: 1.0, v: 1.0, x: -1.0, y:  1.0, z:  1.0}, // 7
+    vertex(0.0, 0.0, -1.0, -1.0, -1.0), // 0
+    vertex(1.0, 0.0, -1.0,  1.0, -1.0), // 3
+    vertex(1.0, 1.0, -1.0,  1.0,  1.0), // 7
 
-    Vertex { u: 0.0, v: 0.0, x: -1.0, y: -1.0, z: -1.0}, // 0
-    Vertex { u: 1.0, v: 1.0, x: -1.0, y:  1.0, z:  1.0}, // 7
-    Vertex { u: 0.0, v: 1.0, x: -1.0, y: -1.0, z:  1.0}, // 4
+    vertex(0.0, 0.0, -1.0, -1.0, -1.0), // 0
+    vertex(1.0, 1.0, -1.0,  1.0,  1.0), // 7
+    vertex(0.0, 1.0, -1.0, -1.0,  1.0), // 4
 
-    Vertex { u: 0.0, v: 0.0, x: -1.0, y:  1.0, z: -1.0}, // 0
-    Vertex { u: 1.0, v: 0.0, x:  1.0, y:  1.0, z: -1.0}, // 1
-    Vertex { u: 1.0, v: 1.0, x:  1.0, y:  1.0, z:  1.0}, // 2
+    vertex(0.0, 0.0, -1.0,  1.0, -1.0), // 0
+    vertex(1.0, 0.0,  1.0,  1.0, -1.0), // 1
+    vertex(1.0, 1.0,  1.0,  1.0,  1.0), // 2
 
-    Vertex { u: 0.0, v: 0.0, x: -1.0, y:  1.0, z: -1.0}, // 0
-    Vertex { u: 1.0, v: 1.0, x:  1.0, y:  1.0, z:  1.0}, // 2
-    Vertex { u: 0.0, v: 1.0, x: -1.0, y:  1.0, z:  1.0}, // 3
+    vertex(0.0, 0.0, -1.0,  1.0, -1.0), // 0
+    vertex(1.0, 1.0,  1.0,  1.0,  1.0), // 2
+    vertex(0.0, 1.0, -1.0,  1.0,  1.0), // 3
 
-    Vertex { u: 0.0, v: 0.0, x: -1.0, y: -1.0, z: -1.0}, // 4
-    Vertex { u: 1.0, v: 0.0, x: -1.0, y: -1.0, z:  1.0}, // 7
-    Vertex { u: 1.0, v: 1.0, x:  1.0, y: -1.0, z:  1.0}, // 6
+    vertex(0.0, 0.0, -1.0, -1.0, -1.0), // 4
+    vertex(1.0, 0.0, -1.0, -1.0,  1.0), // 7
+    vertex(1.0, 1.0,  1.0, -1.0,  1.0), // 6
 
-    Vertex { u: 0.0, v: 0.0, x: -1.0, y: -1.0, z: -1.0}, // 4
-    Vertex { u: 1.0, v: 1.0, x:  1.0, y: -1.0, z:  1.0}, // 6
-    Vertex { u: 0.0, v: 1.0, x:  1.0, y: -1.0, z: -1.0}, // 5
+    vertex(0.0, 0.0, -1.0, -1.0, -1.0), // 4
+    vertex(1.0, 1.0,  1.0, -1.0,  1.0), // 6
+    vertex(0.0, 1.0,  1.0, -1.0, -1.0), // 5
 ]);
 
 fn psp_main() {
@@ -89,11 +95,12 @@ unsafe fn psp_main_inner() {
     psp::enable_home_button();
 
     let allocator = get_vram_allocator().unwrap();
-    let fbp0 = allocator.alloc_texture_pixels(BUF_WIDTH, SCREEN_HEIGHT, TexturePixelFormat::Psm8888);
-    let fbp1 = allocator.alloc_texture_pixels(BUF_WIDTH, SCREEN_HEIGHT, TexturePixelFormat::Psm8888);
-    let zbp = allocator.alloc_texture_pixels(BUF_WIDTH, SCREEN_HEIGHT, TexturePixelFormat::Psm4444);
-    // Attempting to free the three VRAM chunks at this point would give a
-    // compile-time error since fbp0, fbp1 and zbp are used later on
+    let fbp0 = allocator.alloc_framebuffer(BUF_WIDTH, SCREEN_HEIGHT, TexturePixelFormat::Psm8888).unwrap();
+    let fbp1 = allocator.alloc_framebuffer(BUF_WIDTH, SCREEN_HEIGHT, TexturePixelFormat::Psm8888).unwrap();
+    let zbp = allocator.alloc_framebuffer(BUF_WIDTH, SCREEN_HEIGHT, TexturePixelFormat::Psm4444).unwrap();
+    // The three VRAM chunks are freed when dropped. Attempting to free them
+    // with `free_all` at this point would give a compile-time error since
+    // fbp0, fbp1 and zbp are used later on
     //allocator.free_all();
 
     sys::sceGumLoadIdentity();
@@ -172,7 +179,7 @@ unsafe fn psp_main_inner() {
 
         sys::sceGumDrawArray(
             GuPrimitive::Triangles,
-            VertexType::TEXTURE_32BITF | VertexType::VERTEX_32BITF | VertexType::TRANSFORM_3D,
+            <Vertex as psp::gu::Vertex>::TYPE | VertexType::TRANSFORM_3D,
             12 * 3,
             ptr::null_mut(),
             &VERTICES as *const Align16<_> as *const _,
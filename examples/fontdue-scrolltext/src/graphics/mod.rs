@@ -7,6 +7,7 @@ use psp::sys::{
     TextureColorComponent, TextureEffect, TexturePixelFormat, VertexType,
 };
 
+use psp::vram_alloc::{VramAllocator, VramMemChunk};
 use psp::Align16;
 use psp::{BUF_WIDTH, SCREEN_HEIGHT, SCREEN_WIDTH};
 
@@ -24,16 +25,22 @@ pub static mut LIST: Align16<[u32; 0x40000]> = Align16([0; 0x40000]);
 ///
 /// # Parameters
 ///
-/// - `allocator`: A reference to a `SimpleVramAllocator`.
-pub fn setup(allocator: &mut psp::vram_alloc::SimpleVramAllocator) {
-    unsafe {
-        let fbp0 = allocator
-            .alloc_texture_pixels(BUF_WIDTH, SCREEN_HEIGHT, TexturePixelFormat::Psm8888)
-            .as_mut_ptr_from_zero();
-        let fbp1 = allocator
-            .alloc_texture_pixels(BUF_WIDTH, SCREEN_HEIGHT, TexturePixelFormat::Psm8888)
-            .as_mut_ptr_from_zero();
+/// - `allocator`: A reference to a `VramAllocator`.
+///
+/// Returns the frame buffers, which must be kept alive while drawing.
+pub fn setup(allocator: &VramAllocator) -> [VramMemChunk<'_>; 2] {
+    let buffers = [
+        allocator
+            .alloc_framebuffer(BUF_WIDTH, SCREEN_HEIGHT, TexturePixelFormat::Psm8888)
+            .unwrap(),
+        allocator
+            .alloc_framebuffer(BUF_WIDTH, SCREEN_HEIGHT, TexturePixelFormat::Psm8888)
+            .unwrap(),
+    ];
+    let fbp0 = buffers[0].as_mut_ptr_from_zero();
+    let fbp1 = buffers[1].as_mut_ptr_from_zero();
 
+    unsafe {
         sys::sceGumLoadIdentity();
         sys::sceGuInit();
 
@@ -79,6 +86,8 @@ pub fn setup(allocator: &mut psp::vram_alloc::SimpleVramAllocator) {
         sys::sceGuSync(GuSyncMode::Finish, GuSyncBehavior::Wait);
         sys::sceGuDisplay(true);
     }
+
+    buffers
 }
 
 /// Clear the screen a particular colour.
@@ -15,10 +15,13 @@ static mut LIST: psp::Align16<[u32; 0x40000]> = psp::Align16([0; 0x40000]);
 fn psp_main() {
     psp::enable_home_button();
 
-    let mut allocator = get_vram_allocator().unwrap();
-    let fbp0 = allocator.alloc_texture_pixels(BUF_WIDTH, SCREEN_HEIGHT, TexturePixelFormat::Psm8888).as_mut_ptr_from_zero();
-    let fbp1 = allocator.alloc_texture_pixels(BUF_WIDTH, SCREEN_HEIGHT, TexturePixelFormat::Psm8888).as_mut_ptr_from_zero();
-    let zbp = allocator.alloc_texture_pixels(BUF_WIDTH, SCREEN_HEIGHT, TexturePixelFormat::Psm4444).as_mut_ptr_from_zero();
+    let allocator = get_vram_allocator().unwrap();
+    let fbp0_chunk = allocator.alloc_framebuffer(BUF_WIDTH, SCREEN_HEIGHT, TexturePixelFormat::Psm8888).unwrap();
+    let fbp0 = fbp0_chunk.as_mut_ptr_from_zero();
+    let fbp1_chunk = allocator.alloc_framebuffer(BUF_WIDTH, SCREEN_HEIGHT, TexturePixelFormat::Psm8888).unwrap();
+    let fbp1 = fbp1_chunk.as_mut_ptr_from_zero();
+    let zbp_chunk = allocator.alloc_framebuffer(BUF_WIDTH, SCREEN_HEIGHT, TexturePixelFormat::Psm4444).unwrap();
+    let zbp = zbp_chunk.as_mut_ptr_from_zero();
 
     unsafe {
 
@@ -16,8 +16,9 @@ static mut LIST: psp::Align16<[u32; 0x40000]> = psp::Align16([0; 0x40000]);
 fn psp_main() {
     psp::enable_home_button();
 
-    let mut allocator = get_vram_allocator().unwrap();
-    let fbp0 = allocator.alloc_texture_pixels(BUF_WIDTH, SCREEN_HEIGHT, TexturePixelFormat::Psm8888).as_mut_ptr_from_zero();
+    let allocator = get_vram_allocator().unwrap();
+    let fbp0_chunk = allocator.alloc_framebuffer(BUF_WIDTH, SCREEN_HEIGHT, TexturePixelFormat::Psm8888).unwrap();
+    let fbp0 = fbp0_chunk.as_mut_ptr_from_zero();
 
     unsafe {
         sys::sceGuInit();
@@ -2,22 +2,87 @@ use crate::sys::{self, SceSysMemBlockTypes, SceSysMemPartitionId, SceUid};
 use alloc::alloc::{GlobalAlloc, Layout};
 use core::{mem, ptr};
 
+static mut HEAP_PARTITION: SceSysMemPartitionId =
+    SceSysMemPartitionId::SceKernelPrimaryUserPartition;
+static mut HEAP_SIZE_LIMIT: usize = usize::MAX;
+static mut HEAP_USED: usize = 0;
+
+/// Set the partition the heap is allocated from, and the maximum number of
+/// bytes it may use. This is called by `module!` before `psp_main` runs.
+#[doc(hidden)]
+pub fn configure_heap(partition: SceSysMemPartitionId, size_limit: usize) {
+    unsafe {
+        HEAP_PARTITION = partition;
+        HEAP_SIZE_LIMIT = size_limit;
+    }
+}
+
+/// Whether a heap size limit is configured. Without one, usage is not
+/// tracked, to keep interrupts enabled while allocating.
+fn limited() -> bool {
+    unsafe { HEAP_SIZE_LIMIT != usize::MAX }
+}
+
+/// Account for `size` more bytes of heap usage, failing if this would exceed
+/// the limit.
+fn reserve(size: usize) -> bool {
+    if !limited() {
+        return true;
+    }
+
+    unsafe {
+        let flags = sys::sceKernelCpuSuspendIntr();
+
+        let used = HEAP_USED
+            .checked_add(size)
+            .filter(|&u| u <= HEAP_SIZE_LIMIT);
+        if let Some(used) = used {
+            HEAP_USED = used;
+        }
+
+        sys::sceKernelCpuResumeIntr(flags);
+        used.is_some()
+    }
+}
+
+fn release(size: usize) {
+    if !limited() {
+        return;
+    }
+
+    unsafe {
+        let flags = sys::sceKernelCpuSuspendIntr();
+        // Blocks allocated before the limit was configured were not counted.
+        HEAP_USED = HEAP_USED.saturating_sub(size);
+        sys::sceKernelCpuResumeIntr(flags);
+    }
+}
+
+/// The size of the system block needed for an allocation.
+fn block_size(layout: Layout) -> usize {
+    layout.size()
+        // We need to store the memory block ID.
+        + mem::size_of::<SceUid>()
+
+        // We also store padding bytes, in case the block returned from the
+        // system is not aligned. The count of padding bytes is also stored
+        // here, in the last byte.
+        + layout.align()
+}
+
 /// An allocator that hooks directly into the PSP OS memory allocator.
 struct SystemAlloc;
 
 unsafe impl GlobalAlloc for SystemAlloc {
     unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
-        let size = layout.size()
-            // We need to store the memory block ID.
-            + mem::size_of::<SceUid>()
+        let size = block_size(layout);
 
-            // We also store padding bytes, in case the block returned from the
-            // system is not aligned. The count of padding bytes is also stored
-            // here, in the last byte.
-            + layout.align();
+        if !reserve(size) {
+            return ptr::null_mut();
+        }
 
         let id = sys::sceKernelAllocPartitionMemory(
-            SceSysMemPartitionId::SceKernelPrimaryUserPartition,
+            HEAP_PARTITION,
             &b"block\0"[0],
             SceSysMemBlockTypes::Low,
             size as u32,
@@ -25,6 +90,7 @@ unsafe impl GlobalAlloc for SystemAlloc {
         );
 
         if id.0 < 0 {
+            release(size);
             return ptr::null_mut();
         }
 
@@ -40,12 +106,13 @@ unsafe impl GlobalAlloc for SystemAlloc {
     }
 
     #[inline(never)]
-    unsafe fn dealloc(&self, ptr: *mut u8, _layout: Layout) {
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
         let align_padding = *ptr.sub(1);
 
         let id = *ptr.sub(align_padding as usize).cast::<SceUid>().offset(-1);
 
         sys::sceKernelFreePartitionMemory(id);
+        release(block_size(layout));
     }
 }
 
@@ -0,0 +1,621 @@
+//! Crash reports for panics and CPU exceptions.
+//!
+//! When a panic is not caught, the message, location and a backtrace are
+//! printed to the debug screen and saved to [`REPORT_PATH`]. Kernel mode modules (see
+//! `kernel_module!`) can opt in to also report CPU exceptions, such as bus and
+//! address errors, with the faulting address and all registers, see
+//! `install_exception_handler`.
+//!
+//! Addresses are printed as loaded, together with the module's base address,
+//! and can be turned into functions and source lines on the host with:
+//!
+//! ```sh
+//! cargo psp addr2line target/mipsel-sony-psp/debug/app.prx --base <base> <addresses>
+//! ```
+//!
+//! Nothing here allocates, as the heap may be what broke.
+
+use crate::sys::{self, IoOpenFlags, SceKernelModuleInfo, SceUid};
+use core::{
+    ffi::c_void,
+    fmt::{self, Write},
+    mem,
+    panic::PanicInfo,
+};
+
+/// File the latest crash report is written to.
+///
+/// The file is truncated on every crash, so only the latest report is kept.
+/// Use `psp::panic::set_log` for a history of panics.
+pub const REPORT_PATH: &str = "ms0:/crash.txt";
+
+/// Maximum number of frames in a backtrace.
+const MAX_FRAMES: usize = 32;
+
+/// Longest line of a report. Longer lines are split.
+const LINE_LEN: usize = 80;
+
+/// Longest panic message kept for the report. Longer messages are truncated.
+const MESSAGE_LEN: usize = 256;
+
+/// Writes a report line by line, to both the debug screen and the report file.
+struct Report {
+    fd: Option<SceUid>,
+    line: [u8; LINE_LEN],
+    len: usize,
+    /// Whether every line so far was written to the file.
+    saved: bool,
+}
+
+impl Report {
+    fn create() -> Self {
+        let mut path = [0; REPORT_PATH.len() + 1];
+        path[..REPORT_PATH.len()].copy_from_slice(REPORT_PATH.as_bytes());
+
+        let fd = unsafe {
+            sys::sceIoOpen(
+                path.as_ptr(),
+                IoOpenFlags::WR_ONLY | IoOpenFlags::CREAT | IoOpenFlags::TRUNC,
+                0o777,
+            )
+        };
+
+        let fd = Some(fd).filter(|fd| fd.0 >= 0);
+
+        Self {
+            fd,
+            line: [0; LINE_LEN],
+            len: 0,
+            saved: fd.is_some(),
+        }
+    }
+
+    fn flush(&mut self) {
+        let line = &self.line[..self.len];
+
+        // Screen output is line based, so it is only updated once per line.
+        if let Ok(line) = core::str::from_utf8(line) {
+            crate::debug::print_args(format_args!("{}", line));
+        }
+
+        if let Some(fd) = self.fd {
+            let written =
+                unsafe { sys::sceIoWrite(fd, line.as_ptr() as *const c_void, line.len()) };
+            self.saved &= written == line.len() as i32;
+        }
+
+        self.len = 0;
+    }
+
+    /// Close the report, returning whether it was saved to [`REPORT_PATH`].
+    fn finish(mut self) -> bool {
+        self.flush();
+
+        if let Some(fd) = self.fd.take() {
+            self.saved &= unsafe { sys::sceIoClose(fd) } >= 0;
+        }
+
+        self.saved
+    }
+}
+
+impl Write for Report {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for &b in s.as_bytes() {
+            // Only split lines on character boundaries.
+            if self.len >= LINE_LEN - 4 && b & 0xc0 != 0x80 {
+                self.line[self.len] = b'\n';
+                self.len += 1;
+                self.flush();
+            }
+
+            self.line[self.len] = b;
+            self.len += 1;
+
+            if b == b'\n' {
+                self.flush();
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// The name and location of the module containing this code.
+struct Module {
+    name: [u8; 28],
+    base: u32,
+    #[cfg(feature = "kernel")]
+    text_size: u32,
+}
+
+impl Module {
+    fn query() -> Option<Self> {
+        unsafe {
+            let id = sys::sceKernelGetModuleIdByAddress(Self::query as *const c_void);
+            if id.0 < 0 {
+                return None;
+            }
+
+            let mut info: SceKernelModuleInfo = mem::zeroed();
+            info.size = mem::size_of::<SceKernelModuleInfo>();
+
+            if sys::sceKernelQueryModuleInfo(id, &mut info) < 0 {
+                return None;
+            }
+
+            Some(Self {
+                name: info.name,
+                base: info.text_addr,
+                #[cfg(feature = "kernel")]
+                text_size: info.text_size,
+            })
+        }
+    }
+
+    fn name(&self) -> &str {
+        let len = self.name.iter().position(|&b| b == 0).unwrap_or(28);
+        core::str::from_utf8(&self.name[..len]).unwrap_or("?")
+    }
+}
+
+fn write_header(report: &mut Report, module: &Option<Module>) -> fmt::Result {
+    match module {
+        Some(module) => writeln!(
+            report,
+            "module {} loaded at {:#010x}",
+            module.name(),
+            module.base
+        ),
+        None => writeln!(report, "module base address unknown"),
+    }
+}
+
+/// A panic message, formatted on the stack.
+struct Message {
+    buf: [u8; MESSAGE_LEN],
+    len: usize,
+}
+
+impl Message {
+    fn as_str(&self) -> &str {
+        // Only whole characters are written.
+        core::str::from_utf8(&self.buf[..self.len]).unwrap_or("?")
+    }
+}
+
+impl Write for Message {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let mut end = s.len().min(MESSAGE_LEN - self.len);
+
+        while !s.is_char_boundary(end) {
+            end -= 1;
+        }
+
+        self.buf[self.len..self.len + end].copy_from_slice(&s.as_bytes()[..end]);
+        self.len += end;
+
+        Ok(())
+    }
+}
+
+/// The latest panic, kept until it is known whether it is caught.
+struct Panic {
+    message: Message,
+    frames: [u32; MAX_FRAMES],
+    count: usize,
+}
+
+static mut PANIC: Panic = Panic {
+    message: Message {
+        buf: [0; MESSAGE_LEN],
+        len: 0,
+    },
+    frames: [0; MAX_FRAMES],
+    count: 0,
+};
+
+/// Record a panic, with a backtrace from the point of the panic.
+///
+/// This is called by the panic handler, before unwinding starts, as the
+/// backtrace is lost once the stack is unwound. Nothing is reported until
+/// [`report_panic`] is called.
+#[doc(hidden)]
+pub fn record_panic(info: &PanicInfo) {
+    let panic = unsafe { &mut *core::ptr::addr_of_mut!(PANIC) };
+
+    panic.message.len = 0;
+    let _ = write!(panic.message, "panicked");
+
+    if let Some(location) = info.location() {
+        let _ = write!(
+            panic.message,
+            " at {}:{}:{}",
+            location.file(),
+            location.line(),
+            location.column()
+        );
+    }
+
+    let _ = write!(panic.message, ": {}", info.message());
+
+    panic.count = backtrace(&mut panic.frames);
+}
+
+/// Report the panic recorded last, once it is known not to be caught.
+///
+/// Returns whether the report was saved to [`REPORT_PATH`].
+#[doc(hidden)]
+pub fn report_panic() -> bool {
+    let panic = unsafe { &*core::ptr::addr_of!(PANIC) };
+
+    let mut report = Report::create();
+    let _ = write_panic(
+        &mut report,
+        panic.message.as_str(),
+        &panic.frames[..panic.count],
+    );
+    report.finish()
+}
+
+fn write_panic(report: &mut Report, message: &str, frames: &[u32]) -> fmt::Result {
+    writeln!(report, "*** {}", message)?;
+    write_header(report, &Module::query())?;
+    writeln!(report, "backtrace:")?;
+
+    for (i, address) in frames.iter().enumerate() {
+        writeln!(report, "  #{:<2} {:#010x}", i, address)?;
+    }
+
+    Ok(())
+}
+
+/// Walk the stack of the current thread with the unwinder, returning the
+/// number of frames found.
+fn backtrace(frames: &mut [u32]) -> usize {
+    type Context = c_void;
+
+    extern "C" {
+        fn _Unwind_Backtrace(
+            trace: extern "C" fn(*mut Context, *mut c_void) -> i32,
+            arg: *mut c_void,
+        ) -> i32;
+        fn _Unwind_GetIP(context: *mut Context) -> usize;
+    }
+
+    const URC_NO_REASON: i32 = 0;
+    const URC_END_OF_STACK: i32 = 5;
+
+    struct Frames<'a> {
+        frames: &'a mut [u32],
+        count: usize,
+    }
+
+    extern "C" fn trace(context: *mut Context, arg: *mut c_void) -> i32 {
+        let state = unsafe { &mut *(arg as *mut Frames) };
+        let ip = unsafe { _Unwind_GetIP(context) };
+
+        if ip == 0 || state.count == state.frames.len() {
+            return URC_END_OF_STACK;
+        }
+
+        state.frames[state.count] = ip as u32;
+        state.count += 1;
+
+        URC_NO_REASON
+    }
+
+    let mut state = Frames { frames, count: 0 };
+    unsafe { _Unwind_Backtrace(trace, &mut state as *mut Frames as *mut c_void) };
+
+    state.count
+}
+
+#[cfg(feature = "kernel")]
+pub use exception::{install_exception_handler, ExceptionRegisters};
+
+#[cfg(feature = "kernel")]
+mod exception {
+    use super::{write_header, Module, Report, MAX_FRAMES};
+    use crate::sys;
+    use core::fmt::{self, Write};
+
+    /// The state of the CPU when an exception occurred.
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    pub struct ExceptionRegisters {
+        /// General purpose registers. `k0` and `k1` are used by the exception
+        /// handler itself, and are not meaningful.
+        pub gpr: [u32; 32],
+        pub status: u32,
+        pub cause: u32,
+        pub epc: u32,
+        pub bad_vaddr: u32,
+    }
+
+    impl ExceptionRegisters {
+        /// The exception code, from the cause register.
+        pub fn code(&self) -> u32 {
+            (self.cause >> 2) & 0x1f
+        }
+
+        /// Whether the exception happened in a branch delay slot, in which case
+        /// `epc` points to the branch.
+        pub fn in_delay_slot(&self) -> bool {
+            self.cause & 0x8000_0000 != 0
+        }
+    }
+
+    const REGISTER_NAMES: [&str; 32] = [
+        "zr", "at", "v0", "v1", "a0", "a1", "a2", "a3", "t0", "t1", "t2", "t3", "t4", "t5", "t6",
+        "t7", "s0", "s1", "s2", "s3", "s4", "s5", "s6", "s7", "t8", "t9", "k0", "k1", "gp", "sp",
+        "fp", "ra",
+    ];
+
+    fn exception_name(code: u32) -> &'static str {
+        match code {
+            0 => "interrupt",
+            1 => "TLB modification",
+            2 => "TLB miss (load)",
+            3 => "TLB miss (store)",
+            4 => "address error (load)",
+            5 => "address error (store)",
+            6 => "bus error (instruction)",
+            7 => "bus error (data)",
+            8 => "syscall",
+            9 => "breakpoint",
+            10 => "reserved instruction",
+            11 => "coprocessor unusable",
+            12 => "arithmetic overflow",
+            13 => "trap",
+            15 => "floating point",
+            _ => "unknown exception",
+        }
+    }
+
+    impl Module {
+        fn contains(&self, address: u32) -> bool {
+            address.wrapping_sub(self.base) < self.text_size
+        }
+    }
+
+    /// Number of stack words scanned for return addresses after an exception.
+    const STACK_SCAN_WORDS: usize = 256;
+
+    /// Whether `address` looks like a return address: inside the module and
+    /// right after a `jal` or `jalr` and its delay slot.
+    fn is_return_address(module: &Module, address: u32) -> bool {
+        if address % 4 != 0
+            || !module.contains(address)
+            || !module.contains(address.wrapping_sub(8))
+        {
+            return false;
+        }
+
+        let call = unsafe { *((address - 8) as *const u32) };
+        let opcode = call >> 26;
+
+        opcode == 0b000011 || (opcode == 0 && call & 0x3f == 0b001001)
+    }
+
+    fn report_exception(regs: &ExceptionRegisters) {
+        let mut report = Report::create();
+        let _ = write_exception(&mut report, regs);
+        report.finish();
+    }
+
+    fn write_exception(report: &mut Report, regs: &ExceptionRegisters) -> fmt::Result {
+        let module = Module::query();
+
+        writeln!(
+            report,
+            "*** exception: {} ({})",
+            exception_name(regs.code()),
+            regs.code()
+        )?;
+        write_header(report, &module)?;
+        writeln!(
+            report,
+            "epc {:#010x}{}  badvaddr {:#010x}",
+            regs.epc,
+            if regs.in_delay_slot() {
+                " (branch)"
+            } else {
+                ""
+            },
+            regs.bad_vaddr,
+        )?;
+        writeln!(
+            report,
+            "cause {:#010x}  status {:#010x}",
+            regs.cause, regs.status
+        )?;
+
+        for (names, values) in REGISTER_NAMES.chunks(4).zip(regs.gpr.chunks(4)) {
+            for (name, value) in names.iter().zip(values) {
+                write!(report, "{} {:08x}  ", name, value)?;
+            }
+            writeln!(report)?;
+        }
+
+        // There is no unwind information to follow from the faulting
+        // instruction, so look for anything resembling a return address on the
+        // stack instead. Some of these may be stale.
+        if let Some(module) = &module {
+            writeln!(report, "backtrace (guessed from the stack):")?;
+            writeln!(report, "  #0  {:#010x}", regs.epc)?;
+            writeln!(report, "  ra  {:#010x}", regs.gpr[31])?;
+
+            let sp = regs.gpr[29] & !3;
+            let mut found = 0;
+
+            for i in 0..STACK_SCAN_WORDS {
+                if found == MAX_FRAMES {
+                    break;
+                }
+
+                let slot = sp.wrapping_add(i as u32 * 4);
+
+                // Stop at the end of the thread's memory, or anything that is
+                // not RAM.
+                if !(0x0800_0000..0x0a00_0000).contains(&(slot & 0x1fff_ffff)) {
+                    break;
+                }
+
+                let value = unsafe { *(slot as *const u32) };
+
+                if is_return_address(module, value) {
+                    writeln!(report, "  sp+{:<3x} {:#010x}", i * 4, value)?;
+                    found += 1;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Install a handler that reports CPU exceptions. Kernel mode only.
+    ///
+    /// This is opt-in, e.g. with
+    /// `psp::kernel_module!("name", 1, 0, crash_handler = psp::crash::install_exception_handler)`,
+    /// see [`ModuleConfig::crash_handler`](crate::ModuleConfig::crash_handler).
+    ///
+    /// The handler has some limitations:
+    ///
+    /// - It replaces the system-wide default exception handler, including the
+    ///   one installed by PSPLink, for every module.
+    /// - Only faults in kernel mode threads are reported. The report is written
+    ///   by returning from the exception into this module's code, in the mode
+    ///   of the faulting thread, which does not work for user mode threads.
+    /// - It handles a single fault. The registers are saved to one static
+    ///   block, and the report is written on one static stack, so a fault in
+    ///   another thread while a report is being written corrupts both.
+    pub fn install_exception_handler() {
+        #[cfg(target_os = "psp")]
+        unsafe {
+            extern "C" {
+                fn __psp_exception_handler();
+            }
+
+            sys::sceKernelRegisterDefaultExceptionHandler(
+                __psp_exception_handler as *mut core::ffi::c_void,
+            );
+        }
+    }
+
+    #[cfg_attr(not(target_os = "psp"), allow(dead_code))]
+    static mut EXCEPTION_REGISTERS: ExceptionRegisters = ExceptionRegisters {
+        gpr: [0; 32],
+        status: 0,
+        cause: 0,
+        epc: 0,
+        bad_vaddr: 0,
+    };
+
+    /// Size of the stack used to write the report, as the stack of the faulting
+    /// thread may be what caused the exception.
+    const EXCEPTION_STACK_SIZE: usize = 16 * 1024;
+
+    #[cfg_attr(not(target_os = "psp"), allow(dead_code))]
+    static mut EXCEPTION_STACK: crate::Align16<[u8; EXCEPTION_STACK_SIZE]> =
+        crate::Align16([0; EXCEPTION_STACK_SIZE]);
+
+    // The handler runs in exception context. It saves the registers, then returns
+    // from the exception into `__psp_exception_trap`, which continues in the
+    // faulting thread with a fresh stack and reports the exception.
+    #[cfg(target_os = "psp")]
+    core::arch::global_asm!(
+        r#"
+            .section .text.__psp_exception_handler, "ax", @progbits
+            .global __psp_exception_handler
+            .global __psp_exception_trap
+            .set push
+            .set noreorder
+            .set noat
+            .set mips32
+
+        __psp_exception_handler:
+            lui $k0, %hi({regs})
+            addiu $k0, $k0, %lo({regs})
+
+            sw $0, 0($k0)
+            sw $1, 4($k0)
+            sw $2, 8($k0)
+            sw $3, 12($k0)
+            sw $4, 16($k0)
+            sw $5, 20($k0)
+            sw $6, 24($k0)
+            sw $7, 28($k0)
+            sw $8, 32($k0)
+            sw $9, 36($k0)
+            sw $10, 40($k0)
+            sw $11, 44($k0)
+            sw $12, 48($k0)
+            sw $13, 52($k0)
+            sw $14, 56($k0)
+            sw $15, 60($k0)
+            sw $16, 64($k0)
+            sw $17, 68($k0)
+            sw $18, 72($k0)
+            sw $19, 76($k0)
+            sw $20, 80($k0)
+            sw $21, 84($k0)
+            sw $22, 88($k0)
+            sw $23, 92($k0)
+            sw $24, 96($k0)
+            sw $25, 100($k0)
+            sw $26, 104($k0)
+            sw $27, 108($k0)
+            sw $28, 112($k0)
+            sw $29, 116($k0)
+            sw $30, 120($k0)
+            sw $31, 124($k0)
+
+            mfc0 $v0, $12
+            sw $v0, 128($k0)
+            mfc0 $v0, $13
+            sw $v0, 132($k0)
+            mfc0 $v0, $14
+            sw $v0, 136($k0)
+            mfc0 $v0, $8
+            sw $v0, 140($k0)
+
+            lui $v0, %hi(__psp_exception_trap)
+            addiu $v0, $v0, %lo(__psp_exception_trap)
+            mtc0 $v0, $14
+            nop
+            nop
+            eret
+            nop
+
+        __psp_exception_trap:
+            lui $sp, %hi({stack} + {stack_size})
+            addiu $sp, $sp, %lo({stack} + {stack_size})
+            lui $gp, %hi(_gp)
+            addiu $gp, $gp, %lo(_gp)
+            lui $a0, %hi({regs})
+            addiu $a0, $a0, %lo({regs})
+            jal {handler}
+            nop
+
+            .set pop
+        "#,
+        regs = sym EXCEPTION_REGISTERS,
+        stack = sym EXCEPTION_STACK,
+        stack_size = const EXCEPTION_STACK_SIZE,
+        handler = sym exception_trap,
+    );
+
+    #[cfg_attr(not(target_os = "psp"), allow(dead_code))]
+    extern "C" fn exception_trap(regs: &ExceptionRegisters) -> ! {
+        report_exception(regs);
+
+        unsafe {
+            sys::sceKernelExitDeleteThread(1);
+        }
+
+        loop {
+            core::hint::spin_loop()
+        }
+    }
+}
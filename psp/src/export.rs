@@ -0,0 +1,242 @@
+//! Export libraries from a PRX module.
+//!
+//! See the [`export!`](crate::export!) macro.
+
+use core::ffi::c_void;
+
+/// The entry table of an exported library, as pointed to by a `.lib.ent`
+/// entry.
+///
+/// This contains the NIDs of all functions followed by the NIDs of all
+/// variables, and then their addresses in the same order.
+#[doc(hidden)]
+#[repr(C)]
+pub struct ExportTable<const N: usize> {
+    pub nids: [u32; N],
+    pub addresses: [*const c_void; N],
+}
+
+unsafe impl<const N: usize> Sync for ExportTable<N> {}
+
+/// Calculate the NID of a function or variable name.
+///
+/// This is the first 4 bytes of the SHA-1 hash of the name, read as a little
+/// endian integer. For example, `nid("module_start")` is `0xD632ACDB`.
+///
+/// Note that most Sony libraries in later firmware versions use randomized
+/// NIDs, which cannot be derived from the name.
+pub const fn nid(name: &str) -> u32 {
+    let hash = sha1(name.as_bytes());
+    hash[0].swap_bytes()
+}
+
+// Known NIDs, including names whose padding fills a whole extra block.
+const _: () = assert!(nid("module_start") == 0xD632ACDB);
+const _: () = assert!(nid("module_stop") == 0xCEE8593C);
+const _: () = assert!(nid("module_info") == 0xF01D73A7);
+const _: () = assert!(nid("sceIoOpen") == 0x109F50BC);
+const _: () = assert!(nid("sceDisplayWaitVblankStart") == 0x984C27E7);
+const _: () = assert!(nid("aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa") == 0xDCBBC8C1);
+const _: () =
+    assert!(nid("aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa") == 0x0F33DBC2);
+
+/// Byte `i` of a message, after SHA-1 padding has been applied.
+const fn padded_byte(message: &[u8], i: usize, padded_len: usize) -> u8 {
+    let len = message.len();
+
+    if i < len {
+        message[i]
+    } else if i == len {
+        0x80
+    } else if i >= padded_len - 8 {
+        let bit_len = len as u64 * 8;
+        (bit_len >> ((padded_len - 1 - i) * 8)) as u8
+    } else {
+        0
+    }
+}
+
+/// A minimal `const` SHA-1 implementation, returning the hash as 5 big endian
+/// words.
+const fn sha1(message: &[u8]) -> [u32; 5] {
+    let mut h: [u32; 5] = [0x67452301, 0xEFCDAB89, 0x98BADCFE, 0x10325476, 0xC3D2E1F0];
+
+    let padded_len = (message.len() + 8) / 64 * 64 + 64;
+
+    let mut block = 0;
+    while block < padded_len {
+        let mut w = [0u32; 80];
+
+        let mut i = 0;
+        while i < 16 {
+            let offset = block + i * 4;
+            w[i] = u32::from_be_bytes([
+                padded_byte(message, offset, padded_len),
+                padded_byte(message, offset + 1, padded_len),
+                padded_byte(message, offset + 2, padded_len),
+                padded_byte(message, offset + 3, padded_len),
+            ]);
+            i += 1;
+        }
+
+        while i < 80 {
+            w[i] = (w[i - 3] ^ w[i - 8] ^ w[i - 14] ^ w[i - 16]).rotate_left(1);
+            i += 1;
+        }
+
+        let [mut a, mut b, mut c, mut d, mut e] = h;
+
+        i = 0;
+        while i < 80 {
+            let (f, k) = match i {
+                0..=19 => ((b & c) | (!b & d), 0x5A827999),
+                20..=39 => (b ^ c ^ d, 0x6ED9EBA1),
+                40..=59 => ((b & c) | (b & d) | (c & d), 0x8F1BBCDC),
+                _ => (b ^ c ^ d, 0xCA62C1D6),
+            };
+
+            let temp = a
+                .rotate_left(5)
+                .wrapping_add(f)
+                .wrapping_add(e)
+                .wrapping_add(k)
+                .wrapping_add(w[i]);
+
+            e = d;
+            d = c;
+            c = b.rotate_left(30);
+            b = a;
+            a = temp;
+
+            i += 1;
+        }
+
+        h[0] = h[0].wrapping_add(a);
+        h[1] = h[1].wrapping_add(b);
+        h[2] = h[2].wrapping_add(c);
+        h[3] = h[3].wrapping_add(d);
+        h[4] = h[4].wrapping_add(e);
+
+        block += 64;
+    }
+
+    h
+}
+
+/// Export a library from this module, so that other modules can import it.
+///
+/// Each function and variable is identified by its NID. This is either given
+/// explicitly with `#[psp(0x...)]`, or derived from the name with [`nid`].
+/// Functions must be declared `extern "C"`.
+///
+/// The flags are the library attributes, see [`SceLibAttr`]. User mode
+/// libraries usually use `0x0001` (auto export). Kernel mode modules can make
+/// their functions callable from user mode with `0x4001` (syscall export).
+///
+/// [`SceLibAttr`]: crate::sys::SceLibAttr
+///
+/// # Example
+///
+/// ```ignore
+/// psp::module!("my_plugin", 1, 0);
+///
+/// psp::export! {
+///     #![name = "MyPlugin"]
+///     #![flags = 0x0001]
+///     #![version = (1, 0)]
+///
+///     #[psp(0x12345678)]
+///     fn my_plugin_get_version;
+///     fn my_plugin_reset;
+///     static MY_PLUGIN_STATE;
+/// }
+///
+/// extern "C" fn my_plugin_get_version() -> u32 {
+///     0x0100
+/// }
+///
+/// extern "C" fn my_plugin_reset() {}
+///
+/// static MY_PLUGIN_STATE: u32 = 0;
+/// ```
+#[macro_export]
+macro_rules! export {
+    (
+        #![name = $lib_name:expr]
+        #![flags = $lib_flags:expr]
+        #![version = ($lib_major_version:expr, $lib_minor_version:expr)]
+
+        $($items:tt)*
+    ) => {
+        $crate::export!(
+            __ITEMS ($lib_name, $lib_flags, $lib_major_version, $lib_minor_version)
+            [] []
+            $($items)*
+        );
+    };
+
+    // Sort the items into functions and variables, resolving their NIDs.
+    (__ITEMS $lib:tt [$($funcs:tt)*] [$($vars:tt)*] #[psp($nid:expr)] fn $name:ident; $($rest:tt)*) => {
+        $crate::export!(__ITEMS $lib [$($funcs)* ($name, $nid)] [$($vars)*] $($rest)*);
+    };
+
+    (__ITEMS $lib:tt [$($funcs:tt)*] [$($vars:tt)*] fn $name:ident; $($rest:tt)*) => {
+        $crate::export!(
+            __ITEMS $lib
+            [$($funcs)* ($name, $crate::export::nid(stringify!($name)))]
+            [$($vars)*]
+            $($rest)*
+        );
+    };
+
+    (__ITEMS $lib:tt [$($funcs:tt)*] [$($vars:tt)*] #[psp($nid:expr)] static $name:ident; $($rest:tt)*) => {
+        $crate::export!(__ITEMS $lib [$($funcs)*] [$($vars)* ($name, $nid)] $($rest)*);
+    };
+
+    (__ITEMS $lib:tt [$($funcs:tt)*] [$($vars:tt)*] static $name:ident; $($rest:tt)*) => {
+        $crate::export!(
+            __ITEMS $lib
+            [$($funcs)*]
+            [$($vars)* ($name, $crate::export::nid(stringify!($name)))]
+            $($rest)*
+        );
+    };
+
+    // Generate the library entry and its table.
+    (
+        __ITEMS ($lib_name:expr, $lib_flags:expr, $lib_major_version:expr, $lib_minor_version:expr)
+        [$(($func:ident, $func_nid:expr))*]
+        [$(($var:ident, $var_nid:expr))*]
+    ) => {
+        const _: () = {
+            use core::ffi::c_void;
+
+            const FUNC_COUNT: usize = <[&str]>::len(&[$(stringify!($func)),*]);
+            const VAR_COUNT: usize = <[&str]>::len(&[$(stringify!($var)),*]);
+
+            #[link_section = ".rodata.sceResident"]
+            #[used]
+            static TABLE: $crate::export::ExportTable<{ FUNC_COUNT + VAR_COUNT }> =
+                $crate::export::ExportTable {
+                    nids: [$($func_nid,)* $($var_nid,)*],
+                    addresses: [
+                        $($func as *const c_void,)*
+                        $(&raw const $var as *const c_void,)*
+                    ],
+                };
+
+            #[link_section = ".lib.ent"]
+            #[used]
+            static ENTRY: $crate::sys::SceLibraryEntry = $crate::sys::SceLibraryEntry {
+                name: concat!($lib_name, "\0").as_ptr(),
+                // The minor version comes first, as in imported libraries.
+                version: ($lib_minor_version, $lib_major_version),
+                attribute: $crate::sys::SceLibAttr::from_bits_retain($lib_flags),
+                entry_len: 4,
+                var_count: VAR_COUNT as u8,
+                func_count: FUNC_COUNT as u16,
+                entry_table: &TABLE as *const _ as *const $crate::sys::SceLibraryEntryTable,
+            };
+        };
+    };
+}
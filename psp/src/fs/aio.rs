@@ -0,0 +1,257 @@
+//! Asynchronous file I/O.
+//!
+//! Reads and writes are started with [`AsyncFile::read`] and
+//! [`AsyncFile::write`], and run in the background on the I/O thread of the
+//! kernel while the caller keeps going, e.g. rendering a frame. The returned
+//! [`Request`] owns the buffer until the operation is over, and gives it back
+//! together with the result:
+//!
+//! ```no_run
+//! # extern crate alloc;
+//! use alloc::vec;
+//! use psp::fs::aio::AsyncFile;
+//!
+//! let mut file = AsyncFile::open("ms0:/level.bin")?;
+//! let mut request = file.read(vec![0u8; 64 * 1024])?;
+//!
+//! let (buf, read) = loop {
+//!     if let Some(done) = request.try_complete() {
+//!         break done;
+//!     }
+//!
+//!     // Render a frame...
+//! };
+//! # Ok::<(), psp::fs::Error>(())
+//! ```
+//!
+//! [`Request`] also implements [`Future`], so loads can be written as `async`
+//! functions and run with [`block_on`], or any other executor.
+//!
+//! Only one operation can be in flight per file. A request that is dropped
+//! before it completes is cancelled.
+
+use super::{Error, File, OpenOptions, Result};
+use crate::sys::{self, SceUid};
+use alloc::{boxed::Box, vec::Vec};
+use core::{
+    ffi::c_void,
+    future::Future,
+    marker::PhantomData,
+    ops::{Deref, DerefMut},
+    pin::Pin,
+    task::{Context, Poll, Waker},
+};
+
+/// Time the thread sleeps for between polls in [`block_on`], in microseconds.
+const POLL_INTERVAL: u32 = 100;
+
+/// A buffer which can be handed to the kernel for an asynchronous operation.
+///
+/// # Safety
+///
+/// The memory the buffer derefs to must stay at the same address, and stay
+/// valid, when the buffer is moved, and even if it is leaked. This holds for
+/// heap allocations and `'static` memory, but not for arrays on the stack.
+pub unsafe trait IoBuf: Deref<Target = [u8]> + 'static {}
+
+unsafe impl IoBuf for Vec<u8> {}
+unsafe impl IoBuf for Box<[u8]> {}
+unsafe impl IoBuf for &'static [u8] {}
+unsafe impl IoBuf for &'static mut [u8] {}
+
+/// A file for asynchronous reads and writes.
+#[derive(Debug)]
+pub struct AsyncFile {
+    file: File,
+}
+
+impl AsyncFile {
+    /// Open a file for reading.
+    pub fn open(path: &str) -> Result<Self> {
+        File::open(path).map(Self::from)
+    }
+
+    /// Create a file for writing, truncating it if it exists.
+    pub fn create(path: &str) -> Result<Self> {
+        File::create(path).map(Self::from)
+    }
+
+    /// Open a file with the given options.
+    pub fn open_with(options: &OpenOptions, path: &str) -> Result<Self> {
+        options.open(path).map(Self::from)
+    }
+
+    /// The underlying file, for synchronous operations such as seeking.
+    pub fn file(&self) -> &File {
+        &self.file
+    }
+
+    pub fn into_file(self) -> File {
+        self.file
+    }
+
+    /// Set the priority of the kernel thread running operations on this file.
+    ///
+    /// Lower values are a higher priority, as with thread priorities. Raise
+    /// it for streaming that must keep up, lower it for background loading.
+    pub fn set_priority(&self, priority: i32) -> Result<()> {
+        super::check(unsafe { sys::sceIoChangeAsyncPriority(self.file.fd(), priority) }).map(drop)
+    }
+
+    /// Start reading into `buf`, filling as much of it as possible.
+    pub fn read<B: IoBuf + DerefMut>(&mut self, mut buf: B) -> Result<Request<'_, B>> {
+        let len = buf.len().min(i32::MAX as usize) as u32;
+        let ret =
+            unsafe { sys::sceIoReadAsync(self.file.fd(), buf.as_mut_ptr() as *mut c_void, len) };
+
+        super::check(ret)?;
+
+        Ok(Request::new(self.file.fd(), buf))
+    }
+
+    /// Start writing `buf`.
+    pub fn write<B: IoBuf>(&mut self, buf: B) -> Result<Request<'_, B>> {
+        let len = buf.len().min(i32::MAX as usize) as u32;
+        let ret =
+            unsafe { sys::sceIoWriteAsync(self.file.fd(), buf.as_ptr() as *const c_void, len) };
+
+        super::check(ret)?;
+
+        Ok(Request::new(self.file.fd(), buf))
+    }
+}
+
+impl From<File> for AsyncFile {
+    fn from(file: File) -> Self {
+        Self { file }
+    }
+}
+
+/// An operation in flight, holding its buffer until it completes.
+///
+/// The buffer is returned with the number of bytes transferred by
+/// [`Request::wait`], [`Request::try_complete`] or by awaiting the request.
+#[must_use = "requests are cancelled when dropped"]
+#[derive(Debug)]
+pub struct Request<'a, B: IoBuf> {
+    fd: SceUid,
+    buf: Option<B>,
+    _file: PhantomData<&'a mut AsyncFile>,
+}
+
+impl<'a, B: IoBuf> Request<'a, B> {
+    fn new(fd: SceUid, buf: B) -> Self {
+        Self {
+            fd,
+            buf: Some(buf),
+            _file: PhantomData,
+        }
+    }
+
+    fn result(res: i64) -> Result<usize> {
+        if res < 0 {
+            Err(Error::from_code(res as i32))
+        } else {
+            Ok(res as usize)
+        }
+    }
+
+    /// Return the buffer and the result if the operation has completed,
+    /// without blocking.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called again after returning `Some`.
+    pub fn try_complete(&mut self) -> Option<(B, Result<usize>)> {
+        assert!(self.buf.is_some(), "request polled after completion");
+
+        let mut res = 0;
+        let ret = unsafe { sys::sceIoPollAsync(self.fd, &mut res) };
+
+        let result = match ret {
+            // Still in flight.
+            1 => return None,
+            0 => Self::result(res),
+            error => Err(Error::from_code(error)),
+        };
+
+        Some((self.buf.take().unwrap(), result))
+    }
+
+    /// Block until the operation completes, returning the buffer and the
+    /// result.
+    pub fn wait(mut self) -> (B, Result<usize>) {
+        let mut res = 0;
+        let ret = unsafe { sys::sceIoWaitAsync(self.fd, &mut res) };
+
+        let result = if ret < 0 {
+            Err(Error::from_code(ret))
+        } else {
+            Self::result(res)
+        };
+
+        (self.buf.take().unwrap(), result)
+    }
+
+    /// Cancel the operation and return the buffer. Part of the data may
+    /// already have been transferred.
+    pub fn cancel(mut self) -> B {
+        self.abort();
+        self.buf.take().unwrap()
+    }
+
+    fn abort(&mut self) {
+        unsafe {
+            // If the operation has already completed, cancelling fails and
+            // waiting collects the result.
+            sys::sceIoCancel(self.fd);
+
+            let mut res = 0;
+            sys::sceIoWaitAsync(self.fd, &mut res);
+        }
+    }
+}
+
+impl<B: IoBuf> Future for Request<'_, B> {
+    type Output = (B, Result<usize>);
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // `Request` is `Unpin`, the buffer does not move with it.
+        match self.get_mut().try_complete() {
+            Some(done) => Poll::Ready(done),
+            None => {
+                // The kernel has no way to wake us, so ask to be polled again.
+                cx.waker().wake_by_ref();
+                Poll::Pending
+            }
+        }
+    }
+}
+
+impl<B: IoBuf> Unpin for Request<'_, B> {}
+
+impl<B: IoBuf> Drop for Request<'_, B> {
+    fn drop(&mut self) {
+        // The kernel may still be using the buffer.
+        if self.buf.is_some() {
+            self.abort();
+        }
+    }
+}
+
+/// Run a future to completion on the current thread.
+///
+/// Between polls, the thread sleeps briefly so that other threads, including
+/// the kernel's I/O thread, can run.
+pub fn block_on<F: Future>(future: F) -> F::Output {
+    let mut future = core::pin::pin!(future);
+    let mut cx = Context::from_waker(Waker::noop());
+
+    loop {
+        if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+            return output;
+        }
+
+        unsafe { sys::sceKernelDelayThread(POLL_INTERVAL) };
+    }
+}
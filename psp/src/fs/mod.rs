@@ -0,0 +1,765 @@
+//! Filesystem access without `std`.
+//!
+//! This is a thin, safe layer over the `sceIo*` functions, modelled after
+//! `std::fs`. Paths are `&str`s including the device, e.g.
+//! `"ms0:/PSP/GAME/app/save.bin"` or `"host0:/log.txt"`, and are converted
+//! to NUL terminated strings on the stack, so nothing here allocates except
+//! for [`read`], [`read_to_string`] and the `read_to_*` methods of [`File`].
+//!
+//! ```no_run
+//! use core::fmt::Write;
+//! use psp::fs::{self, File};
+//!
+//! let mut file = File::create("ms0:/hello.txt")?;
+//! writeln!(file, "Hello PSP from rust!").unwrap();
+//!
+//! for entry in fs::read_dir("ms0:/PSP/GAME")? {
+//!     let entry = entry?;
+//!     psp::dprintln!("{:?} {}", entry.file_name(), entry.metadata().len());
+//! }
+//! # Ok::<(), psp::fs::Error>(())
+//! ```
+//!
+//! With the `embedded-io` feature, [`File`] also implements the
+//! `embedded_io::{Read, Write, Seek}` traits. Reads and writes which run in
+//! the background are in [`aio`].
+
+use crate::sys::{
+    self, IoOpenFlags, IoPermissions, IoStatAttr, IoStatMode, IoWhence, SceIoDirent, SceIoStat,
+    ScePspDateTime, SceUid,
+};
+use alloc::{string::String, vec::Vec};
+use core::{ffi::c_void, fmt, mem};
+
+pub mod aio;
+
+/// Longest path accepted, in bytes, not including the NUL terminator.
+pub const MAX_PATH: usize = 255;
+
+/// Permissions of newly created files and directories, unless changed with
+/// [`OpenOptions::mode`].
+const DEFAULT_MODE: IoPermissions = 0o777;
+
+/// Facility of errors that wrap a newlib `errno` value.
+const ERRNO_FACILITY: u32 = 0x8001_0000;
+
+/// Returned by the kernel when the device, e.g. the Memory Stick, is missing.
+const ERROR_NO_DEVICE: u32 = 0x8002_0321;
+
+/// A general category of filesystem error.
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ErrorKind {
+    /// The file or directory does not exist.
+    NotFound,
+    /// The file or directory already exists.
+    AlreadyExists,
+    /// The operation is not permitted on the file or directory.
+    PermissionDenied,
+    /// A component of the path is not a directory.
+    NotADirectory,
+    /// A directory was given where a file is expected.
+    IsADirectory,
+    /// The directory to remove is not empty.
+    DirectoryNotEmpty,
+    /// The device is full.
+    StorageFull,
+    /// The device is read only, e.g. `disc0:`.
+    ReadOnlyFilesystem,
+    /// The device does not exist, or no media is inserted.
+    NoDevice,
+    /// The device is in use.
+    Busy,
+    /// The process has too many files open.
+    TooManyOpenFiles,
+    /// An argument, such as the path, is invalid.
+    InvalidInput,
+    /// The data read is not valid, e.g. not UTF-8.
+    InvalidData,
+    /// The file ended before the buffer was filled.
+    UnexpectedEof,
+    /// A write returned 0 bytes written.
+    WriteZero,
+    /// Any other error.
+    Other,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Repr {
+    Code(i32),
+    Kind(ErrorKind),
+}
+
+/// An error returned by a filesystem operation.
+///
+/// Most errors wrap the error code returned by the system, see
+/// [`Error::code`].
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct Error(Repr);
+
+impl Error {
+    /// Create an error from a negative code returned by an `sceIo*` function.
+    pub fn from_code(code: i32) -> Self {
+        Self(Repr::Code(code))
+    }
+
+    /// The error code returned by the system, or `None` for errors detected
+    /// before calling it.
+    pub fn code(&self) -> Option<i32> {
+        match self.0 {
+            Repr::Code(code) => Some(code),
+            Repr::Kind(_) => None,
+        }
+    }
+
+    pub fn kind(&self) -> ErrorKind {
+        let code = match self.0 {
+            Repr::Code(code) => code as u32,
+            Repr::Kind(kind) => return kind,
+        };
+
+        if code == ERROR_NO_DEVICE {
+            return ErrorKind::NoDevice;
+        }
+
+        if code & 0xffff_0000 != ERRNO_FACILITY {
+            return ErrorKind::Other;
+        }
+
+        match code & 0xffff {
+            2 => ErrorKind::NotFound,
+            13 => ErrorKind::PermissionDenied,
+            16 => ErrorKind::Busy,
+            17 => ErrorKind::AlreadyExists,
+            19 => ErrorKind::NoDevice,
+            20 => ErrorKind::NotADirectory,
+            21 => ErrorKind::IsADirectory,
+            22 | 91 => ErrorKind::InvalidInput,
+            24 => ErrorKind::TooManyOpenFiles,
+            28 => ErrorKind::StorageFull,
+            30 => ErrorKind::ReadOnlyFilesystem,
+            90 => ErrorKind::DirectoryNotEmpty,
+            _ => ErrorKind::Other,
+        }
+    }
+}
+
+impl From<ErrorKind> for Error {
+    fn from(kind: ErrorKind) -> Self {
+        Self(Repr::Kind(kind))
+    }
+}
+
+impl fmt::Debug for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut debug = f.debug_struct("Error");
+        debug.field("kind", &self.kind());
+
+        if let Some(code) = self.code() {
+            debug.field("code", &format_args!("{:#010x}", code));
+        }
+
+        debug.finish()
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let description = match self.kind() {
+            ErrorKind::NotFound => "no such file or directory",
+            ErrorKind::AlreadyExists => "file exists",
+            ErrorKind::PermissionDenied => "permission denied",
+            ErrorKind::NotADirectory => "not a directory",
+            ErrorKind::IsADirectory => "is a directory",
+            ErrorKind::DirectoryNotEmpty => "directory not empty",
+            ErrorKind::StorageFull => "no space left on device",
+            ErrorKind::ReadOnlyFilesystem => "read-only filesystem",
+            ErrorKind::NoDevice => "no such device",
+            ErrorKind::Busy => "device or resource busy",
+            ErrorKind::TooManyOpenFiles => "too many open files",
+            ErrorKind::InvalidInput => "invalid argument",
+            ErrorKind::InvalidData => "invalid data",
+            ErrorKind::UnexpectedEof => "unexpected end of file",
+            ErrorKind::WriteZero => "failed to write whole buffer",
+            ErrorKind::Other => "I/O error",
+        };
+
+        match self.code() {
+            Some(code) => write!(f, "{} (error {:#010x})", description, code),
+            None => f.write_str(description),
+        }
+    }
+}
+
+pub type Result<T> = core::result::Result<T, Error>;
+
+/// Turn a negative return value into an error.
+fn check(ret: i32) -> Result<i32> {
+    if ret < 0 {
+        Err(Error::from_code(ret))
+    } else {
+        Ok(ret)
+    }
+}
+
+/// Run `f` with `path` as a NUL terminated string.
+fn with_path<R>(path: &str, f: impl FnOnce(*const u8) -> R) -> Result<R> {
+    if path.len() > MAX_PATH || path.as_bytes().contains(&0) {
+        return Err(ErrorKind::InvalidInput.into());
+    }
+
+    let mut buf = [0u8; MAX_PATH + 1];
+    buf[..path.len()].copy_from_slice(path.as_bytes());
+
+    Ok(f(buf.as_ptr()))
+}
+
+/// Options to open a file with, like `std::fs::OpenOptions`.
+///
+/// ```no_run
+/// use psp::fs::OpenOptions;
+///
+/// let log = OpenOptions::new()
+///     .append(true)
+///     .create(true)
+///     .open("ms0:/log.txt")?;
+/// # Ok::<(), psp::fs::Error>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct OpenOptions {
+    read: bool,
+    write: bool,
+    append: bool,
+    truncate: bool,
+    create: bool,
+    create_new: bool,
+    mode: IoPermissions,
+}
+
+impl OpenOptions {
+    /// Create a blank set of options, with everything set to `false`.
+    pub fn new() -> Self {
+        Self {
+            read: false,
+            write: false,
+            append: false,
+            truncate: false,
+            create: false,
+            create_new: false,
+            mode: DEFAULT_MODE,
+        }
+    }
+
+    pub fn read(&mut self, read: bool) -> &mut Self {
+        self.read = read;
+        self
+    }
+
+    pub fn write(&mut self, write: bool) -> &mut Self {
+        self.write = write;
+        self
+    }
+
+    /// Write at the end of the file. This implies `write`.
+    pub fn append(&mut self, append: bool) -> &mut Self {
+        self.append = append;
+        self
+    }
+
+    /// Truncate the file to 0 bytes when opened.
+    pub fn truncate(&mut self, truncate: bool) -> &mut Self {
+        self.truncate = truncate;
+        self
+    }
+
+    /// Create the file if it does not exist.
+    pub fn create(&mut self, create: bool) -> &mut Self {
+        self.create = create;
+        self
+    }
+
+    /// Create the file, failing if it already exists.
+    pub fn create_new(&mut self, create_new: bool) -> &mut Self {
+        self.create_new = create_new;
+        self
+    }
+
+    /// Unix permissions of the file if it is created. Defaults to `0o777`.
+    pub fn mode(&mut self, mode: IoPermissions) -> &mut Self {
+        self.mode = mode;
+        self
+    }
+
+    fn flags(&self) -> Result<IoOpenFlags> {
+        let write = self.write || self.append;
+
+        let mut flags = match (self.read, write) {
+            (true, false) => IoOpenFlags::RD_ONLY,
+            (false, true) => IoOpenFlags::WR_ONLY,
+            (true, true) => IoOpenFlags::RD_WR,
+            (false, false) => return Err(ErrorKind::InvalidInput.into()),
+        };
+
+        if self.append {
+            flags |= IoOpenFlags::APPEND;
+        }
+
+        if self.truncate {
+            flags |= IoOpenFlags::TRUNC;
+        }
+
+        if self.create_new {
+            flags |= IoOpenFlags::CREAT | IoOpenFlags::EXCL;
+        } else if self.create {
+            flags |= IoOpenFlags::CREAT;
+        }
+
+        Ok(flags)
+    }
+
+    pub fn open(&self, path: &str) -> Result<File> {
+        let flags = self.flags()?;
+        let fd = with_path(path, |path| unsafe {
+            sys::sceIoOpen(path, flags, self.mode)
+        })?;
+
+        check(fd.0).map(|_| File { fd })
+    }
+}
+
+impl Default for OpenOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Where to seek to in a [`File`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SeekFrom {
+    Start(u64),
+    End(i64),
+    Current(i64),
+}
+
+/// An open file, closed when dropped.
+///
+/// Like `std::fs::File`, all operations only need a shared reference, as the
+/// kernel keeps the file position.
+#[derive(Debug)]
+pub struct File {
+    fd: SceUid,
+}
+
+impl File {
+    /// Open a file for reading.
+    pub fn open(path: &str) -> Result<Self> {
+        OpenOptions::new().read(true).open(path)
+    }
+
+    /// Create a file for writing, truncating it if it exists.
+    pub fn create(path: &str) -> Result<Self> {
+        OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)
+    }
+
+    /// Create a new file for reading and writing, failing if it exists.
+    pub fn create_new(path: &str) -> Result<Self> {
+        OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .open(path)
+    }
+
+    /// The file descriptor, for use with `sys` functions.
+    pub fn fd(&self) -> SceUid {
+        self.fd
+    }
+
+    /// Read into `buf`, returning the number of bytes read. This is 0 at the
+    /// end of the file.
+    pub fn read(&self, buf: &mut [u8]) -> Result<usize> {
+        let len = buf.len().min(i32::MAX as usize) as u32;
+        let read = unsafe { sys::sceIoRead(self.fd, buf.as_mut_ptr() as *mut c_void, len) };
+
+        check(read).map(|read| read as usize)
+    }
+
+    /// Fill `buf`, failing with [`ErrorKind::UnexpectedEof`] if the file ends
+    /// first.
+    pub fn read_exact(&self, mut buf: &mut [u8]) -> Result<()> {
+        while !buf.is_empty() {
+            match self.read(buf)? {
+                0 => return Err(ErrorKind::UnexpectedEof.into()),
+                n => buf = &mut buf[n..],
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Read the rest of the file, appending it to `buf`, and return the number
+    /// of bytes read.
+    pub fn read_to_end(&self, buf: &mut Vec<u8>) -> Result<usize> {
+        const CHUNK: usize = 4096;
+
+        let start = buf.len();
+
+        loop {
+            let len = buf.len();
+            buf.resize(len + CHUNK, 0);
+
+            match self.read(&mut buf[len..]) {
+                Ok(0) => {
+                    buf.truncate(len);
+                    return Ok(len - start);
+                }
+                Ok(n) => buf.truncate(len + n),
+                Err(e) => {
+                    buf.truncate(len);
+                    return Err(e);
+                }
+            }
+        }
+    }
+
+    /// Read the rest of the file, which must be UTF-8, appending it to `buf`.
+    pub fn read_to_string(&self, buf: &mut String) -> Result<usize> {
+        let mut bytes = Vec::new();
+        self.read_to_end(&mut bytes)?;
+
+        let s = core::str::from_utf8(&bytes).map_err(|_| Error::from(ErrorKind::InvalidData))?;
+        buf.push_str(s);
+
+        Ok(s.len())
+    }
+
+    /// Write from `buf`, returning the number of bytes written.
+    pub fn write(&self, buf: &[u8]) -> Result<usize> {
+        let written = unsafe { sys::sceIoWrite(self.fd, buf.as_ptr() as *const c_void, buf.len()) };
+
+        check(written).map(|written| written as usize)
+    }
+
+    /// Write all of `buf`, failing with [`ErrorKind::WriteZero`] if the
+    /// device stops accepting data.
+    pub fn write_all(&self, mut buf: &[u8]) -> Result<()> {
+        while !buf.is_empty() {
+            match self.write(buf)? {
+                0 => return Err(ErrorKind::WriteZero.into()),
+                n => buf = &buf[n..],
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Move the file position, returning the new position from the start of
+    /// the file.
+    pub fn seek(&self, pos: SeekFrom) -> Result<u64> {
+        let (offset, whence) = match pos {
+            SeekFrom::Start(offset) => (offset as i64, IoWhence::Set),
+            SeekFrom::End(offset) => (offset, IoWhence::End),
+            SeekFrom::Current(offset) => (offset, IoWhence::Cur),
+        };
+
+        let pos = unsafe { sys::sceIoLseek(self.fd, offset, whence) };
+
+        if pos < 0 {
+            Err(Error::from_code(pos as i32))
+        } else {
+            Ok(pos as u64)
+        }
+    }
+
+    /// The current file position.
+    pub fn stream_position(&self) -> Result<u64> {
+        self.seek(SeekFrom::Current(0))
+    }
+}
+
+impl Drop for File {
+    fn drop(&mut self) {
+        unsafe { sys::sceIoClose(self.fd) };
+    }
+}
+
+impl fmt::Write for File {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.write_all(s.as_bytes()).map_err(|_| fmt::Error)
+    }
+}
+
+/// Information about a file or directory.
+#[derive(Debug, Clone, Copy)]
+pub struct Metadata {
+    stat: SceIoStat,
+}
+
+impl Metadata {
+    pub fn is_dir(&self) -> bool {
+        self.stat.st_mode.contains(IoStatMode::IFDIR)
+    }
+
+    pub fn is_file(&self) -> bool {
+        self.stat.st_mode.contains(IoStatMode::IFREG)
+    }
+
+    /// Size of the file in bytes.
+    pub fn len(&self) -> u64 {
+        self.stat.st_size as u64
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn mode(&self) -> IoStatMode {
+        self.stat.st_mode
+    }
+
+    pub fn attributes(&self) -> IoStatAttr {
+        self.stat.st_attr
+    }
+
+    pub fn created(&self) -> ScePspDateTime {
+        self.stat.st_ctime
+    }
+
+    pub fn accessed(&self) -> ScePspDateTime {
+        self.stat.st_atime
+    }
+
+    pub fn modified(&self) -> ScePspDateTime {
+        self.stat.st_mtime
+    }
+
+    /// The raw status, for use with `sys` functions.
+    pub fn stat(&self) -> &SceIoStat {
+        &self.stat
+    }
+}
+
+/// Get information about a file or directory.
+pub fn metadata(path: &str) -> Result<Metadata> {
+    let mut stat: SceIoStat = unsafe { mem::zeroed() };
+    check(with_path(path, |path| unsafe {
+        sys::sceIoGetstat(path, &mut stat)
+    })?)?;
+
+    Ok(Metadata { stat })
+}
+
+/// Whether a file or directory exists at `path`.
+pub fn exists(path: &str) -> bool {
+    metadata(path).is_ok()
+}
+
+/// An iterator over the entries of a directory, returned by [`read_dir`].
+///
+/// The `.` and `..` entries are skipped.
+#[derive(Debug)]
+pub struct ReadDir {
+    fd: SceUid,
+}
+
+/// An entry of a directory.
+pub struct DirEntry {
+    dirent: SceIoDirent,
+}
+
+impl DirEntry {
+    /// The name of the entry as bytes, without the directory.
+    pub fn file_name_bytes(&self) -> &[u8] {
+        let name = &self.dirent.d_name;
+        let len = name.iter().position(|&b| b == 0).unwrap_or(name.len());
+
+        &name[..len]
+    }
+
+    /// The name of the entry, without the directory, or `None` if it is not
+    /// UTF-8.
+    pub fn file_name(&self) -> Option<&str> {
+        core::str::from_utf8(self.file_name_bytes()).ok()
+    }
+
+    pub fn metadata(&self) -> Metadata {
+        Metadata {
+            stat: self.dirent.d_stat,
+        }
+    }
+}
+
+impl fmt::Debug for DirEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DirEntry")
+            .field("file_name", &self.file_name())
+            .field("metadata", &self.metadata())
+            .finish()
+    }
+}
+
+impl Iterator for ReadDir {
+    type Item = Result<DirEntry>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            // `d_private` must be zeroed, or the driver may write through it.
+            let mut dirent: SceIoDirent = unsafe { mem::zeroed() };
+
+            match check(unsafe { sys::sceIoDread(self.fd, &mut dirent) }) {
+                Ok(0) => return None,
+                Ok(_) => {
+                    let entry = DirEntry { dirent };
+
+                    if !matches!(entry.file_name_bytes(), b"." | b"..") {
+                        return Some(Ok(entry));
+                    }
+                }
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+}
+
+impl Drop for ReadDir {
+    fn drop(&mut self) {
+        unsafe { sys::sceIoDclose(self.fd) };
+    }
+}
+
+/// Iterate over the entries of a directory.
+pub fn read_dir(path: &str) -> Result<ReadDir> {
+    let fd = with_path(path, |path| unsafe { sys::sceIoDopen(path) })?;
+
+    check(fd.0).map(|_| ReadDir { fd })
+}
+
+/// Read a whole file.
+pub fn read(path: &str) -> Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    File::open(path)?.read_to_end(&mut bytes)?;
+
+    Ok(bytes)
+}
+
+/// Read a whole file, which must be UTF-8.
+pub fn read_to_string(path: &str) -> Result<String> {
+    let mut s = String::new();
+    File::open(path)?.read_to_string(&mut s)?;
+
+    Ok(s)
+}
+
+/// Write `contents` to a file, replacing it if it exists.
+pub fn write(path: &str, contents: impl AsRef<[u8]>) -> Result<()> {
+    File::create(path)?.write_all(contents.as_ref())
+}
+
+/// Create a directory. Its parent must exist.
+pub fn create_dir(path: &str) -> Result<()> {
+    check(with_path(path, |path| unsafe {
+        sys::sceIoMkdir(path, DEFAULT_MODE)
+    })?)
+    .map(drop)
+}
+
+/// Create a directory and all of its missing parents.
+pub fn create_dir_all(path: &str) -> Result<()> {
+    let path = path.trim_end_matches('/');
+
+    match create_dir(path) {
+        Ok(()) => return Ok(()),
+        Err(e) if e.kind() == ErrorKind::AlreadyExists => return Ok(()),
+        Err(e) if e.kind() != ErrorKind::NotFound => return Err(e),
+        Err(_) => {}
+    }
+
+    // Create the parent, but not the device root, e.g. `ms0:/`.
+    match path.rfind('/') {
+        Some(i) if !path[..i].ends_with(':') => create_dir_all(&path[..i])?,
+        _ => return Err(ErrorKind::NotFound.into()),
+    }
+
+    match create_dir(path) {
+        Err(e) if e.kind() == ErrorKind::AlreadyExists => Ok(()),
+        result => result,
+    }
+}
+
+/// Remove a file.
+pub fn remove_file(path: &str) -> Result<()> {
+    check(with_path(path, |path| unsafe { sys::sceIoRemove(path) })?).map(drop)
+}
+
+/// Remove an empty directory.
+pub fn remove_dir(path: &str) -> Result<()> {
+    check(with_path(path, |path| unsafe { sys::sceIoRmdir(path) })?).map(drop)
+}
+
+/// Rename a file or directory. Both paths must be on the same device.
+pub fn rename(from: &str, to: &str) -> Result<()> {
+    let ret = with_path(from, |from| {
+        with_path(to, |to| unsafe { sys::sceIoRename(from, to) })
+    })??;
+
+    check(ret).map(drop)
+}
+
+#[cfg(feature = "embedded-io")]
+mod embedded_io_impl {
+    use super::{Error, ErrorKind, File, SeekFrom};
+
+    impl embedded_io::Error for Error {
+        fn kind(&self) -> embedded_io::ErrorKind {
+            match self.kind() {
+                ErrorKind::NotFound => embedded_io::ErrorKind::NotFound,
+                ErrorKind::AlreadyExists => embedded_io::ErrorKind::AlreadyExists,
+                ErrorKind::PermissionDenied | ErrorKind::ReadOnlyFilesystem => {
+                    embedded_io::ErrorKind::PermissionDenied
+                }
+                ErrorKind::InvalidInput => embedded_io::ErrorKind::InvalidInput,
+                ErrorKind::InvalidData => embedded_io::ErrorKind::InvalidData,
+                ErrorKind::WriteZero => embedded_io::ErrorKind::WriteZero,
+                _ => embedded_io::ErrorKind::Other,
+            }
+        }
+    }
+
+    impl embedded_io::ErrorType for File {
+        type Error = Error;
+    }
+
+    impl embedded_io::Read for File {
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+            File::read(self, buf)
+        }
+    }
+
+    impl embedded_io::Write for File {
+        fn write(&mut self, buf: &[u8]) -> Result<usize, Error> {
+            File::write(self, buf)
+        }
+
+        fn flush(&mut self) -> Result<(), Error> {
+            // Writes are not buffered.
+            Ok(())
+        }
+    }
+
+    impl embedded_io::Seek for File {
+        fn seek(&mut self, pos: embedded_io::SeekFrom) -> Result<u64, Error> {
+            File::seek(
+                self,
+                match pos {
+                    embedded_io::SeekFrom::Start(offset) => SeekFrom::Start(offset),
+                    embedded_io::SeekFrom::End(offset) => SeekFrom::End(offset),
+                    embedded_io::SeekFrom::Current(offset) => SeekFrom::Current(offset),
+                },
+            )
+        }
+    }
+}
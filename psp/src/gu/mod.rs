@@ -0,0 +1,648 @@
+//! A safe layer over the GU.
+//!
+//! The functions in `sys` build display lists in global state, and nothing
+//! stops a missing `sceGuFinish`, a list that overflows its buffer, or a
+//! vertex array that is freed while the GE still reads it. Here, the GU is a
+//! [`Gu`] handle, of which there is only one, and each frame is recorded into
+//! a [`DisplayList`] through a [`Frame`]:
+//!
+//! ```no_run
+//! use psp::gu::vertex::{Color8888, ScreenPosition};
+//! use psp::gu::{DisplayList, FrameBuffers, Gu};
+//! use psp::sys::{ClearBuffer, GuPrimitive};
+//!
+//! psp::vertex! {
+//!     struct Vertex {
+//!         color: Color8888,
+//!         pos: ScreenPosition<f32>,
+//!     }
+//! }
+//!
+//! const fn vertex(color: u32, x: f32, y: f32) -> Vertex {
+//!     Vertex {
+//!         color: Color8888(color),
+//!         pos: ScreenPosition { x, y, z: 0.0 },
+//!     }
+//! }
+//!
+//! static TRIANGLE: [Vertex; 3] = [
+//!     vertex(0xff0000ff, 0.0, 0.0),
+//!     vertex(0xff00ff00, 480.0, 0.0),
+//!     vertex(0xffff0000, 240.0, 272.0),
+//! ];
+//!
+//! let allocator = psp::vram_alloc::get_vram_allocator().unwrap();
+//! let buffers = FrameBuffers::alloc(&allocator).unwrap();
+//!
+//! let mut list = DisplayList::new(256 * 1024);
+//! let mut gu = Gu::init(&mut list, buffers).unwrap();
+//!
+//! loop {
+//!     gu.frame(&mut list, |frame| {
+//!         frame.clear_color(0xff554433);
+//!         frame.clear(ClearBuffer::COLOR_BUFFER_BIT);
+//!         frame.draw_array(GuPrimitive::Triangles, &TRIANGLE);
+//!     });
+//! }
+//! ```
+//!
+//! Buffers passed to a frame must outlive the call to [`Gu::frame`], which
+//! only returns once the GE is done with the list. This holds even if the
+//! [`Frame`] is leaked, as it never leaves the closure.
+
+use crate::sys::{
+    self, BlendFactor, BlendOp, ClearBuffer, DepthFunc, DisplayPixelFormat, FrontFaceDirection,
+    GuContextType, GuPrimitive, GuState, GuSyncBehavior, GuSyncMode, GuTexWrapMode, MatrixMode,
+    MipmapLevel, ScePspFMatrix4, ShadingModel, TextureColorComponent, TextureEffect, TextureFilter,
+    TexturePixelFormat,
+};
+use crate::vram_alloc::{VramAllocError, VramAllocator, VramMemChunk};
+use crate::{BUF_WIDTH, SCREEN_HEIGHT, SCREEN_WIDTH};
+use alloc::{boxed::Box, vec};
+use core::{
+    ffi::c_void,
+    marker::PhantomData,
+    mem,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+pub mod texture;
+pub mod vertex;
+
+pub use texture::{Texture, TextureBuilder, TextureError};
+pub use vertex::{Index, Vertex};
+
+/// Space checked for before each command, in bytes. No single command writes
+/// more than this, except for [`Frame::clear`], which reserves its own size.
+const MAX_COMMAND_SIZE: usize = 128;
+
+/// Size of the commands written by `sceGuClear`, in bytes: the jump over its
+/// vertices, two clear mode, the vertex type, two address and the primitive
+/// commands.
+const CLEAR_COMMANDS_SIZE: usize = 8 + 6 * 4;
+
+/// Size of a vertex written by `sceGuClear`, in bytes.
+const CLEAR_VERTEX_SIZE: usize = 12;
+
+/// Space kept free at the end of a list for `sceGuFinish`, in bytes.
+const FINISH_SIZE: usize = 16;
+
+/// Set while a [`Gu`] exists.
+static GU_TAKEN: AtomicBool = AtomicBool::new(false);
+
+/// A cache line of a display list, only used for its size and alignment.
+#[repr(align(64))]
+#[derive(Clone, Copy)]
+#[allow(dead_code)]
+struct Block([u32; 16]);
+
+/// A buffer for display lists, aligned to the 64 byte cache lines.
+///
+/// The GU writes the list through uncached addresses, so no cache line may be
+/// shared with other data, and none of it may be dirty in the cache.
+pub struct DisplayList {
+    buf: Box<[Block]>,
+}
+
+impl DisplayList {
+    /// Allocate a display list of `size` bytes, rounded up to a multiple of
+    /// 64.
+    ///
+    /// Complex scenes need more space, and a frame panics when its list is
+    /// full. 256 KiB is plenty for most games.
+    pub fn new(size: usize) -> Self {
+        let blocks = size.div_ceil(mem::size_of::<Block>());
+        let buf = vec![Block([0; 16]); blocks].into_boxed_slice();
+        assert!(
+            mem::size_of_val(&*buf) >= MAX_COMMAND_SIZE + FINISH_SIZE,
+            "display list too small"
+        );
+
+        // Zeroing the buffer leaves dirty lines in the cache, which would
+        // overwrite commands written by the GU when evicted.
+        unsafe {
+            sys::sceKernelDcacheWritebackInvalidateRange(
+                buf.as_ptr() as *const c_void,
+                mem::size_of_val(&*buf) as u32,
+            )
+        };
+
+        Self { buf }
+    }
+
+    /// Size of the list in bytes.
+    pub fn len(&self) -> usize {
+        mem::size_of_val(&*self.buf)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.buf.is_empty()
+    }
+
+    fn as_mut_ptr(&mut self) -> *mut c_void {
+        self.buf.as_mut_ptr() as *mut c_void
+    }
+
+    /// The end of the list as written by the GU, which uses the uncached
+    /// address.
+    fn end(&self) -> usize {
+        (self.buf.as_ptr() as usize | 0x4000_0000) + self.len()
+    }
+}
+
+/// The frame buffers drawn to and displayed by a [`Gu`].
+///
+/// The buffers are chunks of VRAM, which are kept allocated by the [`Gu`],
+/// so that nothing else, such as a texture, is placed over them while it
+/// draws. [`FrameBuffers::alloc`] allocates the usual double buffered 32-bit
+/// color with a 16-bit depth buffer.
+#[derive(Debug)]
+pub struct FrameBuffers<'a> {
+    pub format: DisplayPixelFormat,
+    /// Width of the buffers in pixels, at least the screen width.
+    pub width: u32,
+    /// The buffer drawn to in the first frame.
+    pub draw: VramMemChunk<'a>,
+    /// The buffer displayed during the first frame.
+    pub display: VramMemChunk<'a>,
+    /// The depth buffer, with 16 bits per pixel.
+    pub depth: Option<VramMemChunk<'a>>,
+}
+
+impl<'a> FrameBuffers<'a> {
+    /// Allocate double buffered 32-bit color and a 16-bit depth buffer, the
+    /// width of [`BUF_WIDTH`], from `allocator`.
+    pub fn alloc(allocator: &'a VramAllocator) -> Result<Self, VramAllocError> {
+        let color = |allocator: &'a VramAllocator| {
+            allocator.alloc_framebuffer(BUF_WIDTH, SCREEN_HEIGHT, TexturePixelFormat::Psm8888)
+        };
+
+        Ok(Self {
+            format: DisplayPixelFormat::Psm8888,
+            width: BUF_WIDTH,
+            draw: color(allocator)?,
+            display: color(allocator)?,
+            depth: Some(allocator.alloc_framebuffer(
+                BUF_WIDTH,
+                SCREEN_HEIGHT,
+                TexturePixelFormat::Psm4444,
+            )?),
+        })
+    }
+
+    fn check(&self) -> Result<(), GuInitError> {
+        let bytes_per_pixel = match self.format {
+            DisplayPixelFormat::Psm8888 => 4,
+            _ => 2,
+        };
+
+        let color_size = self.width * SCREEN_HEIGHT * bytes_per_pixel;
+        let depth_size = self.width * SCREEN_HEIGHT * 2;
+
+        let fits = |chunk: &VramMemChunk, size: u32| {
+            chunk.as_mut_ptr_from_zero() as usize % 16 == 0 && chunk.len() >= size
+        };
+
+        if self.width < SCREEN_WIDTH
+            || self.width % 64 != 0
+            || !fits(&self.draw, color_size)
+            || !fits(&self.display, color_size)
+            || self
+                .depth
+                .as_ref()
+                .is_some_and(|depth| !fits(depth, depth_size))
+        {
+            return Err(GuInitError::InvalidBuffers);
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub enum GuInitError {
+    /// A [`Gu`] already exists.
+    InUse,
+    /// A frame buffer is not 16 byte aligned, is too small, or the buffer
+    /// width is not a multiple of 64 of at least the screen width.
+    InvalidBuffers,
+}
+
+/// The GU, set up for drawing to the screen.
+///
+/// Only one can exist at a time. It is shut down when dropped, after which
+/// its frame buffers are freed.
+pub struct Gu<'a> {
+    vsync: bool,
+    _buffers: FrameBuffers<'a>,
+    // The GU is driven from a single thread.
+    _not_send: PhantomData<*const ()>,
+}
+
+impl<'a> Gu<'a> {
+    /// Initialize the GU, set up the frame buffers, viewport and scissor
+    /// region for the whole screen, and turn on the display.
+    ///
+    /// `list` is used for the setup commands, and can then be used for frames.
+    /// The frame buffers are kept until the `Gu` is dropped.
+    pub fn init(list: &mut DisplayList, buffers: FrameBuffers<'a>) -> Result<Self, GuInitError> {
+        buffers.check()?;
+
+        if GU_TAKEN.swap(true, Ordering::Acquire) {
+            return Err(GuInitError::InUse);
+        }
+
+        let width = buffers.width as i32;
+        let (screen_width, screen_height) = (SCREEN_WIDTH as i32, SCREEN_HEIGHT as i32);
+
+        unsafe {
+            sys::sceGuInit();
+            sys::sceGuStart(GuContextType::Direct, list.as_mut_ptr());
+
+            sys::sceGuDrawBuffer(
+                buffers.format,
+                buffers.draw.as_mut_ptr_from_zero() as *mut c_void,
+                width,
+            );
+            sys::sceGuDispBuffer(
+                screen_width,
+                screen_height,
+                buffers.display.as_mut_ptr_from_zero() as *mut c_void,
+                width,
+            );
+
+            if let Some(depth) = &buffers.depth {
+                sys::sceGuDepthBuffer(depth.as_mut_ptr_from_zero() as *mut c_void, width);
+                sys::sceGuDepthRange(65535, 0);
+            }
+
+            sys::sceGuOffset(2048 - (SCREEN_WIDTH / 2), 2048 - (SCREEN_HEIGHT / 2));
+            sys::sceGuViewport(2048, 2048, screen_width, screen_height);
+            sys::sceGuScissor(0, 0, screen_width, screen_height);
+            sys::sceGuEnable(GuState::ScissorTest);
+
+            sys::sceGuFinish();
+            sys::sceGuSync(GuSyncMode::Finish, GuSyncBehavior::Wait);
+
+            sys::sceDisplayWaitVblankStart();
+            sys::sceGuDisplay(true);
+        }
+
+        Ok(Self {
+            vsync: true,
+            _buffers: buffers,
+            _not_send: PhantomData,
+        })
+    }
+
+    /// Whether frames wait for vertical blank before being shown, which
+    /// avoids tearing. On by default.
+    pub fn set_vsync(&mut self, vsync: bool) {
+        self.vsync = vsync;
+    }
+
+    /// Record a frame into `list` with `f`, run it and show it.
+    ///
+    /// Buffers borrowed by the frame, such as vertices and textures, must
+    /// outlive this call, which waits for the GE to finish.
+    ///
+    /// # Panics
+    ///
+    /// A command which does not fit in `list` panics. The commands recorded
+    /// so far are still run.
+    pub fn frame<'env, R>(
+        &mut self,
+        list: &mut DisplayList,
+        f: impl FnOnce(&mut Frame<'env>) -> R,
+    ) -> R {
+        let end = list.end() - FINISH_SIZE;
+
+        unsafe { sys::sceGuStart(GuContextType::Direct, list.as_mut_ptr()) };
+
+        let mut frame = Frame {
+            end,
+            vsync: self.vsync,
+            _env: PhantomData,
+        };
+
+        let result = f(&mut frame);
+        drop(frame);
+
+        result
+    }
+}
+
+impl Drop for Gu<'_> {
+    fn drop(&mut self) {
+        unsafe {
+            sys::sceGuDisplay(false);
+            sys::sceGuTerm();
+        }
+
+        GU_TAKEN.store(false, Ordering::Release);
+    }
+}
+
+/// A frame being recorded, see [`Gu::frame`].
+///
+/// When dropped, the list is finished, and the frame is shown once the GE has
+/// drawn it.
+pub struct Frame<'env> {
+    end: usize,
+    vsync: bool,
+    // Invariant, so that `'env` cannot be shortened to fit shorter borrows.
+    _env: PhantomData<fn(&'env ()) -> &'env ()>,
+}
+
+impl<'env> Frame<'env> {
+    /// Check that the next command fits in the list.
+    fn reserve(&self) {
+        self.reserve_bytes(MAX_COMMAND_SIZE);
+    }
+
+    /// Check that `size` bytes fit in the list.
+    fn reserve_bytes(&self, size: usize) {
+        let current = unsafe { sys::current_list_position() } as usize;
+
+        if current + size > self.end {
+            panic!("display list is full");
+        }
+    }
+
+    /// Make sure data written by the CPU is in memory before the GE reads it.
+    fn writeback<T>(data: &[T]) {
+        unsafe {
+            sys::sceKernelDcacheWritebackRange(
+                data.as_ptr() as *const c_void,
+                mem::size_of_val(data) as u32,
+            )
+        };
+    }
+
+    pub fn clear_color(&mut self, color: u32) {
+        self.reserve();
+        unsafe { sys::sceGuClearColor(color) };
+    }
+
+    pub fn clear_depth(&mut self, depth: u32) {
+        self.reserve();
+        unsafe { sys::sceGuClearDepth(depth) };
+    }
+
+    pub fn clear_stencil(&mut self, stencil: u32) {
+        self.reserve();
+        unsafe { sys::sceGuClearStencil(stencil) };
+    }
+
+    /// Clear the given buffers, with the values set by `clear_color`,
+    /// `clear_depth` and `clear_stencil`.
+    pub fn clear(&mut self, buffers: ClearBuffer) {
+        // A fast clear draws a sprite for each 64 pixel wide column of the
+        // screen, otherwise a single sprite.
+        let vertices = if buffers.intersects(ClearBuffer::FAST_CLEAR_BIT) {
+            (SCREEN_WIDTH as usize).div_ceil(64) * 2
+        } else {
+            2
+        };
+
+        self.reserve_bytes(CLEAR_COMMANDS_SIZE + vertices * CLEAR_VERTEX_SIZE);
+        unsafe { sys::sceGuClear(buffers) };
+    }
+
+    pub fn enable(&mut self, state: GuState) {
+        self.reserve();
+        unsafe { sys::sceGuEnable(state) };
+    }
+
+    pub fn disable(&mut self, state: GuState) {
+        self.reserve();
+        unsafe { sys::sceGuDisable(state) };
+    }
+
+    pub fn depth_func(&mut self, function: DepthFunc) {
+        self.reserve();
+        unsafe { sys::sceGuDepthFunc(function) };
+    }
+
+    /// Whether drawing writes to the depth buffer.
+    pub fn depth_write(&mut self, enabled: bool) {
+        self.reserve();
+        unsafe { sys::sceGuDepthMask(!enabled as i32) };
+    }
+
+    pub fn front_face(&mut self, order: FrontFaceDirection) {
+        self.reserve();
+        unsafe { sys::sceGuFrontFace(order) };
+    }
+
+    pub fn shade_model(&mut self, mode: ShadingModel) {
+        self.reserve();
+        unsafe { sys::sceGuShadeModel(mode) };
+    }
+
+    pub fn blend_func(
+        &mut self,
+        op: BlendOp,
+        src: BlendFactor,
+        dest: BlendFactor,
+        src_fix: u32,
+        dest_fix: u32,
+    ) {
+        self.reserve();
+        unsafe { sys::sceGuBlendFunc(op, src, dest, src_fix, dest_fix) };
+    }
+
+    /// Set the color used for vertices without one.
+    pub fn color(&mut self, color: u32) {
+        self.reserve();
+        unsafe { sys::sceGuColor(color) };
+    }
+
+    pub fn ambient_color(&mut self, color: u32) {
+        self.reserve();
+        unsafe { sys::sceGuAmbientColor(color) };
+    }
+
+    /// Only draw inside the given rectangle, if `GuState::ScissorTest` is
+    /// enabled.
+    pub fn scissor(&mut self, x: i32, y: i32, width: i32, height: i32) {
+        self.reserve();
+        unsafe { sys::sceGuScissor(x, y, width, height) };
+    }
+
+    pub fn viewport(&mut self, cx: i32, cy: i32, width: i32, height: i32) {
+        self.reserve();
+        unsafe { sys::sceGuViewport(cx, cy, width, height) };
+    }
+
+    pub fn set_matrix(&mut self, mode: MatrixMode, matrix: &ScePspFMatrix4) {
+        self.reserve();
+        unsafe { sys::sceGuSetMatrix(mode, matrix) };
+    }
+
+    /// Set the texture format and the number of mipmap levels after the
+    /// first.
+    pub fn tex_mode(&mut self, format: TexturePixelFormat, max_mips: i32, swizzled: bool) {
+        self.reserve();
+        unsafe { sys::sceGuTexMode(format, max_mips, 0, swizzled as i32) };
+    }
+
+    /// Set a texture level. The pixels are read by the GE as the frame is
+    /// drawn, so they must outlive the frame.
+    ///
+    /// `width` and `height` must be powers of two of at most 512, and
+    /// `buffer_width` is the row length of `pixels`, in pixels.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `pixels` is not 16 byte aligned.
+    pub fn tex_image<T: Copy>(
+        &mut self,
+        level: MipmapLevel,
+        width: i32,
+        height: i32,
+        buffer_width: i32,
+        pixels: &'env [T],
+    ) {
+        assert!(
+            pixels.as_ptr() as usize % 16 == 0,
+            "texture data must be 16 byte aligned"
+        );
+
+        Self::writeback(pixels);
+        self.reserve();
+
+        unsafe {
+            sys::sceGuTexImage(
+                level,
+                width,
+                height,
+                buffer_width,
+                pixels.as_ptr() as *const c_void,
+            );
+            sys::sceGuTexFlush();
+        }
+    }
+
+    /// Draw with `texture`: set its format, CLUT and all its levels. The
+    /// texture must outlive the frame.
+    pub fn bind_texture(&mut self, texture: &'env Texture<'_>) {
+        if let Some(clut) = texture.clut() {
+            self.reserve();
+            unsafe {
+                sys::sceGuClutMode(clut.format(), 0, 0xff, 0);
+                sys::sceGuClutLoad(clut.blocks(), clut.as_ptr());
+            }
+        }
+
+        self.tex_mode(
+            texture.format(),
+            texture.mipmaps() as i32,
+            texture.is_swizzled(),
+        );
+
+        for (level, width, height, buffer_width, pixels) in texture.levels() {
+            self.reserve();
+            unsafe { sys::sceGuTexImage(level, width, height, buffer_width, pixels) };
+        }
+
+        self.reserve();
+        unsafe { sys::sceGuTexFlush() };
+    }
+
+    pub fn tex_func(&mut self, effect: TextureEffect, component: TextureColorComponent) {
+        self.reserve();
+        unsafe { sys::sceGuTexFunc(effect, component) };
+    }
+
+    pub fn tex_filter(&mut self, min: TextureFilter, mag: TextureFilter) {
+        self.reserve();
+        unsafe { sys::sceGuTexFilter(min, mag) };
+    }
+
+    pub fn tex_wrap(&mut self, u: GuTexWrapMode, v: GuTexWrapMode) {
+        self.reserve();
+        unsafe { sys::sceGuTexWrap(u, v) };
+    }
+
+    pub fn tex_scale(&mut self, u: f32, v: f32) {
+        self.reserve();
+        unsafe { sys::sceGuTexScale(u, v) };
+    }
+
+    pub fn tex_offset(&mut self, u: f32, v: f32) {
+        self.reserve();
+        unsafe { sys::sceGuTexOffset(u, v) };
+    }
+
+    /// Draw primitives from an array of vertices.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there are more than 65535 vertices.
+    pub fn draw_array<V: Vertex>(&mut self, primitive: GuPrimitive, vertices: &'env [V]) {
+        assert!(vertices.len() <= 0xffff, "too many vertices");
+
+        Self::writeback(vertices);
+        self.reserve();
+
+        unsafe {
+            sys::sceGuDrawArray(
+                primitive,
+                V::TYPE,
+                vertices.len() as i32,
+                core::ptr::null(),
+                vertices.as_ptr() as *const c_void,
+            )
+        };
+    }
+
+    /// Draw primitives from the vertices picked by `indices`, which are `u8`
+    /// or `u16`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there are more than 65535 indices, or if an index is out of
+    /// bounds of `vertices`.
+    pub fn draw_indexed<V: Vertex, I: Index>(
+        &mut self,
+        primitive: GuPrimitive,
+        indices: &'env [I],
+        vertices: &'env [V],
+    ) {
+        assert!(indices.len() <= 0xffff, "too many indices");
+        assert!(
+            indices.iter().all(|&i| i.as_usize() < vertices.len()),
+            "index out of bounds"
+        );
+
+        Self::writeback(indices);
+        Self::writeback(vertices);
+        self.reserve();
+
+        unsafe {
+            sys::sceGuDrawArray(
+                primitive,
+                V::TYPE | I::TYPE,
+                indices.len() as i32,
+                indices.as_ptr() as *const c_void,
+                vertices.as_ptr() as *const c_void,
+            )
+        };
+    }
+}
+
+impl Drop for Frame<'_> {
+    fn drop(&mut self) {
+        unsafe {
+            sys::sceGuFinish();
+            sys::sceGuSync(GuSyncMode::Finish, GuSyncBehavior::Wait);
+
+            if self.vsync {
+                sys::sceDisplayWaitVblankStart();
+            }
+
+            sys::sceGuSwapBuffers();
+        }
+    }
+}
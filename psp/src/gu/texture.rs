@@ -0,0 +1,696 @@
+//! Textures.
+//!
+//! A [`Texture`] holds the pixels of every mipmap level, in the layout the GE
+//! reads, in RAM or in VRAM. It is created with a [`TextureBuilder`], which
+//! converts the pixels to the texture format, builds the smaller mipmap levels
+//! and swizzles the result:
+//!
+//! ```no_run
+//! # extern crate alloc;
+//! use alloc::vec;
+//! use psp::gu::TextureBuilder;
+//! use psp::sys::TexturePixelFormat;
+//! use psp::vram_alloc::get_vram_allocator;
+//!
+//! let allocator = get_vram_allocator().unwrap();
+//! let pixels = vec![0xff00_00ffu32; 64 * 64];
+//!
+//! let texture = TextureBuilder::new(64, 64, TexturePixelFormat::Psm5650)
+//!     .swizzle(true)
+//!     .mipmaps(2)
+//!     .vram(&allocator)
+//!     .upload_rgba(&pixels)?;
+//! # Ok::<(), psp::gu::TextureError>(())
+//! ```
+//!
+//! It is then drawn with [`Frame::bind_texture`](super::Frame::bind_texture),
+//! which sets the format, the CLUT and all the levels.
+
+use crate::sys::{self, ClutPixelFormat, MipmapLevel, TexturePixelFormat};
+use crate::vram_alloc::{VramAllocError, VramAllocator, VramMemChunk};
+use crate::Align16;
+use alloc::{boxed::Box, vec};
+use core::{ffi::c_void, fmt, slice};
+
+/// Largest width or height of a texture.
+pub const MAX_SIZE: u32 = 512;
+
+/// Largest number of mipmap levels after the first.
+pub const MAX_MIPMAPS: u32 = 7;
+
+/// Swizzled textures are stored in blocks of 16 bytes by 8 rows.
+const BLOCK_WIDTH: usize = 16;
+const BLOCK_HEIGHT: usize = 8;
+
+/// A texture could not be created.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextureError {
+    /// The width or height is not a power of two from 1 to [`MAX_SIZE`].
+    InvalidSize,
+    /// More mipmap levels than the GE supports, or than the size allows.
+    TooManyLevels,
+    /// The pixel format cannot be used for this kind of data, e.g. DXT.
+    UnsupportedFormat,
+    /// The pixels or the palette do not match the size and format.
+    InvalidData,
+    /// The texture does not fit in VRAM.
+    Vram(VramAllocError),
+}
+
+impl fmt::Display for TextureError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidSize => f.write_str("invalid texture size"),
+            Self::TooManyLevels => f.write_str("too many mipmap levels"),
+            Self::UnsupportedFormat => f.write_str("unsupported texture format"),
+            Self::InvalidData => f.write_str("texture data does not match its size and format"),
+            Self::Vram(error) => error.fmt(f),
+        }
+    }
+}
+
+impl From<VramAllocError> for TextureError {
+    fn from(error: VramAllocError) -> Self {
+        Self::Vram(error)
+    }
+}
+
+/// Bits per pixel of a format, or `None` for DXT formats.
+fn bits_per_pixel(format: TexturePixelFormat) -> Option<usize> {
+    match format {
+        TexturePixelFormat::PsmT4 => Some(4),
+        TexturePixelFormat::PsmT8 => Some(8),
+        TexturePixelFormat::Psm5650
+        | TexturePixelFormat::Psm5551
+        | TexturePixelFormat::Psm4444
+        | TexturePixelFormat::PsmT16 => Some(16),
+        TexturePixelFormat::Psm8888 | TexturePixelFormat::PsmT32 => Some(32),
+        TexturePixelFormat::PsmDxt1 | TexturePixelFormat::PsmDxt3 | TexturePixelFormat::PsmDxt5 => {
+            None
+        }
+    }
+}
+
+fn is_color(format: TexturePixelFormat) -> bool {
+    matches!(
+        format,
+        TexturePixelFormat::Psm5650
+            | TexturePixelFormat::Psm5551
+            | TexturePixelFormat::Psm4444
+            | TexturePixelFormat::Psm8888
+    )
+}
+
+/// Convert a `0xAABBGGRR` color to a pixel of a color format.
+pub(crate) fn encode_color(format: TexturePixelFormat, rgba: u32) -> u32 {
+    let [r, g, b, a] = rgba.to_le_bytes().map(u32::from);
+
+    match format {
+        TexturePixelFormat::Psm5650 => (r >> 3) | (g >> 2) << 5 | (b >> 3) << 11,
+        TexturePixelFormat::Psm5551 => (r >> 3) | (g >> 3) << 5 | (b >> 3) << 10 | (a >> 7) << 15,
+        TexturePixelFormat::Psm4444 => (r >> 4) | (g >> 4) << 4 | (b >> 4) << 8 | (a >> 4) << 12,
+        _ => rgba,
+    }
+}
+
+/// Convert a pixel of a color format to a `0xAABBGGRR` color.
+pub(crate) fn decode_color(format: TexturePixelFormat, pixel: u32) -> u32 {
+    // Widen a channel, repeating its high bits in the new low bits.
+    let expand = |value: u32, bits: u32| {
+        let value = value & ((1 << bits) - 1);
+        (value << (8 - bits)) | (value >> (2 * bits).saturating_sub(8))
+    };
+
+    let (r, g, b, a) = match format {
+        TexturePixelFormat::Psm5650 => (
+            expand(pixel, 5),
+            expand(pixel >> 5, 6),
+            expand(pixel >> 11, 5),
+            0xff,
+        ),
+        TexturePixelFormat::Psm5551 => (
+            expand(pixel, 5),
+            expand(pixel >> 5, 5),
+            expand(pixel >> 10, 5),
+            if pixel & 0x8000 != 0 { 0xff } else { 0 },
+        ),
+        TexturePixelFormat::Psm4444 => (
+            expand(pixel, 4),
+            expand(pixel >> 4, 4),
+            expand(pixel >> 8, 4),
+            expand(pixel >> 12, 4),
+        ),
+        _ => return pixel,
+    };
+
+    r | g << 8 | b << 16 | a << 24
+}
+
+/// Layout of the pixels of one level, unswizzled, with rows of
+/// `buffer_width` pixels.
+#[derive(Debug, Clone, Copy)]
+struct Layout {
+    bits: usize,
+    buffer_width: usize,
+}
+
+impl Layout {
+    fn get(&self, data: &[u8], x: usize, y: usize) -> u32 {
+        let bit = (y * self.buffer_width + x) * self.bits;
+        let bytes = &data[bit / 8..];
+
+        match self.bits {
+            4 => u32::from(bytes[0] >> (bit % 8)) & 0xf,
+            8 => u32::from(bytes[0]),
+            16 => u32::from(u16::from_le_bytes([bytes[0], bytes[1]])),
+            _ => u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+        }
+    }
+
+    fn set(&self, data: &mut [u8], x: usize, y: usize, value: u32) {
+        let bit = (y * self.buffer_width + x) * self.bits;
+        let bytes = &mut data[bit / 8..];
+
+        match self.bits {
+            4 => {
+                let shift = bit % 8;
+                bytes[0] = (bytes[0] & !(0xf << shift)) | ((value as u8 & 0xf) << shift);
+            }
+            8 => bytes[0] = value as u8,
+            16 => bytes[..2].copy_from_slice(&(value as u16).to_le_bytes()),
+            _ => bytes[..4].copy_from_slice(&value.to_le_bytes()),
+        }
+    }
+}
+
+/// Fill a level from the one above it, averaging 2x2 blocks of colors, or
+/// taking the top left index of indexed formats.
+fn downsample(
+    format: TexturePixelFormat,
+    (src, src_layout): (&[u8], Layout),
+    (src_width, src_height): (usize, usize),
+    (dst, dst_layout): (&mut [u8], Layout),
+    (width, height): (usize, usize),
+) {
+    for y in 0..height {
+        for x in 0..width {
+            let (x0, y0) = (2 * x, 2 * y);
+
+            if !is_color(format) {
+                dst_layout.set(dst, x, y, src_layout.get(src, x0, y0));
+                continue;
+            }
+
+            let (x1, y1) = ((x0 + 1).min(src_width - 1), (y0 + 1).min(src_height - 1));
+            let mut sum = [0u32; 4];
+
+            for (sx, sy) in [(x0, y0), (x1, y0), (x0, y1), (x1, y1)] {
+                let color = decode_color(format, src_layout.get(src, sx, sy)).to_le_bytes();
+
+                for (sum, channel) in sum.iter_mut().zip(color) {
+                    *sum += u32::from(channel);
+                }
+            }
+
+            let color = u32::from_le_bytes(sum.map(|sum| ((sum + 2) / 4) as u8));
+            dst_layout.set(dst, x, y, encode_color(format, color));
+        }
+    }
+}
+
+/// Rearrange a level into blocks of 16 bytes by 8 rows, which the GE reads
+/// faster.
+fn swizzle(dst: &mut [u8], src: &[u8], row_bytes: usize) {
+    let blocks_per_row = row_bytes / BLOCK_WIDTH;
+
+    for (y, row) in src.chunks_exact(row_bytes).enumerate() {
+        let (block_y, line) = (y / BLOCK_HEIGHT, y % BLOCK_HEIGHT);
+
+        for (block_x, chunk) in row.chunks_exact(BLOCK_WIDTH).enumerate() {
+            let block = block_y * blocks_per_row + block_x;
+            let offset = (block * BLOCK_HEIGHT + line) * BLOCK_WIDTH;
+
+            dst[offset..offset + BLOCK_WIDTH].copy_from_slice(chunk);
+        }
+    }
+}
+
+/// Make sure data written by the CPU is in memory before the GE reads it.
+fn writeback(data: &[u8]) {
+    unsafe {
+        sys::sceKernelDcacheWritebackRange(data.as_ptr() as *const c_void, data.len() as u32)
+    };
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Level {
+    offset: usize,
+    width: u32,
+    height: u32,
+    buffer_width: u32,
+}
+
+#[derive(Debug)]
+enum Storage<'a> {
+    Ram(Box<[Align16<[u8; 16]>]>),
+    Vram(VramMemChunk<'a>),
+}
+
+impl Storage<'_> {
+    fn bytes(&self) -> &[u8] {
+        match self {
+            Self::Ram(blocks) => unsafe {
+                slice::from_raw_parts(blocks.as_ptr() as *const u8, blocks.len() * 16)
+            },
+            Self::Vram(chunk) => unsafe {
+                slice::from_raw_parts(chunk.as_mut_ptr_direct_to_vram(), chunk.len() as usize)
+            },
+        }
+    }
+
+    fn bytes_mut(&mut self) -> &mut [u8] {
+        match self {
+            Self::Ram(blocks) => unsafe {
+                slice::from_raw_parts_mut(blocks.as_mut_ptr() as *mut u8, blocks.len() * 16)
+            },
+            Self::Vram(chunk) => unsafe {
+                slice::from_raw_parts_mut(chunk.as_mut_ptr_direct_to_vram(), chunk.len() as usize)
+            },
+        }
+    }
+
+    fn as_ptr(&self) -> *const u8 {
+        match self {
+            Self::Ram(blocks) => blocks.as_ptr() as *const u8,
+            Self::Vram(chunk) => chunk.as_mut_ptr_direct_to_vram(),
+        }
+    }
+}
+
+/// A color lookup table for an indexed texture.
+#[derive(Debug)]
+pub struct Clut {
+    format: ClutPixelFormat,
+    entries: Box<[Align16<[u8; 16]>]>,
+    len: usize,
+}
+
+impl Clut {
+    fn new(format: ClutPixelFormat, palette: &[u32]) -> Self {
+        let texture_format = match format {
+            ClutPixelFormat::Psm5650 => TexturePixelFormat::Psm5650,
+            ClutPixelFormat::Psm5551 => TexturePixelFormat::Psm5551,
+            ClutPixelFormat::Psm4444 => TexturePixelFormat::Psm4444,
+            ClutPixelFormat::Psm8888 => TexturePixelFormat::Psm8888,
+        };
+        let bits = bits_per_pixel(texture_format).unwrap();
+
+        // The CLUT is loaded in blocks of 32 bytes.
+        let size = (palette.len() * bits / 8).div_ceil(32) * 32;
+        let mut entries = vec![Align16([0; 16]); size / 16].into_boxed_slice();
+
+        let data = unsafe {
+            slice::from_raw_parts_mut(entries.as_mut_ptr() as *mut u8, entries.len() * 16)
+        };
+        let layout = Layout {
+            bits,
+            buffer_width: palette.len(),
+        };
+
+        for (i, &color) in palette.iter().enumerate() {
+            layout.set(data, i, 0, encode_color(texture_format, color));
+        }
+
+        writeback(data);
+
+        Self {
+            format,
+            entries,
+            len: palette.len(),
+        }
+    }
+
+    pub fn format(&self) -> ClutPixelFormat {
+        self.format
+    }
+
+    /// Number of colors.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub(crate) fn as_ptr(&self) -> *const c_void {
+        self.entries.as_ptr() as *const c_void
+    }
+
+    /// Size of the table, in blocks of 32 bytes.
+    pub fn blocks(&self) -> i32 {
+        (self.entries.len() / 2) as i32
+    }
+}
+
+/// Options for creating a [`Texture`].
+///
+/// By default, textures are stored in RAM, unswizzled and without mipmaps.
+#[derive(Debug, Clone)]
+pub struct TextureBuilder<'a> {
+    width: u32,
+    height: u32,
+    format: TexturePixelFormat,
+    mipmaps: u32,
+    swizzle: bool,
+    clut_format: ClutPixelFormat,
+    vram: Option<&'a VramAllocator>,
+}
+
+impl<'a> TextureBuilder<'a> {
+    /// A texture of `width` by `height` pixels in the given format.
+    pub fn new(width: u32, height: u32, format: TexturePixelFormat) -> Self {
+        Self {
+            width,
+            height,
+            format,
+            mipmaps: 0,
+            swizzle: false,
+            clut_format: ClutPixelFormat::Psm8888,
+            vram: None,
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn format(&self) -> TexturePixelFormat {
+        self.format
+    }
+
+    /// Number of mipmap levels to build after the first, each half the size
+    /// of the one before.
+    pub fn mipmaps(&mut self, mipmaps: u32) -> &mut Self {
+        self.mipmaps = mipmaps;
+        self
+    }
+
+    /// Swizzle the pixels, which makes drawing faster.
+    pub fn swizzle(&mut self, swizzle: bool) -> &mut Self {
+        self.swizzle = swizzle;
+        self
+    }
+
+    /// Format of the CLUT of indexed textures, 8888 by default.
+    pub fn clut_format(&mut self, format: ClutPixelFormat) -> &mut Self {
+        self.clut_format = format;
+        self
+    }
+
+    /// Store the texture in VRAM, allocated from `allocator`.
+    pub fn vram(&mut self, allocator: &'a VramAllocator) -> &mut Self {
+        self.vram = Some(allocator);
+        self
+    }
+
+    /// Create the texture from `0xAABBGGRR` colors, converted to a 5650,
+    /// 5551, 4444 or 8888 format.
+    pub fn upload_rgba(&self, pixels: &[u32]) -> Result<Texture<'a>, TextureError> {
+        if !is_color(self.format) {
+            return Err(TextureError::UnsupportedFormat);
+        }
+
+        self.upload(pixels.len(), |i| encode_color(self.format, pixels[i]), None)
+    }
+
+    /// Create an indexed `PsmT4` or `PsmT8` texture, with one index per pixel,
+    /// and a CLUT from the `0xAABBGGRR` colors of `palette`.
+    ///
+    /// Smaller mipmap levels take one pixel of each 2x2 block, as indices
+    /// cannot be averaged.
+    pub fn upload_indexed(
+        &self,
+        indices: &[u8],
+        palette: &[u32],
+    ) -> Result<Texture<'a>, TextureError> {
+        let max_colors = match self.format {
+            TexturePixelFormat::PsmT4 => 16,
+            TexturePixelFormat::PsmT8 => 256,
+            _ => return Err(TextureError::UnsupportedFormat),
+        };
+
+        if palette.is_empty() || palette.len() > max_colors {
+            return Err(TextureError::InvalidData);
+        }
+
+        let clut = Clut::new(self.clut_format, palette);
+        self.upload(indices.len(), |i| u32::from(indices[i]), Some(clut))
+    }
+
+    /// Create the texture from pixels already in its format, with rows
+    /// packed one after the other. Indexed textures created this way have no
+    /// CLUT.
+    pub fn upload_raw(&self, pixels: &[u8]) -> Result<Texture<'a>, TextureError> {
+        let bits = bits_per_pixel(self.format).ok_or(TextureError::UnsupportedFormat)?;
+        let layout = Layout {
+            bits,
+            buffer_width: self.width as usize,
+        };
+
+        let len = (self.width as usize * self.height as usize * bits).div_ceil(8);
+        if pixels.len() != len {
+            return Err(TextureError::InvalidData);
+        }
+
+        let width = self.width as usize;
+        self.upload(
+            width * self.height as usize,
+            |i| layout.get(pixels, i % width, i / width),
+            None,
+        )
+    }
+
+    /// Build a texture from `len` pixels in its format, returned by `pixel`.
+    fn upload(
+        &self,
+        len: usize,
+        pixel: impl Fn(usize) -> u32,
+        clut: Option<Clut>,
+    ) -> Result<Texture<'a>, TextureError> {
+        let bits = bits_per_pixel(self.format).ok_or(TextureError::UnsupportedFormat)?;
+
+        let valid_size = |size: u32| size.is_power_of_two() && size <= MAX_SIZE;
+        if !valid_size(self.width) || !valid_size(self.height) {
+            return Err(TextureError::InvalidSize);
+        }
+
+        let max_mipmaps = self
+            .width
+            .max(self.height)
+            .trailing_zeros()
+            .min(MAX_MIPMAPS);
+        if self.mipmaps > max_mipmaps {
+            return Err(TextureError::TooManyLevels);
+        }
+
+        if len != self.width as usize * self.height as usize {
+            return Err(TextureError::InvalidData);
+        }
+
+        // Rows are at least 16 bytes, and swizzled levels at least 8 rows.
+        let min_width = (BLOCK_WIDTH * 8 / bits) as u32;
+        let min_rows = if self.swizzle { BLOCK_HEIGHT as u32 } else { 1 };
+
+        let mut levels = [Level::default(); MAX_MIPMAPS as usize + 1];
+        let mut size = 0;
+
+        for (i, level) in levels
+            .iter_mut()
+            .enumerate()
+            .take(self.mipmaps as usize + 1)
+        {
+            let width = (self.width >> i).max(1);
+            let height = (self.height >> i).max(1);
+            let buffer_width = width.max(min_width);
+
+            *level = Level {
+                offset: size,
+                width,
+                height,
+                buffer_width,
+            };
+
+            size += buffer_width as usize * bits / 8 * height.max(min_rows) as usize;
+        }
+
+        // Build all levels unswizzled, then copy them to the texture.
+        let mut data = vec![0u8; size];
+        let levels = &levels[..self.mipmaps as usize + 1];
+
+        for (i, level) in levels.iter().enumerate() {
+            let (before, dst) = data.split_at_mut(level.offset);
+            let dst_layout = Layout {
+                bits,
+                buffer_width: level.buffer_width as usize,
+            };
+
+            if i == 0 {
+                let width = self.width as usize;
+
+                for p in 0..len {
+                    dst_layout.set(dst, p % width, p / width, pixel(p));
+                }
+            } else {
+                let above = levels[i - 1];
+                let src_layout = Layout {
+                    bits,
+                    buffer_width: above.buffer_width as usize,
+                };
+
+                downsample(
+                    self.format,
+                    (&before[above.offset..], src_layout),
+                    (above.width as usize, above.height as usize),
+                    (dst, dst_layout),
+                    (level.width as usize, level.height as usize),
+                );
+            }
+        }
+
+        let mut storage = match self.vram {
+            Some(allocator) => Storage::Vram(allocator.alloc(size as u32)?),
+            None => Storage::Ram(vec![Align16([0; 16]); size / 16].into_boxed_slice()),
+        };
+
+        let bytes = storage.bytes_mut();
+
+        if self.swizzle {
+            for (i, level) in levels.iter().enumerate() {
+                let end = levels.get(i + 1).map_or(size, |next| next.offset);
+                let row_bytes = level.buffer_width as usize * bits / 8;
+
+                swizzle(
+                    &mut bytes[level.offset..end],
+                    &data[level.offset..end],
+                    row_bytes,
+                );
+            }
+        } else {
+            bytes.copy_from_slice(&data);
+        }
+
+        writeback(bytes);
+
+        let mut texture_levels = [Level::default(); MAX_MIPMAPS as usize + 1];
+        texture_levels[..levels.len()].copy_from_slice(levels);
+
+        Ok(Texture {
+            storage,
+            format: self.format,
+            swizzled: self.swizzle,
+            levels: texture_levels,
+            level_count: levels.len(),
+            clut,
+        })
+    }
+}
+
+/// Texture pixels in RAM or VRAM, with all their mipmap levels and an
+/// optional CLUT.
+///
+/// Textures in VRAM are freed when dropped.
+#[derive(Debug)]
+pub struct Texture<'a> {
+    storage: Storage<'a>,
+    format: TexturePixelFormat,
+    swizzled: bool,
+    levels: [Level; MAX_MIPMAPS as usize + 1],
+    level_count: usize,
+    clut: Option<Clut>,
+}
+
+impl Texture<'_> {
+    pub fn width(&self) -> u32 {
+        self.levels[0].width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.levels[0].height
+    }
+
+    pub fn format(&self) -> TexturePixelFormat {
+        self.format
+    }
+
+    /// Number of mipmap levels after the first.
+    pub fn mipmaps(&self) -> u32 {
+        self.level_count as u32 - 1
+    }
+
+    pub fn is_swizzled(&self) -> bool {
+        self.swizzled
+    }
+
+    pub fn is_in_vram(&self) -> bool {
+        matches!(self.storage, Storage::Vram(_))
+    }
+
+    pub fn clut(&self) -> Option<&Clut> {
+        self.clut.as_ref()
+    }
+
+    /// The width, height and buffer width of a mipmap level, 0 being the
+    /// full size texture.
+    pub fn level_size(&self, level: u32) -> Option<(u32, u32, u32)> {
+        let level = self.levels[..self.level_count].get(level as usize)?;
+        Some((level.width, level.height, level.buffer_width))
+    }
+
+    /// The pixels of a mipmap level as read by the GE, in rows of the buffer
+    /// width, and swizzled if the texture is.
+    pub fn level_pixels(&self, level: u32) -> Option<&[u8]> {
+        let levels = &self.levels[..self.level_count];
+        let start = levels.get(level as usize)?.offset;
+        let bytes = self.storage.bytes();
+        let end = levels
+            .get(level as usize + 1)
+            .map_or(bytes.len(), |next| next.offset);
+
+        Some(&bytes[start..end])
+    }
+
+    /// The levels as passed to `sceGuTexImage`: the level, its width, height
+    /// and buffer width, and its pixels.
+    pub(crate) fn levels(
+        &self,
+    ) -> impl Iterator<Item = (MipmapLevel, i32, i32, i32, *const c_void)> + '_ {
+        const LEVELS: [MipmapLevel; 8] = [
+            MipmapLevel::None,
+            MipmapLevel::Level1,
+            MipmapLevel::Level2,
+            MipmapLevel::Level3,
+            MipmapLevel::Level4,
+            MipmapLevel::Level5,
+            MipmapLevel::Level6,
+            MipmapLevel::Level7,
+        ];
+
+        self.levels[..self.level_count]
+            .iter()
+            .zip(LEVELS)
+            .map(move |(level, mipmap)| {
+                (
+                    mipmap,
+                    level.width as i32,
+                    level.height as i32,
+                    level.buffer_width as i32,
+                    unsafe { self.storage.as_ptr().add(level.offset) } as *const c_void,
+                )
+            })
+    }
+}
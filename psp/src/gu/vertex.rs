@@ -0,0 +1,318 @@
+//! Typed vertex formats.
+//!
+//! The GE reads vertices as a fixed sequence of optional components, described
+//! by a [`VertexType`]. Vertices are declared with [`vertex!`](crate::vertex)
+//! from the component types in this module, which gives both the struct layout
+//! and the matching [`VertexType`], so the two cannot disagree:
+//!
+//! ```
+//! use psp::gu::vertex::{Color8888, Position, TexCoord};
+//!
+//! psp::vertex! {
+//!     pub struct TexturedVertex {
+//!         pub uv: TexCoord<f32>,
+//!         pub color: Color8888,
+//!         pub pos: Position<f32>,
+//!     }
+//! }
+//! ```
+//!
+//! Components must appear in the order the GE reads them: [`Weights`],
+//! [`TexCoord`], a color, [`Normal`], and finally [`Position`] or
+//! [`ScreenPosition`], which is required. This is checked at compile time,
+//! together with the offset of each component. Vertices for morphing are
+//! declared with [`Morph`].
+
+use crate::sys::VertexType;
+
+/// Order of the components in a vertex.
+const WEIGHTS: u8 = 0;
+const TEXTURE: u8 = 1;
+const COLOR: u8 = 2;
+const NORMAL: u8 = 3;
+const POSITION: u8 = 4;
+
+/// A vertex layout the GE can read.
+///
+/// # Safety
+///
+/// The layout of the type must match `TYPE` exactly. Use
+/// [`vertex!`](crate::vertex) instead of implementing this by hand.
+pub unsafe trait Vertex: Copy {
+    /// The vertex type passed to the GE, without an index type.
+    const TYPE: VertexType;
+}
+
+/// An index type for indexed drawing, `u8` or `u16`.
+///
+/// # Safety
+///
+/// `TYPE` must be the index format matching the type.
+pub unsafe trait Index: Copy {
+    const TYPE: VertexType;
+
+    /// The index as a position in the vertex slice.
+    fn as_usize(self) -> usize;
+}
+
+unsafe impl Index for u8 {
+    const TYPE: VertexType = VertexType::INDEX_8BIT;
+
+    fn as_usize(self) -> usize {
+        self as usize
+    }
+}
+
+unsafe impl Index for u16 {
+    const TYPE: VertexType = VertexType::INDEX_16BIT;
+
+    fn as_usize(self) -> usize {
+        self as usize
+    }
+}
+
+/// A part of a vertex, such as its color or position.
+///
+/// # Safety
+///
+/// The size and alignment of the type must match the format in `FLAGS`.
+pub unsafe trait Component: Copy {
+    /// Position of the component among the others in a vertex.
+    const ORDER: u8;
+    const FLAGS: VertexType;
+}
+
+/// Texture coordinates.
+///
+/// `u8` and `u16` coordinates are normalized, so that the largest value is
+/// 1.0, unless drawn with a [`ScreenPosition`], where they are in texels.
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct TexCoord<T> {
+    pub u: T,
+    pub v: T,
+}
+
+/// A 16-bit R5G6B5 color.
+#[repr(transparent)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Color5650(pub u16);
+
+/// A 16-bit R5G5B5A1 color.
+#[repr(transparent)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Color5551(pub u16);
+
+/// A 16-bit R4G4B4A4 color.
+#[repr(transparent)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Color4444(pub u16);
+
+/// A 32-bit color, `0xAABBGGRR`.
+#[repr(transparent)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Color8888(pub u32);
+
+/// A normal. `i8` and `i16` normals are normalized, so that the largest value
+/// is 1.0.
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Normal<T> {
+    pub x: T,
+    pub y: T,
+    pub z: T,
+}
+
+/// A position, transformed by the world, view and projection matrices.
+///
+/// `i8` and `i16` positions are normalized, so that the largest value is 1.0.
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Position<T> {
+    pub x: T,
+    pub y: T,
+    pub z: T,
+}
+
+/// A position in screen coordinates, passed to the rasterizer as is.
+///
+/// `z` is the depth, in the range of the depth buffer.
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct ScreenPosition<T> {
+    pub x: T,
+    pub y: T,
+    pub z: T,
+}
+
+/// `N` skinning weights, for 1 to 8 bones. `u8` and `u16` weights are
+/// normalized, so that the largest value is 1.0.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Weights<T, const N: usize>(pub [T; N]);
+
+macro_rules! impl_component {
+    ($($ty:ty => $order:ident, $flags:expr;)*) => {
+        $(
+            unsafe impl Component for $ty {
+                const ORDER: u8 = $order;
+                const FLAGS: VertexType = $flags;
+            }
+        )*
+    };
+}
+
+impl_component! {
+    TexCoord<u8> => TEXTURE, VertexType::TEXTURE_8BIT;
+    TexCoord<u16> => TEXTURE, VertexType::TEXTURE_16BIT;
+    TexCoord<f32> => TEXTURE, VertexType::TEXTURE_32BITF;
+
+    Color5650 => COLOR, VertexType::COLOR_5650;
+    Color5551 => COLOR, VertexType::COLOR_5551;
+    Color4444 => COLOR, VertexType::COLOR_4444;
+    Color8888 => COLOR, VertexType::COLOR_8888;
+
+    Normal<i8> => NORMAL, VertexType::NORMAL_8BIT;
+    Normal<i16> => NORMAL, VertexType::NORMAL_16BIT;
+    Normal<f32> => NORMAL, VertexType::NORMAL_32BITF;
+
+    Position<i8> => POSITION, VertexType::VERTEX_8BIT;
+    Position<i16> => POSITION, VertexType::VERTEX_16BIT;
+    Position<f32> => POSITION, VertexType::VERTEX_32BITF;
+
+    ScreenPosition<i16> => POSITION, VertexType::VERTEX_16BIT.union(VertexType::TRANSFORM_2D);
+    ScreenPosition<f32> => POSITION, VertexType::VERTEX_32BITF.union(VertexType::TRANSFORM_2D);
+}
+
+macro_rules! impl_weights {
+    ($($ty:ty => $flags:expr;)*) => {
+        $(
+            unsafe impl<const N: usize> Component for Weights<$ty, N> {
+                const ORDER: u8 = WEIGHTS;
+                const FLAGS: VertexType = {
+                    assert!(N >= 1 && N <= 8, "vertices have 1 to 8 weights");
+                    $flags.union(VertexType::from_bits_retain(VertexType::num_weights(N as u32)))
+                };
+            }
+        )*
+    };
+}
+
+impl_weights! {
+    u8 => VertexType::WEIGHT_8BIT;
+    u16 => VertexType::WEIGHT_16BIT;
+    f32 => VertexType::WEIGHT_32BITF;
+}
+
+/// `N` versions of a vertex, from 2 to 8, blended with the morph weights set
+/// with `sceGuMorphWeight`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Morph<V, const N: usize>(pub [V; N]);
+
+unsafe impl<V: Vertex, const N: usize> Vertex for Morph<V, N> {
+    const TYPE: VertexType = {
+        assert!(N >= 2 && N <= 8, "morphing needs 2 to 8 vertices");
+        V::TYPE.union(VertexType::from_bits_retain(VertexType::num_vertices(
+            N as u32,
+        )))
+    };
+}
+
+/// Check the layout of a vertex declared with `vertex!`, given the order,
+/// size and alignment of its components, their offsets and the vertex size.
+#[doc(hidden)]
+pub const fn check_layout(components: &[(u8, usize, usize)], offsets: &[usize], size: usize) {
+    let mut end: usize = 0;
+    let mut max_align = 1;
+    let mut i = 0;
+
+    while i < components.len() {
+        let (order, component_size, align) = components[i];
+
+        assert!(
+            i == 0 || components[i - 1].0 < order,
+            "vertex components must appear at most once, in the order weights, \
+            texture coordinates, color, normal, position"
+        );
+
+        // The GE aligns each component to the size of its elements.
+        let offset = end.div_ceil(align) * align;
+        assert!(
+            offsets[i] == offset,
+            "vertex component is not at the offset the GE reads it from"
+        );
+
+        end = offset + component_size;
+        if align > max_align {
+            max_align = align;
+        }
+
+        i += 1;
+    }
+
+    assert!(
+        i > 0 && components[i - 1].0 == POSITION,
+        "vertices must end with a position"
+    );
+    assert!(
+        size == end.div_ceil(max_align) * max_align,
+        "vertex size does not match its components"
+    );
+}
+
+/// Declare a vertex struct, implementing [`Vertex`](crate::gu::Vertex) with
+/// the `VertexType` matching its fields.
+///
+/// Each field must be a component from `psp::gu::vertex`, in the order the GE
+/// reads them. The struct is `#[repr(C)]`, and derives `Clone` and `Copy`.
+///
+/// ```
+/// use psp::gu::vertex::{Normal, Position};
+///
+/// psp::vertex! {
+///     #[derive(Debug)]
+///     pub struct LitVertex {
+///         pub normal: Normal<f32>,
+///         pub pos: Position<f32>,
+///     }
+/// }
+/// ```
+#[macro_export]
+macro_rules! vertex {
+    (
+        $(#[$attr:meta])*
+        $vis:vis struct $name:ident {
+            $(
+                $(#[$field_attr:meta])*
+                $field_vis:vis $field:ident : $ty:ty
+            ),+ $(,)?
+        }
+    ) => {
+        $(#[$attr])*
+        #[repr(C)]
+        #[derive(Clone, Copy)]
+        $vis struct $name {
+            $(
+                $(#[$field_attr])*
+                $field_vis $field: $ty,
+            )+
+        }
+
+        unsafe impl $crate::gu::Vertex for $name {
+            const TYPE: $crate::sys::VertexType = $crate::sys::VertexType::from_bits_retain(
+                0 $(| <$ty as $crate::gu::vertex::Component>::FLAGS.bits())+
+            );
+        }
+
+        const _: () = $crate::gu::vertex::check_layout(
+            &[$((
+                <$ty as $crate::gu::vertex::Component>::ORDER,
+                ::core::mem::size_of::<$ty>(),
+                ::core::mem::align_of::<$ty>(),
+            )),+],
+            &[$(::core::mem::offset_of!($name, $field)),+],
+            ::core::mem::size_of::<$name>(),
+        );
+    };
+}
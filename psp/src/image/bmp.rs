@@ -0,0 +1,171 @@
+//! BMP decoding.
+
+use super::{check_size, rgba, Image, ImageData, ImageError, Reader};
+use alloc::vec::Vec;
+
+const FILE_HEADER_LEN: usize = 14;
+
+/// Uncompressed.
+const BI_RGB: u32 = 0;
+/// Uncompressed, with channel masks.
+const BI_BITFIELDS: u32 = 3;
+/// Uncompressed, with channel masks including alpha.
+const BI_ALPHABITFIELDS: u32 = 6;
+
+/// Decode a BMP file.
+///
+/// Uncompressed images of 1, 4, 8, 16, 24 and 32 bits per pixel are
+/// supported, including channel masks. Images with a palette are decoded as
+/// [`ImageData::Indexed`]. Compressed images are not supported.
+pub fn decode_bmp(bytes: &[u8]) -> Result<Image, ImageError> {
+    let mut reader = Reader::new(bytes);
+
+    if reader.take(2)? != b"BM" {
+        return Err(ImageError::UnknownFormat);
+    }
+
+    reader.skip(8)?;
+    let data_offset = reader.u32_le()? as usize;
+    let header_len = reader.u32_le()? as usize;
+
+    let (width, height, depth, compression, colors_used) = if header_len == 12 {
+        // BITMAPCOREHEADER
+        let width = i32::from(reader.u16_le()?);
+        let height = i32::from(reader.u16_le()?);
+        reader.skip(2)?;
+        let depth = reader.u16_le()?;
+
+        (width, height, depth, BI_RGB, 0)
+    } else if header_len >= 40 {
+        let width = reader.u32_le()? as i32;
+        let height = reader.u32_le()? as i32;
+        reader.skip(2)?;
+        let depth = reader.u16_le()?;
+        let compression = reader.u32_le()?;
+        reader.skip(12)?;
+        let colors_used = reader.u32_le()? as usize;
+        reader.skip(4)?;
+
+        (width, height, depth, compression, colors_used)
+    } else {
+        return Err(ImageError::Unsupported);
+    };
+
+    // Rows are stored from the bottom, unless the height is negative.
+    let top_down = height < 0;
+    let (width, height) = (width.unsigned_abs(), height.unsigned_abs());
+    check_size(width, height)?;
+
+    let masks = match (compression, depth) {
+        (BI_RGB, 16) => [0x7c00, 0x03e0, 0x001f, 0],
+        (BI_RGB, 24 | 32) => [0xff_0000, 0x00_ff00, 0x00_00ff, 0],
+        (BI_RGB, _) => [0; 4],
+        (BI_BITFIELDS | BI_ALPHABITFIELDS, 16 | 32) => {
+            // The masks follow a 40 byte header, or are part of a larger one.
+            let count = if compression == BI_ALPHABITFIELDS || header_len >= 56 {
+                4
+            } else {
+                3
+            };
+
+            let mut masks = [0; 4];
+            for mask in &mut masks[..count] {
+                *mask = reader.u32_le()?;
+            }
+
+            masks
+        }
+        _ => return Err(ImageError::Unsupported),
+    };
+
+    let palette = if depth <= 8 {
+        if !matches!(depth, 1 | 4 | 8) {
+            return Err(ImageError::Unsupported);
+        }
+
+        let max_colors = 1 << depth;
+        let colors = if colors_used == 0 || colors_used > max_colors {
+            max_colors
+        } else {
+            colors_used
+        };
+
+        let mut palette = Reader::new(bytes.get(FILE_HEADER_LEN + header_len..).unwrap_or(&[]));
+        let entry_len = if header_len == 12 { 3 } else { 4 };
+
+        (0..colors)
+            .map(|_| {
+                let entry = palette.take(entry_len)?;
+                Ok(rgba(entry[2], entry[1], entry[0], 0xff))
+            })
+            .collect::<Result<Vec<_>, ImageError>>()?
+    } else if matches!(depth, 16 | 24 | 32) {
+        Vec::new()
+    } else {
+        return Err(ImageError::Unsupported);
+    };
+
+    let (width, height) = (width as usize, height as usize);
+    let depth = usize::from(depth);
+
+    // Rows are padded to 4 bytes.
+    let stride = (width * depth).div_ceil(32) * 4;
+    let pixels = bytes
+        .get(data_offset..)
+        .and_then(|data| data.get(..stride * height))
+        .ok_or(ImageError::InvalidData)?;
+
+    let row = |y: usize| {
+        let y = if top_down { y } else { height - 1 - y };
+        &pixels[y * stride..][..stride]
+    };
+
+    let data = if depth <= 8 {
+        let mut indices = Vec::with_capacity(width * height);
+
+        for y in 0..height {
+            let row = row(y);
+
+            indices.extend((0..width).map(|x| {
+                let bit = x * depth;
+                let shift = 8 - depth - bit % 8;
+                (row[bit / 8] >> shift) & ((1 << depth) - 1) as u8
+            }));
+        }
+
+        ImageData::Indexed { indices, palette }
+    } else {
+        let mut colors = Vec::with_capacity(width * height);
+        let bytes = depth / 8;
+
+        for y in 0..height {
+            colors.extend(row(y).chunks_exact(bytes).take(width).map(|pixel| {
+                let mut value = [0; 4];
+                value[..bytes].copy_from_slice(pixel);
+                let value = u32::from_le_bytes(value);
+
+                let [r, g, b, a] = masks.map(|mask| channel(value, mask));
+                let a = if masks[3] == 0 { 0xff } else { a };
+
+                rgba(r, g, b, a)
+            }));
+        }
+
+        ImageData::Rgba(colors)
+    };
+
+    Image::new(width as u32, height as u32, data)
+}
+
+/// Extract the channel selected by `mask` from a pixel, scaled to 8 bits.
+fn channel(value: u32, mask: u32) -> u8 {
+    if mask == 0 {
+        return 0;
+    }
+
+    let bits = mask.count_ones();
+    let value = (value & mask) >> mask.trailing_zeros();
+    let max = (1u64 << bits) - 1;
+
+    (u64::from(value) * 255 / max) as u8
+}
@@ -0,0 +1,339 @@
+//! Image decoding.
+//!
+//! PNG, TGA and BMP files are decoded into an [`Image`], which holds either
+//! `0xAABBGGRR` colors or palette indices, and can then be converted to a
+//! [`Texture`]:
+//!
+//! ```no_run
+//! use psp::gu::TextureBuilder;
+//! use psp::sys::TexturePixelFormat;
+//!
+//! let bytes = psp::fs::read("ms0:/sprite.png").unwrap();
+//! let image = psp::image::decode(&bytes)?;
+//!
+//! let builder = TextureBuilder::new(64, 64, TexturePixelFormat::Psm4444);
+//! let texture = image.to_texture(&builder, true)?;
+//! # Ok::<(), psp::image::ImageError>(())
+//! ```
+//!
+//! Paletted images with at most 256 colors can be uploaded as `PsmT8` (or
+//! `PsmT4` for 16 colors) textures with a CLUT, and all images as 5650, 5551,
+//! 4444 or 8888 textures. Conversions to 16-bit formats can be dithered, which
+//! hides the banding of gradients.
+//!
+//! This module requires the `image` feature.
+
+use crate::gu::{Texture, TextureBuilder, TextureError};
+use crate::sys::TexturePixelFormat;
+use alloc::{vec, vec::Vec};
+use core::fmt;
+
+mod bmp;
+mod png;
+mod tga;
+
+pub use bmp::decode_bmp;
+pub use png::decode_png;
+pub use tga::decode_tga;
+
+/// Largest width or height of an image that can be decoded, so that a bad
+/// header cannot take all memory. A 1024x1024 RGBA image takes 4 MiB, and
+/// textures are at most 512x512.
+pub const MAX_SIZE: u32 = 1024;
+
+/// 4x4 Bayer matrix, for ordered dithering.
+const BAYER: [[u32; 4]; 4] = [[0, 8, 2, 10], [12, 4, 14, 6], [3, 11, 1, 9], [15, 7, 13, 5]];
+
+/// An image could not be decoded or converted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageError {
+    /// The data is not a PNG, TGA or BMP file.
+    UnknownFormat,
+    /// A feature of the file is not supported, such as BMP compression.
+    Unsupported,
+    /// The file is truncated or corrupt.
+    InvalidData,
+    /// The image is larger than [`MAX_SIZE`], or empty.
+    InvalidSize,
+    /// The image cannot be converted to the format of the texture, e.g. an
+    /// image with more than 256 colors to `PsmT8`.
+    IncompatibleFormat,
+    /// The texture could not be created.
+    Texture(TextureError),
+}
+
+impl fmt::Display for ImageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownFormat => f.write_str("unknown image format"),
+            Self::Unsupported => f.write_str("unsupported image"),
+            Self::InvalidData => f.write_str("invalid image data"),
+            Self::InvalidSize => f.write_str("invalid image size"),
+            Self::IncompatibleFormat => f.write_str("image does not fit the texture format"),
+            Self::Texture(error) => error.fmt(f),
+        }
+    }
+}
+
+impl From<TextureError> for ImageError {
+    fn from(error: TextureError) -> Self {
+        Self::Texture(error)
+    }
+}
+
+/// Pixels of an [`Image`], row by row from the top left.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ImageData {
+    /// `0xAABBGGRR` colors.
+    Rgba(Vec<u32>),
+    /// Palette indices, and the `0xAABBGGRR` colors of the palette.
+    Indexed { indices: Vec<u8>, palette: Vec<u32> },
+}
+
+/// A decoded image.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Image {
+    width: u32,
+    height: u32,
+    data: ImageData,
+}
+
+impl Image {
+    /// Create an image, checking the size against the data.
+    pub fn new(width: u32, height: u32, data: ImageData) -> Result<Self, ImageError> {
+        check_size(width, height)?;
+
+        let len = match &data {
+            ImageData::Rgba(pixels) => pixels.len(),
+            ImageData::Indexed { indices, palette } => {
+                if indices.iter().any(|&i| usize::from(i) >= palette.len()) {
+                    return Err(ImageError::InvalidData);
+                }
+
+                indices.len()
+            }
+        };
+
+        if len != width as usize * height as usize {
+            return Err(ImageError::InvalidData);
+        }
+
+        Ok(Self {
+            width,
+            height,
+            data,
+        })
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn data(&self) -> &ImageData {
+        &self.data
+    }
+
+    pub fn into_data(self) -> ImageData {
+        self.data
+    }
+
+    /// The `0xAABBGGRR` color of each pixel.
+    pub fn to_rgba(&self) -> Vec<u32> {
+        match &self.data {
+            ImageData::Rgba(pixels) => pixels.clone(),
+            ImageData::Indexed { indices, palette } => {
+                indices.iter().map(|&i| palette[usize::from(i)]).collect()
+            }
+        }
+    }
+
+    /// Convert the pixels to a 5650, 5551, 4444 or 8888 texture format, with
+    /// rows packed one after the other, as taken by
+    /// [`TextureBuilder::upload_raw`].
+    ///
+    /// With `dither`, colors are dithered with a 4x4 ordered pattern instead
+    /// of being truncated. Alpha is never dithered.
+    pub fn to_pixels(
+        &self,
+        format: TexturePixelFormat,
+        dither: bool,
+    ) -> Result<Vec<u8>, ImageError> {
+        convert(&self.to_rgba(), self.width as usize, format, dither)
+    }
+
+    /// Create a texture with the options of `builder`.
+    ///
+    /// The texture may be larger than the image, e.g. to round it up to
+    /// powers of two, in which case the image is placed in its top left
+    /// corner and the rest is transparent, or index 0. `dither` is used for
+    /// 16-bit color formats, see [`Image::to_pixels`].
+    pub fn to_texture<'a>(
+        &self,
+        builder: &TextureBuilder<'a>,
+        dither: bool,
+    ) -> Result<Texture<'a>, ImageError> {
+        let (width, height) = (builder.width(), builder.height());
+
+        if width < self.width || height < self.height {
+            return Err(ImageError::IncompatibleFormat);
+        }
+
+        match builder.format() {
+            format @ (TexturePixelFormat::PsmT4 | TexturePixelFormat::PsmT8) => {
+                let max_colors = match format {
+                    TexturePixelFormat::PsmT4 => 16,
+                    _ => 256,
+                };
+
+                match &self.data {
+                    ImageData::Indexed { indices, palette } if palette.len() <= max_colors => {
+                        let indices = self.pad(indices, width, height, 0);
+                        Ok(builder.upload_indexed(&indices, palette)?)
+                    }
+                    _ => Err(ImageError::IncompatibleFormat),
+                }
+            }
+            format => {
+                let rgba = self.pad(&self.to_rgba(), width, height, 0);
+                let pixels = convert(&rgba, width as usize, format, dither)?;
+
+                Ok(builder.upload_raw(&pixels)?)
+            }
+        }
+    }
+
+    /// Copy `pixels` of this image into a `width` by `height` buffer, filled
+    /// with `fill`.
+    fn pad<T: Copy>(&self, pixels: &[T], width: u32, height: u32, fill: T) -> Vec<T> {
+        if (width, height) == (self.width, self.height) {
+            return pixels.to_vec();
+        }
+
+        let mut padded = vec![fill; width as usize * height as usize];
+
+        for (dst, src) in padded
+            .chunks_exact_mut(width as usize)
+            .zip(pixels.chunks_exact(self.width as usize))
+        {
+            dst[..src.len()].copy_from_slice(src);
+        }
+
+        padded
+    }
+}
+
+/// Decode a PNG, TGA or BMP file, detected from its contents.
+pub fn decode(bytes: &[u8]) -> Result<Image, ImageError> {
+    if bytes.starts_with(png::SIGNATURE) {
+        decode_png(bytes)
+    } else if bytes.starts_with(b"BM") {
+        decode_bmp(bytes)
+    } else if tga::is_tga(bytes) {
+        decode_tga(bytes)
+    } else {
+        Err(ImageError::UnknownFormat)
+    }
+}
+
+fn check_size(width: u32, height: u32) -> Result<(), ImageError> {
+    if width == 0 || height == 0 || width > MAX_SIZE || height > MAX_SIZE {
+        Err(ImageError::InvalidSize)
+    } else {
+        Ok(())
+    }
+}
+
+/// Convert `0xAABBGGRR` colors to a color format, optionally dithered.
+fn convert(
+    rgba: &[u32],
+    width: usize,
+    format: TexturePixelFormat,
+    dither: bool,
+) -> Result<Vec<u8>, ImageError> {
+    // Bits of red, green and blue.
+    let bits = match format {
+        TexturePixelFormat::Psm5650 => [5, 6, 5],
+        TexturePixelFormat::Psm5551 => [5, 5, 5],
+        TexturePixelFormat::Psm4444 => [4, 4, 4],
+        TexturePixelFormat::Psm8888 => {
+            return Ok(rgba.iter().flat_map(|color| color.to_le_bytes()).collect())
+        }
+        _ => return Err(ImageError::IncompatibleFormat),
+    };
+
+    let mut pixels = Vec::with_capacity(rgba.len() * 2);
+
+    for (i, &color) in rgba.iter().enumerate() {
+        let mut channels = color.to_le_bytes();
+
+        if dither {
+            let threshold = BAYER[i / width % 4][i % width % 4];
+
+            // Add an offset of up to one step of the format before
+            // truncating, so that the average matches the original color.
+            for (channel, bits) in channels.iter_mut().zip(bits) {
+                let step = 1 << (8 - bits);
+                *channel = (u32::from(*channel) + threshold * step / 16).min(0xff) as u8;
+            }
+        }
+
+        let pixel = crate::gu::texture::encode_color(format, u32::from_le_bytes(channels));
+        pixels.extend_from_slice(&(pixel as u16).to_le_bytes());
+    }
+
+    Ok(pixels)
+}
+
+/// A reader over the bytes of a file, failing with `InvalidData` at the end.
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes }
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], ImageError> {
+        if len > self.bytes.len() {
+            return Err(ImageError::InvalidData);
+        }
+
+        let (taken, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+
+        Ok(taken)
+    }
+
+    fn skip(&mut self, len: usize) -> Result<(), ImageError> {
+        self.take(len).map(drop)
+    }
+
+    fn u8(&mut self) -> Result<u8, ImageError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16_le(&mut self) -> Result<u16, ImageError> {
+        let bytes = self.take(2)?;
+        Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn u32_le(&mut self) -> Result<u32, ImageError> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn u32_be(&mut self) -> Result<u32, ImageError> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+}
+
+/// Build a `0xAABBGGRR` color.
+fn rgba(r: u8, g: u8, b: u8, a: u8) -> u32 {
+    u32::from_le_bytes([r, g, b, a])
+}
@@ -0,0 +1,340 @@
+//! PNG decoding.
+
+use super::{check_size, rgba, Image, ImageData, ImageError, Reader};
+use alloc::{vec, vec::Vec};
+use miniz_oxide::inflate::decompress_to_vec_zlib_with_limit;
+
+pub(super) const SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+/// Passes of Adam7 interlacing: the first column and row, and the distance
+/// between columns and rows.
+const ADAM7: [(usize, usize, usize, usize); 7] = [
+    (0, 0, 8, 8),
+    (4, 0, 8, 8),
+    (0, 4, 4, 8),
+    (2, 0, 4, 4),
+    (0, 2, 2, 4),
+    (1, 0, 2, 2),
+    (0, 1, 1, 2),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ColorType {
+    Gray,
+    Rgb,
+    Indexed,
+    GrayAlpha,
+    Rgba,
+}
+
+impl ColorType {
+    fn channels(self) -> usize {
+        match self {
+            Self::Gray | Self::Indexed => 1,
+            Self::GrayAlpha => 2,
+            Self::Rgb => 3,
+            Self::Rgba => 4,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Header {
+    width: usize,
+    height: usize,
+    bit_depth: usize,
+    color_type: ColorType,
+    interlaced: bool,
+}
+
+impl Header {
+    fn parse(data: &[u8]) -> Result<Self, ImageError> {
+        let mut reader = Reader::new(data);
+
+        let width = reader.u32_be()?;
+        let height = reader.u32_be()?;
+        let bit_depth = reader.u8()?;
+        let color_type = reader.u8()?;
+        let compression = reader.u8()?;
+        let filter = reader.u8()?;
+        let interlace = reader.u8()?;
+
+        check_size(width, height)?;
+
+        let color_type = match (color_type, bit_depth) {
+            (0, 1 | 2 | 4 | 8 | 16) => ColorType::Gray,
+            (2, 8 | 16) => ColorType::Rgb,
+            (3, 1 | 2 | 4 | 8) => ColorType::Indexed,
+            (4, 8 | 16) => ColorType::GrayAlpha,
+            (6, 8 | 16) => ColorType::Rgba,
+            _ => return Err(ImageError::InvalidData),
+        };
+
+        if compression != 0 || filter != 0 || interlace > 1 {
+            return Err(ImageError::Unsupported);
+        }
+
+        Ok(Self {
+            width: width as usize,
+            height: height as usize,
+            bit_depth: usize::from(bit_depth),
+            color_type,
+            interlaced: interlace == 1,
+        })
+    }
+
+    fn bits_per_pixel(&self) -> usize {
+        self.color_type.channels() * self.bit_depth
+    }
+
+    fn row_bytes(&self, width: usize) -> usize {
+        (width * self.bits_per_pixel()).div_ceil(8)
+    }
+
+    /// The passes of the image: the first column and row, the distance
+    /// between them, and the width and height of the pass.
+    fn passes(&self) -> impl Iterator<Item = [usize; 6]> + '_ {
+        let passes: &[_] = if self.interlaced {
+            &ADAM7
+        } else {
+            &[(0, 0, 1, 1)]
+        };
+
+        passes.iter().map(move |&(x, y, dx, dy)| {
+            let width = (self.width + dx - 1 - x) / dx;
+            let height = (self.height + dy - 1 - y) / dy;
+
+            [x, y, dx, dy, width, height]
+        })
+    }
+}
+
+/// Decode a PNG file.
+///
+/// All color types and bit depths are supported, as well as interlacing.
+/// Paletted images are decoded as [`ImageData::Indexed`], with the
+/// transparency of the palette, and others as [`ImageData::Rgba`], with
+/// 16-bit channels reduced to 8 bits.
+pub fn decode_png(bytes: &[u8]) -> Result<Image, ImageError> {
+    let mut reader = Reader::new(bytes);
+
+    if reader.take(SIGNATURE.len())? != SIGNATURE {
+        return Err(ImageError::UnknownFormat);
+    }
+
+    let mut header = None;
+    let mut palette = Vec::new();
+    let mut transparency: &[u8] = &[];
+    let mut compressed = Vec::new();
+
+    loop {
+        let len = reader.u32_be()? as usize;
+        let kind = reader.take(4)?;
+        let data = reader.take(len)?;
+
+        // The CRC is not checked, the compressed data has its own checksum.
+        reader.skip(4)?;
+
+        match kind {
+            b"IHDR" => header = Some(Header::parse(data)?),
+            b"PLTE" => {
+                palette = data
+                    .chunks_exact(3)
+                    .map(|c| rgba(c[0], c[1], c[2], 0xff))
+                    .collect()
+            }
+            b"tRNS" => transparency = data,
+            b"IDAT" => compressed.extend_from_slice(data),
+            b"IEND" => break,
+            // Chunks starting with an uppercase letter are needed to display
+            // the image correctly.
+            _ if kind[0].is_ascii_uppercase() => return Err(ImageError::Unsupported),
+            _ => {}
+        }
+    }
+
+    let header = header.ok_or(ImageError::InvalidData)?;
+
+    let len = header
+        .passes()
+        .filter(|pass| pass[4] > 0)
+        .map(|pass| pass[5] * (1 + header.row_bytes(pass[4])))
+        .sum();
+
+    let mut data =
+        decompress_to_vec_zlib_with_limit(&compressed, len).map_err(|_| ImageError::InvalidData)?;
+
+    if data.len() != len {
+        return Err(ImageError::InvalidData);
+    }
+
+    let pixels = header.width * header.height;
+    let mut out = match header.color_type {
+        ColorType::Indexed => {
+            for (color, &alpha) in palette.iter_mut().zip(transparency) {
+                *color = (*color & 0x00ff_ffff) | u32::from(alpha) << 24;
+            }
+
+            Output::Indexed(vec![0; pixels])
+        }
+        _ => Output::Rgba(vec![0; pixels]),
+    };
+
+    let bpp = header.bits_per_pixel().div_ceil(8);
+    let mut rest = &mut data[..];
+
+    for [x0, y0, dx, dy, width, height] in header.passes() {
+        if width == 0 {
+            continue;
+        }
+
+        let stride = 1 + header.row_bytes(width);
+        let (pass, next) = rest.split_at_mut(stride * height);
+        rest = next;
+
+        unfilter(pass, stride, bpp)?;
+
+        for (y, row) in pass.chunks_exact(stride).enumerate() {
+            let row = &row[1..];
+
+            for x in 0..width {
+                let i = (y0 + y * dy) * header.width + x0 + x * dx;
+                out.set(i, &header, transparency, row, x);
+            }
+        }
+    }
+
+    let data = match out {
+        Output::Rgba(pixels) => ImageData::Rgba(pixels),
+        Output::Indexed(indices) => ImageData::Indexed { indices, palette },
+    };
+
+    Image::new(header.width as u32, header.height as u32, data)
+}
+
+/// Undo the filter of each row, in place. Each row starts with its filter
+/// type.
+fn unfilter(data: &mut [u8], stride: usize, bpp: usize) -> Result<(), ImageError> {
+    for start in (0..data.len()).step_by(stride) {
+        let (before, rest) = data.split_at_mut(start);
+        let prior: &[u8] = if start == 0 {
+            &[]
+        } else {
+            &before[start - stride + 1..]
+        };
+        let (&mut filter, row) = rest[..stride].split_first_mut().unwrap();
+        let up = |i: usize| prior.get(i).copied().unwrap_or(0);
+
+        for i in 0..row.len() {
+            let left = if i >= bpp { row[i - bpp] } else { 0 };
+            let up_left = if i >= bpp { up(i - bpp) } else { 0 };
+
+            let predictor = match filter {
+                0 => 0,
+                1 => left,
+                2 => up(i),
+                3 => ((u16::from(left) + u16::from(up(i))) / 2) as u8,
+                4 => paeth(left, up(i), up_left),
+                _ => return Err(ImageError::InvalidData),
+            };
+
+            row[i] = row[i].wrapping_add(predictor);
+        }
+    }
+
+    Ok(())
+}
+
+fn paeth(a: u8, b: u8, c: u8) -> u8 {
+    let p = i16::from(a) + i16::from(b) - i16::from(c);
+    let (pa, pb, pc) = (
+        (p - i16::from(a)).abs(),
+        (p - i16::from(b)).abs(),
+        (p - i16::from(c)).abs(),
+    );
+
+    if pa <= pb && pa <= pc {
+        a
+    } else if pb <= pc {
+        b
+    } else {
+        c
+    }
+}
+
+enum Output {
+    Rgba(Vec<u32>),
+    Indexed(Vec<u8>),
+}
+
+impl Output {
+    /// Set pixel `i` from pixel `x` of an unfiltered row.
+    fn set(&mut self, i: usize, header: &Header, transparency: &[u8], row: &[u8], x: usize) {
+        let depth = header.bit_depth;
+        let channels = header.color_type.channels();
+
+        // Read channel `c` of the pixel, at its bit depth.
+        let sample = |c: usize| -> u16 {
+            let index = x * channels + c;
+
+            match depth {
+                16 => u16::from_be_bytes([row[2 * index], row[2 * index + 1]]),
+                8 => u16::from(row[index]),
+                _ => {
+                    let bit = index * depth;
+                    let shift = 8 - depth - bit % 8;
+                    u16::from(row[bit / 8] >> shift) & ((1 << depth) - 1)
+                }
+            }
+        };
+
+        // Scale a sample to 8 bits.
+        let scale = |value: u16| -> u8 {
+            match depth {
+                16 => (value >> 8) as u8,
+                _ => (u32::from(value) * 255 / ((1 << depth) - 1)) as u8,
+            }
+        };
+
+        // Samples matching the color in the tRNS chunk are transparent.
+        let key = |c: usize| {
+            transparency
+                .get(2 * c..2 * c + 2)
+                .map(|bytes| u16::from_be_bytes([bytes[0], bytes[1]]))
+        };
+
+        match self {
+            Self::Indexed(indices) => indices[i] = sample(0) as u8,
+            Self::Rgba(pixels) => {
+                pixels[i] = match header.color_type {
+                    ColorType::Gray => {
+                        let v = sample(0);
+                        let a = if key(0) == Some(v) { 0 } else { 0xff };
+                        let v = scale(v);
+
+                        rgba(v, v, v, a)
+                    }
+                    ColorType::Rgb => {
+                        let (r, g, b) = (sample(0), sample(1), sample(2));
+                        let transparent = (0..3).all(|c| key(c).is_some())
+                            && (key(0), key(1), key(2)) == (Some(r), Some(g), Some(b));
+                        let a = if transparent { 0 } else { 0xff };
+
+                        rgba(scale(r), scale(g), scale(b), a)
+                    }
+                    ColorType::GrayAlpha => {
+                        let v = scale(sample(0));
+                        rgba(v, v, v, scale(sample(1)))
+                    }
+                    ColorType::Rgba => rgba(
+                        scale(sample(0)),
+                        scale(sample(1)),
+                        scale(sample(2)),
+                        scale(sample(3)),
+                    ),
+                    ColorType::Indexed => unreachable!(),
+                }
+            }
+        }
+    }
+}
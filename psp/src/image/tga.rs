@@ -0,0 +1,184 @@
+//! TGA decoding.
+
+use super::{check_size, rgba, Image, ImageData, ImageError, Reader};
+use alloc::vec::Vec;
+
+const HEADER_LEN: usize = 18;
+
+/// Whether `bytes` look like a TGA file, which has no signature.
+pub(super) fn is_tga(bytes: &[u8]) -> bool {
+    bytes.len() >= HEADER_LEN
+        && bytes[1] <= 1
+        && matches!(bytes[2], 1 | 2 | 3 | 9 | 10 | 11)
+        && matches!(bytes[16], 8 | 15 | 16 | 24 | 32)
+}
+
+/// Decode a TGA file.
+///
+/// Color mapped, true color and grayscale images are supported, compressed
+/// or not. Color mapped images are decoded as [`ImageData::Indexed`] if they
+/// have 8-bit indices and at most 256 colors.
+pub fn decode_tga(bytes: &[u8]) -> Result<Image, ImageError> {
+    let mut reader = Reader::new(bytes);
+
+    let id_len = reader.u8()?;
+    let color_map_type = reader.u8()?;
+    let image_type = reader.u8()?;
+    let map_first = reader.u16_le()?;
+    let map_len = reader.u16_le()?;
+    let map_depth = reader.u8()?;
+    reader.skip(4)?; // Origin
+    let width = reader.u16_le()?;
+    let height = reader.u16_le()?;
+    let depth = reader.u8()?;
+    let descriptor = reader.u8()?;
+
+    let (width, height) = (u32::from(width), u32::from(height));
+    check_size(width, height)?;
+
+    reader.skip(usize::from(id_len))?;
+
+    let palette = if color_map_type == 1 {
+        let bytes = usize::from(map_depth).div_ceil(8);
+        let colors = reader.take(usize::from(map_len) * bytes)?;
+
+        colors
+            .chunks_exact(bytes)
+            .map(|c| color(c, map_depth, descriptor))
+            .collect::<Result<Vec<_>, _>>()?
+    } else {
+        Vec::new()
+    };
+
+    let (mapped, compressed) = match image_type {
+        1 => (true, false),
+        2 | 3 => (false, false),
+        9 => (true, true),
+        10 | 11 => (false, true),
+        _ => return Err(ImageError::Unsupported),
+    };
+    let gray = image_type & 3 == 3;
+
+    if mapped && (color_map_type != 1 || depth != 8) {
+        return Err(ImageError::Unsupported);
+    }
+
+    let pixel_bytes = usize::from(depth).div_ceil(8);
+    let pixels = width as usize * height as usize;
+    let raw = if compressed {
+        decompress(&mut reader, pixels, pixel_bytes)?
+    } else {
+        reader.take(pixels * pixel_bytes)?.to_vec()
+    };
+
+    // Rows are stored from the bottom, unless bit 5 of the descriptor is set,
+    // and from the left, unless bit 4 is set.
+    let flip_y = descriptor & 0x20 == 0;
+    let flip_x = descriptor & 0x10 != 0;
+    let (width, height) = (width as usize, height as usize);
+
+    let source = |i: usize| {
+        let (x, y) = (i % width, i / width);
+        let x = if flip_x { width - 1 - x } else { x };
+        let y = if flip_y { height - 1 - y } else { y };
+
+        &raw[(y * width + x) * pixel_bytes..][..pixel_bytes]
+    };
+
+    let data = if mapped {
+        let first = usize::from(map_first);
+        let indices = (0..pixels)
+            .map(|i| {
+                usize::from(source(i)[0])
+                    .checked_sub(first)
+                    .filter(|&index| index < palette.len())
+                    .ok_or(ImageError::InvalidData)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        if palette.len() <= 256 {
+            ImageData::Indexed {
+                indices: indices.iter().map(|&i| i as u8).collect(),
+                palette,
+            }
+        } else {
+            ImageData::Rgba(indices.iter().map(|&i| palette[i]).collect())
+        }
+    } else if gray {
+        if depth != 8 {
+            return Err(ImageError::Unsupported);
+        }
+
+        ImageData::Rgba(
+            (0..pixels)
+                .map(|i| {
+                    let v = source(i)[0];
+                    rgba(v, v, v, 0xff)
+                })
+                .collect(),
+        )
+    } else {
+        ImageData::Rgba(
+            (0..pixels)
+                .map(|i| color(source(i), depth, descriptor))
+                .collect::<Result<_, _>>()?,
+        )
+    };
+
+    Image::new(width as u32, height as u32, data)
+}
+
+/// Convert a BGR(A) color of the given depth.
+fn color(bytes: &[u8], depth: u8, descriptor: u8) -> Result<u32, ImageError> {
+    match depth {
+        15 | 16 => {
+            let value = u16::from_le_bytes([bytes[0], bytes[1]]);
+            let channel = |shift: u16| {
+                let v = ((value >> shift) & 0x1f) as u8;
+                (v << 3) | (v >> 2)
+            };
+
+            // The top bit is alpha if the descriptor gives any alpha bits.
+            let alpha = if depth == 15 || descriptor & 0xf == 0 || value & 0x8000 != 0 {
+                0xff
+            } else {
+                0
+            };
+
+            Ok(rgba(channel(10), channel(5), channel(0), alpha))
+        }
+        24 => Ok(rgba(bytes[2], bytes[1], bytes[0], 0xff)),
+        32 => Ok(rgba(bytes[2], bytes[1], bytes[0], bytes[3])),
+        _ => Err(ImageError::Unsupported),
+    }
+}
+
+/// Decode run length encoded pixels.
+fn decompress(
+    reader: &mut Reader<'_>,
+    pixels: usize,
+    pixel_bytes: usize,
+) -> Result<Vec<u8>, ImageError> {
+    let len = pixels * pixel_bytes;
+    let mut out = Vec::with_capacity(len);
+
+    while out.len() < len {
+        let packet = reader.u8()?;
+        let count = usize::from(packet & 0x7f) + 1;
+
+        if packet & 0x80 != 0 {
+            let pixel = reader.take(pixel_bytes)?;
+
+            for _ in 0..count {
+                out.extend_from_slice(pixel);
+            }
+        } else {
+            out.extend_from_slice(reader.take(count * pixel_bytes)?);
+        }
+    }
+
+    // A packet may run past the last row.
+    out.truncate(len);
+
+    Ok(out)
+}
@@ -23,8 +23,6 @@
 #![allow(incomplete_features)]
 #![cfg_attr(not(feature = "std"), no_std)]
 
-#[macro_use]
-extern crate paste;
 #[cfg(not(feature = "stub-only"))]
 extern crate alloc;
 #[cfg(not(feature = "stub-only"))]
@@ -38,7 +36,16 @@ pub mod debug;
 #[macro_use]
 mod vfpu;
 mod eabi;
+pub mod export;
+#[cfg(not(feature = "stub-only"))]
+pub mod fs;
+#[cfg(not(feature = "stub-only"))]
+pub mod gu;
+#[cfg(all(feature = "image", not(feature = "stub-only")))]
+pub mod image;
 pub mod math;
+#[cfg(not(feature = "stub-only"))]
+pub mod shutdown;
 pub mod sys;
 #[cfg(not(feature = "stub-only"))]
 pub mod test_runner;
@@ -48,8 +55,14 @@ pub mod vram_alloc;
 #[cfg(not(feature = "stub-only"))]
 mod alloc_impl;
 #[cfg(not(feature = "stub-only"))]
+#[doc(hidden)]
+pub use alloc_impl::configure_heap;
+#[cfg(not(feature = "stub-only"))]
 pub mod panic;
 
+#[cfg(not(feature = "stub-only"))]
+pub mod crash;
+
 #[cfg(not(feature = "stub-only"))]
 mod screenshot;
 #[cfg(not(feature = "stub-only"))]
@@ -65,6 +78,8 @@ mod constants;
 #[cfg(not(feature = "stub-only"))]
 pub use constants::*;
 
+#[doc(hidden)]
+pub use paste::paste;
 #[doc(hidden)]
 pub use unstringify::unstringify;
 
@@ -95,7 +110,7 @@ pub use panic::catch_unwind;
 pub mod embedded_graphics;
 
 #[repr(align(16))]
-#[derive(Copy, Clone)]
+#[derive(Debug, Copy, Clone)]
 pub struct Align16<T>(pub T);
 
 #[cfg(all(target_os = "psp", not(feature = "stub-only")))]
@@ -170,8 +185,19 @@ macro_rules! _start {
             unsafe { init_cwd($argv as *mut u8) };
         }
 
-        // TODO: Maybe print any error to debug screen?
-        let _ = $crate::catch_unwind($psp_main);
+        if $crate::catch_unwind($psp_main).is_err() {
+            if $crate::crash::report_panic() {
+                $crate::dprintln!(
+                    "psp_main panicked, report saved to {}",
+                    $crate::crash::REPORT_PATH
+                );
+            } else {
+                $crate::dprintln!(
+                    "psp_main panicked, failed to save report to {}",
+                    $crate::crash::REPORT_PATH
+                );
+            }
+        }
 
         0
     }};
@@ -181,9 +207,80 @@ macro_rules! _start {
 ///
 /// You must also define a `fn psp_main() { ... }` function in conjunction with
 /// this macro.
+///
+/// Options from [`ModuleConfig`] can be set with trailing `key = value`
+/// arguments, for example a function to run when the module is stopped, or the
+/// main thread's stack size:
+///
+/// ```ignore
+/// psp::module!("sample_module", 1, 0, stop = psp_stop, stack_size = 1024 * 1024);
+///
+/// fn psp_stop() {
+///     // Stop threads, free resources...
+/// }
+/// ```
 #[macro_export]
 macro_rules! module {
-    ($name:expr, $version_major:expr, $version_minor: expr) => {
+    (
+        $name:expr, $version_major:expr, $version_minor: expr
+        $(, $key:ident = $value:expr)* $(,)?
+    ) => {
+        $crate::_module!(
+            $name,
+            $version_major,
+            $version_minor,
+            $crate::sys::ModuleInfoAttr::User,
+            $crate::ModuleConfig::DEFAULT,
+            { $($key: $value,)* }
+        );
+    };
+}
+
+/// Declare a kernel mode PSP module.
+///
+/// This is the same as `module!`, except that the module is loaded into kernel
+/// memory, and `psp_main` runs in a kernel mode thread. Kernel mode modules
+/// can call `_driver` libraries, such as `sceNand`, but can only be loaded by
+/// custom firmware, e.g. as a plugin.
+///
+/// Requires the `kernel` feature.
+#[cfg(feature = "kernel")]
+#[macro_export]
+macro_rules! kernel_module {
+    (
+        $name:expr, $version_major:expr, $version_minor: expr
+        $(, $key:ident = $value:expr)* $(,)?
+    ) => {
+        $crate::_module!(
+            $name,
+            $version_major,
+            $version_minor,
+            $crate::sys::ModuleInfoAttr::Kernel,
+            $crate::ModuleConfig::KERNEL_DEFAULT,
+            { $($key: $value,)* }
+        );
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! _module {
+    (
+        $name:expr,
+        $version_major:expr,
+        $version_minor:expr,
+        $mod_attribute:expr,
+        $defaults:expr,
+        { $($key:ident: $value:expr,)* }
+    ) => {
+        #[doc(hidden)]
+        fn __psp_module_config() -> $crate::ModuleConfig {
+            $crate::ModuleConfig {
+                $($key: $value,)*
+                ..$defaults
+            }
+        }
+
         #[doc(hidden)]
         mod __psp_module {
             #[no_mangle]
@@ -191,7 +288,7 @@ macro_rules! module {
             #[used]
             static MODULE_INFO: $crate::Align16<$crate::sys::SceModuleInfo> =
                 $crate::Align16($crate::sys::SceModuleInfo {
-                    mod_attribute: 0,
+                    mod_attribute: $mod_attribute as u16,
                     mod_version: [$version_major, $version_minor],
                     mod_name: $crate::sys::SceModuleInfo::name($name),
                     terminal: 0,
@@ -214,26 +311,32 @@ macro_rules! module {
             #[link_section = ".lib.ent"]
             #[used]
             static LIB_ENT: $crate::sys::SceLibraryEntry = $crate::sys::SceLibraryEntry {
-                // TODO: Fix this?
+                // The module's own library is unnamed. Named libraries are
+                // declared with `export!`.
                 name: core::ptr::null(),
                 version: ($version_major, $version_minor),
                 attribute: $crate::sys::SceLibAttr::SCE_LIB_IS_SYSLIB,
                 entry_len: 4,
                 var_count: 1,
-                func_count: 1,
-                entry_table: &LIB_ENT_TABLE,
+                func_count: 2,
+                entry_table: &LIB_ENT_TABLE as *const _ as *const _,
             };
 
             #[no_mangle]
             #[link_section = ".rodata.sceResident"]
             #[used]
-            static LIB_ENT_TABLE: $crate::sys::SceLibraryEntryTable =
-                $crate::sys::SceLibraryEntryTable {
-                    module_start_nid: 0xd632acdb, // module_start
-                    module_info_nid: 0xf01d73a7,  // SceModuleInfo
-                    module_start: module_start,
-                    module_info: &MODULE_INFO.0,
-                };
+            static LIB_ENT_TABLE: $crate::export::ExportTable<3> = $crate::export::ExportTable {
+                nids: [
+                    0xd632acdb, // module_start
+                    0xcee8593c, // module_stop
+                    0xf01d73a7, // SceModuleInfo
+                ],
+                addresses: [
+                    module_start as *const c_void,
+                    module_stop as *const c_void,
+                    &MODULE_INFO.0 as *const _ as *const c_void,
+                ],
+            };
 
             use core::ffi::c_void;
 
@@ -243,15 +346,22 @@ macro_rules! module {
                     $crate::_start!(super::psp_main, argc, argv)
                 }
 
+                let config = super::__psp_module_config();
+
+                $crate::configure_heap(config.heap_partition, config.heap_size_limit);
+
+                // Registered first, so that it runs after every other hook.
+                let _ = $crate::shutdown::register(config.stop);
+
+                (config.crash_handler)();
+
                 unsafe {
                     let id = $crate::sys::sceKernelCreateThread(
                         b"main_thread\0".as_ptr(),
                         main_thread,
-                        // default priority of 32.
-                        32,
-                        // 256kb stack
-                        256 * 1024,
-                        $crate::sys::ThreadAttributes::USER | $crate::sys::ThreadAttributes::VFPU,
+                        config.priority,
+                        config.stack_size,
+                        config.thread_attributes,
                         core::ptr::null_mut(),
                     );
 
@@ -260,14 +370,88 @@ macro_rules! module {
 
                 0
             }
+
+            #[no_mangle]
+            extern "C" fn module_stop(_argc_bytes: usize, _argv: *mut c_void) -> isize {
+                $crate::shutdown::run();
+
+                0
+            }
         }
     };
 }
 
+/// Options for `module!` and `kernel_module!`.
+///
+/// Every field is optional, and is set with a `key = value` argument to the
+/// macro.
+pub struct ModuleConfig {
+    /// Called when the module is stopped or unloaded, or when the game is
+    /// exited with the HOME button. See [`shutdown`].
+    pub stop: fn(),
+
+    /// Priority of the main thread, from 1 (highest) to 127 (lowest).
+    ///
+    /// Default: 32.
+    pub priority: i32,
+
+    /// Stack size of the main thread, in bytes.
+    ///
+    /// Default: 256 KiB.
+    pub stack_size: i32,
+
+    /// Attributes of the main thread.
+    ///
+    /// Default: `USER | VFPU` for `module!`, and `VFPU` for `kernel_module!`.
+    pub thread_attributes: sys::ThreadAttributes,
+
+    /// Memory partition the heap is allocated from.
+    ///
+    /// Default: `SceKernelPrimaryUserPartition` for `module!`, and
+    /// `SceKernelPrimaryKernelPartition` for `kernel_module!`.
+    pub heap_partition: sys::SceSysMemPartitionId,
+
+    /// Maximum number of bytes the heap may use, including allocation
+    /// overhead. Allocations past this limit fail.
+    ///
+    /// Default: `usize::MAX`, which means no limit, and usage is not tracked.
+    pub heap_size_limit: usize,
+
+    /// Installs crash reporting before `psp_main` runs. See [`crash`].
+    ///
+    /// Uncaught panics are always reported. Kernel mode modules can also report
+    /// CPU exceptions with
+    /// `crash_handler = psp::crash::install_exception_handler`, which has
+    /// limitations described there.
+    ///
+    /// Default: nothing.
+    pub crash_handler: fn(),
+}
+
+impl ModuleConfig {
+    #[doc(hidden)]
+    pub const DEFAULT: Self = Self {
+        stop: || {},
+        priority: 32,
+        stack_size: 256 * 1024,
+        thread_attributes: sys::ThreadAttributes::USER.union(sys::ThreadAttributes::VFPU),
+        heap_partition: sys::SceSysMemPartitionId::SceKernelPrimaryUserPartition,
+        heap_size_limit: usize::MAX,
+        crash_handler: || {},
+    };
+
+    #[doc(hidden)]
+    pub const KERNEL_DEFAULT: Self = Self {
+        thread_attributes: sys::ThreadAttributes::VFPU,
+        heap_partition: sys::SceSysMemPartitionId::SceKernelPrimaryKernelPartition,
+        ..Self::DEFAULT
+    };
+}
+
 /// Enable the home button.
 ///
-/// This API does not have destructor support yet. You can manually setup an
-/// exit callback if you need this, see the source code of this function.
+/// When the user exits through the HOME menu, the [`shutdown`] hooks are run
+/// before the game exits, including the `stop` function passed to `module!`.
 pub fn enable_home_button() {
     use core::{ffi::c_void, ptr};
     use sys::ThreadAttributes;
@@ -275,6 +459,8 @@ pub fn enable_home_button() {
     unsafe {
         unsafe extern "C" fn exit_thread(_args: usize, _argp: *mut c_void) -> i32 {
             unsafe extern "C" fn exit_callback(_arg1: i32, _arg2: i32, _arg: *mut c_void) -> i32 {
+                #[cfg(not(feature = "stub-only"))]
+                shutdown::run();
                 sys::sceKernelExitGame();
                 0
             }
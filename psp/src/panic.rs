@@ -1,4 +1,18 @@
 //! Panic support for the PSP.
+//!
+//! Besides the crash report (see [`crate::crash`]), every panic can also be
+//! appended to a log file on the Memory Stick, so that crashes during field
+//! testing are kept across runs. Each record holds the local time, the module
+//! name, the message and the location:
+//!
+//! ```text
+//! 2026-10-18 14:03:22 sample_module: panicked at src/main.rs:12:5: oh no
+//! ```
+//!
+//! The log is enabled with [`set_log`], or from startup at [`DEFAULT_LOG_PATH`]
+//! with the `panic-log` feature. When it would grow past its size limit, it is
+//! renamed to `<path>.1`, replacing the previous one, and a new file is
+//! started.
 
 // Most of the code here is lifted from `rustc/src/libstd/panicking.rs`. It has
 // been adapted to run on the PSP.
@@ -31,6 +45,215 @@ fn print_and_die(s: String) -> ! {
     }
 }
 
+/// File panics are logged to with the `panic-log` feature.
+#[cfg(not(feature = "std"))]
+pub const DEFAULT_LOG_PATH: &str = "ms0:/panic.log";
+
+/// Size in bytes at which the log started by the `panic-log` feature is
+/// rotated.
+#[cfg(not(feature = "std"))]
+pub const DEFAULT_LOG_SIZE: u32 = 64 * 1024;
+
+/// Longest log path accepted by [`set_log`], in bytes.
+#[cfg(not(feature = "std"))]
+pub const MAX_LOG_PATH: usize = 254;
+
+/// Longest record written to the log. Longer messages are truncated.
+#[cfg(not(feature = "std"))]
+const RECORD_LEN: usize = 256;
+
+#[cfg(not(feature = "std"))]
+#[derive(Clone, Copy)]
+struct Log {
+    path: &'static str,
+    max_size: u32,
+}
+
+#[cfg(not(feature = "std"))]
+static mut LOG: Option<Log> = if cfg!(feature = "panic-log") {
+    Some(Log {
+        path: DEFAULT_LOG_PATH,
+        max_size: DEFAULT_LOG_SIZE,
+    })
+} else {
+    None
+};
+
+#[cfg(not(feature = "std"))]
+#[derive(Debug)]
+pub struct LogPathTooLongError {}
+
+/// Run `f` with interrupts disabled, so that the log can be configured from
+/// any thread.
+#[cfg(not(feature = "std"))]
+fn with_log<R>(f: impl FnOnce(&mut Option<Log>) -> R) -> R {
+    unsafe {
+        let flags = sys::sceKernelCpuSuspendIntr();
+        let result = f(&mut *core::ptr::addr_of_mut!(LOG));
+        sys::sceKernelCpuResumeIntr(flags);
+
+        result
+    }
+}
+
+/// Append a record of every panic to the file at `path`, e.g.
+/// `"ms0:/PSP/GAME/app/panic.log"`.
+///
+/// When a record would make the file larger than `max_size` bytes, the file
+/// is first renamed to `<path>.1`, replacing any previous one. This replaces
+/// any log set before, including the one from the `panic-log` feature.
+#[cfg(not(feature = "std"))]
+pub fn set_log(path: &'static str, max_size: u32) -> Result<(), LogPathTooLongError> {
+    if path.len() > MAX_LOG_PATH {
+        return Err(LogPathTooLongError {});
+    }
+
+    with_log(|log| *log = Some(Log { path, max_size }));
+
+    Ok(())
+}
+
+/// Stop logging panics to a file.
+#[cfg(not(feature = "std"))]
+pub fn disable_log() {
+    with_log(|log| *log = None);
+}
+
+/// A single log record, formatted on the stack.
+#[cfg(not(feature = "std"))]
+struct Record {
+    buf: [u8; RECORD_LEN],
+    len: usize,
+}
+
+#[cfg(not(feature = "std"))]
+impl core::fmt::Write for Record {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        // Keep the last byte for the newline.
+        let space = RECORD_LEN - 1 - self.len;
+        let mut end = s.len().min(space);
+
+        while !s.is_char_boundary(end) {
+            end -= 1;
+        }
+
+        self.buf[self.len..self.len + end].copy_from_slice(&s.as_bytes()[..end]);
+        self.len += end;
+
+        Ok(())
+    }
+}
+
+/// The name of this module, from its `SceModuleInfo`.
+#[cfg(not(feature = "std"))]
+fn module_name() -> &'static str {
+    extern "C" {
+        // Defined by `module!`.
+        static MODULE_INFO: sys::SceModuleInfo;
+    }
+
+    let name = unsafe { &*core::ptr::addr_of!(MODULE_INFO.mod_name) };
+    let len = name.iter().position(|&b| b == 0).unwrap_or(name.len());
+
+    core::str::from_utf8(&name[..len]).unwrap_or("?")
+}
+
+/// Append a panic to the log file, if enabled.
+///
+/// This must not allocate, as the heap may be what panicked.
+#[cfg(not(feature = "std"))]
+fn log_panic(info: &PanicInfo) {
+    use core::fmt::Write;
+
+    let log = match with_log(|log| *log) {
+        Some(log) => log,
+        None => return,
+    };
+
+    let mut record = Record {
+        buf: [0; RECORD_LEN],
+        len: 0,
+    };
+
+    let mut time: sys::ScePspDateTime = unsafe { mem::zeroed() };
+    if unsafe { sys::sceRtcGetCurrentClockLocalTime(&mut time) } >= 0 {
+        let _ = write!(
+            record,
+            "{:04}-{:02}-{:02} {:02}:{:02}:{:02} ",
+            time.year, time.month, time.day, time.hour, time.minutes, time.seconds
+        );
+    } else {
+        let _ = write!(record, "????-??-?? ??:??:?? ");
+    }
+
+    let _ = write!(record, "{}: panicked", module_name());
+
+    if let Some(location) = info.location() {
+        let _ = write!(
+            record,
+            " at {}:{}:{}",
+            location.file(),
+            location.line(),
+            location.column()
+        );
+    }
+
+    let _ = write!(record, ": {}", info.message());
+
+    // Messages may span several lines, but records may not.
+    for b in &mut record.buf[..record.len] {
+        if *b == b'\n' || *b == b'\r' {
+            *b = b' ';
+        }
+    }
+
+    record.buf[record.len] = b'\n';
+    record.len += 1;
+
+    append_record(log, &record.buf[..record.len]);
+}
+
+/// Append a record to the log, rotating it first if it would grow too large.
+#[cfg(not(feature = "std"))]
+fn append_record(log: Log, record: &[u8]) {
+    use core::ffi::c_void;
+    use sys::{IoOpenFlags, IoWhence};
+
+    // Nul terminated paths of the log and the rotated log.
+    let mut path = [0u8; MAX_LOG_PATH + 1];
+    let mut old_path = [0u8; MAX_LOG_PATH + 3];
+    let len = log.path.len();
+    path[..len].copy_from_slice(log.path.as_bytes());
+    old_path[..len].copy_from_slice(log.path.as_bytes());
+    old_path[len..len + 2].copy_from_slice(b".1");
+
+    unsafe {
+        let open =
+            |flags: IoOpenFlags| sys::sceIoOpen(path.as_ptr(), IoOpenFlags::WR_ONLY | flags, 0o777);
+
+        let mut fd = open(IoOpenFlags::CREAT | IoOpenFlags::APPEND);
+        if fd.0 < 0 {
+            return;
+        }
+
+        let size = sys::sceIoLseek(fd, 0, IoWhence::End);
+
+        if size > 0 && size as u64 + record.len() as u64 > log.max_size as u64 {
+            sys::sceIoClose(fd);
+            sys::sceIoRemove(old_path.as_ptr());
+            sys::sceIoRename(path.as_ptr(), old_path.as_ptr());
+
+            fd = open(IoOpenFlags::CREAT | IoOpenFlags::TRUNC);
+            if fd.0 < 0 {
+                return;
+            }
+        }
+
+        sys::sceIoWrite(fd, record.as_ptr() as *const c_void, record.len());
+        sys::sceIoClose(fd);
+    }
+}
+
 #[cfg(not(feature = "std"))]
 #[panic_handler]
 #[inline(never)]
@@ -44,6 +267,13 @@ fn panic(info: &PanicInfo) -> ! {
 fn panic_impl(info: &PanicInfo) -> ! {
     use core::fmt;
 
+    log_panic(info);
+
+    // Nested panics abort, keeping the first panic for the report.
+    if update_panic_count(0) == 0 {
+        crate::crash::record_panic(info);
+    }
+
     struct PanicPayload<'a> {
         message: PanicMessage<'a>,
         location: &'a Location<'a>,
@@ -62,15 +292,11 @@ fn panic_impl(info: &PanicInfo) -> ! {
         }
 
         fn fill(&mut self) -> &mut String {
-            let s = alloc::format!(
-                "panicked at {}:{}:{}: {}",
-                self.location.file(),
-                self.location.line(),
-                self.location.column(),
-                self.message.as_str().unwrap_or_default()
-            );
+            if self.string.is_none() {
+                self.string = Some(alloc::format!("{}", self));
+            }
 
-            self.string.get_or_insert_with(|| s)
+            self.string.as_mut().unwrap()
         }
     }
 
@@ -87,11 +313,14 @@ fn panic_impl(info: &PanicInfo) -> ! {
 
     impl fmt::Display for PanicPayload<'_> {
         fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-            if let Some(s) = &self.string {
-                s.fmt(f)
-            } else {
-                Err(fmt::Error)
-            }
+            write!(
+                f,
+                "panicked at {}:{}:{}: {}",
+                self.location.file(),
+                self.location.line(),
+                self.location.column(),
+                self.message
+            )
         }
     }
 
@@ -111,14 +340,12 @@ fn rust_panic_with_hook(payload: &mut dyn BoxMeUp) -> ! {
         print_and_die("thread panicked while processing panic. aborting.".into());
     }
 
-    payload.get(); // populate the payload's string
-    dprintln!("{}", payload);
-
     if panics > 1 {
         // If a thread panics while it's already unwinding then we
         // have limited options. Currently our preference is to
         // just abort. In the future we may consider resuming
         // unwinding or otherwise exiting the thread cleanly.
+        crate::crash::report_panic();
         die_nested();
     }
 
@@ -156,6 +383,9 @@ fn rust_panic(msg: &mut dyn BoxMeUp) -> ! {
         panic_unwind::__rust_start_panic(obj as _)
     };
 
+    // Nothing can catch the panic now.
+    crate::crash::report_panic();
+
     print_and_die(alloc::format!("failed to initiate panic, error {}", code))
 }
 
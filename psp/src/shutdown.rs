@@ -0,0 +1,63 @@
+//! Cleanup when the module stops.
+//!
+//! Hooks registered here run when the module is stopped or unloaded (in
+//! `module_stop`), or when the game is exited with the HOME button (see
+//! [`enable_home_button`](crate::enable_home_button)). The `stop` function
+//! passed to `module!` is always the last hook to run.
+
+use crate::sys;
+
+/// The maximum number of hooks that can be registered at once.
+pub const MAX_HOOKS: usize = 16;
+
+static mut HOOKS: [Option<fn()>; MAX_HOOKS] = [None; MAX_HOOKS];
+static mut HOOK_COUNT: usize = 0;
+
+#[derive(Debug)]
+pub struct TooManyHooksError {}
+
+/// Run `f` with interrupts disabled, so that hooks can be registered from any
+/// thread.
+fn with_hooks<R>(f: impl FnOnce(&mut [Option<fn()>; MAX_HOOKS], &mut usize) -> R) -> R {
+    unsafe {
+        let flags = sys::sceKernelCpuSuspendIntr();
+        let result = f(&mut *(&raw mut HOOKS), &mut *(&raw mut HOOK_COUNT));
+        sys::sceKernelCpuResumeIntr(flags);
+
+        result
+    }
+}
+
+/// Register a function to run when the module stops.
+///
+/// Hooks run in reverse order of registration, and each hook runs at most
+/// once. This is a good place to stop threads, delete callbacks and drop any
+/// resources held in statics.
+pub fn register(hook: fn()) -> Result<(), TooManyHooksError> {
+    with_hooks(|hooks, count| {
+        let slot = hooks.get_mut(*count).ok_or(TooManyHooksError {})?;
+        *slot = Some(hook);
+        *count += 1;
+
+        Ok(())
+    })
+}
+
+/// Run and unregister all hooks.
+///
+/// This is done automatically when the module stops, or when exiting through
+/// the HOME button. Call it before `sceKernelExitGame` if you exit the game
+/// yourself.
+pub fn run() {
+    let pop = || {
+        with_hooks(|hooks, count| {
+            *count = count.checked_sub(1)?;
+            hooks[*count].take()
+        })
+    };
+
+    while let Some(hook) = pop() {
+        // A panicking hook must not prevent the others from running.
+        let _ = crate::catch_unwind(hook);
+    }
+}
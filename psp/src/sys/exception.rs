@@ -0,0 +1,23 @@
+use core::ffi::c_void;
+
+psp_extern! {
+    #![name = "ExceptionManagerForKernel"]
+    #![flags = 0x0001]
+    #![version = (0x00, 0x00)]
+
+    #[psp(0x565C0B0E)]
+    /// Register the handler for CPU exceptions not handled by any other
+    /// handler, such as address and bus errors.
+    ///
+    /// The handler is entered in exception context: it must not touch the
+    /// stack, and should save what it needs and return with `eret`.
+    ///
+    /// # Parameters
+    ///
+    /// - `handler`: Address of the handler.
+    ///
+    /// # Return Value
+    ///
+    /// < 0 on error.
+    pub fn sceKernelRegisterDefaultExceptionHandler(handler: *mut c_void) -> i32;
+}
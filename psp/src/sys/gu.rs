@@ -157,11 +157,11 @@ bitflags::bitflags! {
 }
 
 impl VertexType {
-    const fn num_weights(n: u32) -> i32 {
+    pub(crate) const fn num_weights(n: u32) -> i32 {
         (((n - 1) & 7) << 14) as i32
     }
 
-    const fn num_vertices(n: u32) -> i32 {
+    pub(crate) const fn num_vertices(n: u32) -> i32 {
         (((n - 1) & 7) << 18) as i32
     }
 }
@@ -1636,6 +1636,13 @@ pub unsafe extern "C" fn sceGuGetMemory(mut size: i32) -> *mut c_void {
     orig_ptr.add(2).cast::<c_void>()
 }
 
+/// The position in the current display list the next command is written to.
+///
+/// Used by `psp::gu` to keep lists from overflowing.
+pub(crate) unsafe fn current_list_position() -> *mut u32 {
+    (*LIST).current
+}
+
 /// Start filling a new display-context
 ///
 /// The previous context-type is stored so that it can be restored at `sceGuFinish`.
@@ -3133,6 +3140,7 @@ pub unsafe extern "C" fn sceGuClutLoad(num_blocks: i32, cbp: *const c_void) {
 /// CLUT palette pixel formats.
 ///
 /// This is the pixel format for the input palette when setting up a CLUT.
+#[derive(Debug, Clone, Copy)]
 #[repr(u32)]
 pub enum ClutPixelFormat {
     /// Hicolor, 16-bit, RGB 5:6:5
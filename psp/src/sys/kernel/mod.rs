@@ -82,6 +82,7 @@ impl From<i32> for SceUid {
 
 // https://github.com/uofw/uofw/blob/f099b78dc0937df4e7346e2e417b63f471f8a3af/include/sysmem_user.h#L12
 #[repr(i32)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum SceSysMemPartitionId {
     SceKernelUnknownPartition = 0,
     SceKernelPrimaryKernelPartition = 1,
@@ -976,6 +977,18 @@ psp_extern! {
         read_buf_size: i32,
         id_count: *mut i32,
     ) -> i32;
+
+    #[psp(0xD8B73127)]
+    /// Get the UID of the module containing an address.
+    ///
+    /// # Parameters
+    ///
+    /// - `address`: An address inside the module, e.g. of one of its functions.
+    ///
+    /// # Return Value
+    ///
+    /// The UID of the module, < 0 on error.
+    pub fn sceKernelGetModuleIdByAddress(address: *const c_void) -> SceUid;
 }
 
 psp_extern! {
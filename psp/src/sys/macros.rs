@@ -3,8 +3,9 @@ use crate::sys::SceStubLibraryEntry;
 
 /// A macro that enables the use of `concat!` inside the `#[link_section = ...]`
 /// attribute.
-#[cfg(target_os = "psp")]
-macro_rules! link_section_concat {
+#[doc(hidden)]
+#[macro_export]
+macro_rules! _link_section_concat {
     ($(#[link_section = $section:expr] $item:item)*) => {
         $(
             #[link_section = $section]
@@ -33,12 +34,12 @@ macro_rules! link_section_concat {
 /// more.
 #[cfg(target_os = "psp")]
 #[derive(Copy, Clone)]
-pub(crate) struct Stub {
+pub struct Stub {
     // These are never read, but need to be written into as static items.
     #[allow(dead_code)]
-    pub(crate) lib_addr: &'static SceStubLibraryEntry,
+    pub lib_addr: &'static SceStubLibraryEntry,
     #[allow(dead_code)]
-    pub(crate) nid_addr: &'static u32,
+    pub nid_addr: &'static u32,
 }
 
 /// Calculate the padded length for a library name.
@@ -69,11 +70,41 @@ pub const fn lib_name_bytes<const T: usize>(name: &str) -> [u8; T] {
     buf
 }
 
-/// A complex macro used to define and link a PSP system library.
-macro_rules! psp_extern {
+/// Import functions from a PRX library, by NID.
+///
+/// This is how every library in [`psp::sys`](crate::sys) is declared, and can
+/// also be used to call into other modules, such as your own PRX plugins or
+/// community modules like `kubridge` and `SystemControl`. The library name,
+/// flags, version and NIDs must match the library exported by the module, and
+/// can usually be found in its import stubs (`.S` files) or `exports.exp`.
+///
+/// The module exporting the library must be loaded before the importing
+/// module, otherwise the importing module will fail to load.
+///
+/// Each function is declared in the current module as an `unsafe extern "C"`
+/// function, and is linked when the module is loaded. Functions which are
+/// never called are removed during LTO, like regular functions.
+///
+/// # Example
+///
+/// ```ignore
+/// psp::import! {
+///     #![name = "kubridge"]
+///     #![flags = 0x4009]
+///     #![version = (0x00, 0x00)]
+///
+///     #[psp(0x24331850)]
+///     /// Get the model of the PSP, e.g. 0 for PSP-1000.
+///     pub fn kuKernelGetModel() -> i32;
+/// }
+///
+/// let model = unsafe { kuKernelGetModel() };
+/// ```
+#[macro_export]
+macro_rules! import {
     // Generate body with default ABI.
     (__BODY $name:ident ($($arg:ident : $arg_ty:ty),*) $(-> $ret:ty)?) => {{
-        paste! {
+        $crate::paste! {
             extern "C" {
                 pub fn [< __ $name _stub >]($($arg : $arg_ty),*) $(-> $ret)?;
             }
@@ -86,7 +117,7 @@ macro_rules! psp_extern {
     (__BODY $abi:ident $name:ident ($($arg:ident : $arg_ty:ty),*) $(-> $ret:ty)?) => {{
         type Func = unsafe extern "C" fn($($arg : $arg_ty),*) $(-> $ret)?;
 
-        paste! {
+        $crate::paste! {
             extern "C" {
                 pub fn [< __ $name _stub >]($($arg : $arg_ty),*) $(-> $ret)?;
             }
@@ -113,14 +144,14 @@ macro_rules! psp_extern {
             $(-> $ret:ty)?;
         )*
     ) => {
-        paste! {
+        $crate::paste! {
             #[allow(non_snake_case)]
             mod [< __ $lib_name _mod >] {
                 #[allow(unused)]
                 use super::*;
 
                 #[cfg(target_os = "psp")]
-                link_section_concat! {
+                $crate::_link_section_concat! {
                     #[link_section = concat!(".rodata.sceResident.", $lib_name)]
                     #[allow(non_upper_case_globals)]
                     static [< __ $lib_name _RESIDENT >] : [u8; $crate::sys::macros::lib_name_bytes_len($lib_name)] = $crate::sys::macros::lib_name_bytes($lib_name);
@@ -136,7 +167,7 @@ macro_rules! psp_extern {
                     #[link_section = concat!(".lib.stub.entry.", $lib_name)]
                     #[allow(non_upper_case_globals)]
                     static [< __ $lib_name _STUB >] : $crate::sys::SceStubLibraryEntry = $crate::sys::SceStubLibraryEntry {
-                        name: paste! { & [< __ $lib_name _RESIDENT >] [0] },
+                        name: $crate::paste! { & [< __ $lib_name _RESIDENT >] [0] },
                         version: [$lib_minor_version, $lib_major_version],
                         flags: $lib_flags,
                         len: 5,
@@ -163,7 +194,7 @@ macro_rules! psp_extern {
                     use super::*;
 
                     #[cfg(target_os = "psp")]
-                    link_section_concat! {
+                    $crate::_link_section_concat! {
                         #[link_section = concat!(
                             ".rodata.sceNid.", $lib_name,
                             ".", stringify!($name)
@@ -189,7 +220,7 @@ macro_rules! psp_extern {
                     pub unsafe extern "C" fn $name($($arg : $arg_ty),*) $(-> $ret)? {
                         #[cfg(target_os = "psp")]
                         {
-                            psp_extern!(
+                            $crate::import!(
                                 __BODY $($abi)?
                                 $name($($arg : $arg_ty),*) $(-> $ret)?
                             )
@@ -208,10 +239,17 @@ macro_rules! psp_extern {
             }
         }
 
-        paste! {
+        $crate::paste! {
             $(
                 pub use self :: [< __ $lib_name _mod >] :: $name;
             )*
         }
     }
 }
+
+/// `import!`, as used for the system libraries in this crate.
+macro_rules! psp_extern {
+    ($($body:tt)*) => {
+        $crate::import! { $($body)* }
+    };
+}
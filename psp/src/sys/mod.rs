@@ -26,13 +26,18 @@
 //!     - `sceRegistry`: PSP OS Registry API
 //!     - `sceOpenPSID`: Console identification API (unique to every console)
 //!     - `sceUtility`: Various utilities such as msg dialogs and savedata
+//!     - `sceNand`: NAND flash access (kernel mode only, requires the `kernel`
+//!       feature)
+//!     - `sceKernelRegisterDefaultExceptionHandler`: CPU exception handling
+//!       (kernel mode only, requires the `kernel` feature)
 
 #![allow(clippy::missing_safety_doc)]
 
 use core::{mem, ptr};
 
 #[macro_use]
-mod macros;
+#[doc(hidden)]
+pub mod macros;
 
 mod ctrl;
 pub use ctrl::*;
@@ -112,8 +117,17 @@ pub use psmf::*;
 // These are not found (likely because this was tested in user mode on a PSP-2000).
 // pub mod sircs;
 // pub mod codec;
-// TODO: Add kernel module support to this crate.
-// pub mod nand;
+
+// Kernel mode only libraries. These require a module declared with
+// `kernel_module!`.
+#[cfg(feature = "kernel")]
+mod nand;
+#[cfg(feature = "kernel")]
+pub use nand::*;
+#[cfg(feature = "kernel")]
+mod exception;
+#[cfg(feature = "kernel")]
+pub use exception::*;
 
 pub mod vfpu_context;
 
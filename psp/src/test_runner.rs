@@ -1,5 +1,5 @@
-use crate::sys::{self, SceUid};
-use core::ffi::c_void;
+use crate::fs::{File, OpenOptions};
+use crate::sys;
 
 pub const OUTPUT_FILENAME: &str = "psp_output_file.log";
 pub const OUTPUT_FIFO: &str = "psp_output_pipe.fifo";
@@ -9,6 +9,7 @@ pub const SUCCESS_TOKEN: &str = "FINAL_SUCCESS";
 pub const FAILURE_TOKEN: &str = "FINAL_FAILURE";
 
 use alloc::format;
+use alloc::string::String;
 use alloc::vec::Vec;
 use core::fmt::Arguments;
 
@@ -19,8 +20,8 @@ pub struct TestRunner<'a> {
 }
 
 enum TestRunnerMode {
-    Fifo(SceUid),
-    File(SceUid),
+    Fifo(File),
+    File(File),
     Dprintln,
 }
 
@@ -170,9 +171,9 @@ impl<'a> TestRunner<'a> {
     }
 
     pub fn write_args(&self, args: Arguments) {
-        match self.mode {
-            TestRunnerMode::File(fd) | TestRunnerMode::Fifo(fd) => {
-                write_to_psp_output_fd(fd, &format!("{}", args));
+        match &self.mode {
+            TestRunnerMode::File(file) | TestRunnerMode::Fifo(file) => {
+                let _ = file.write_all(format!("{}", args).as_bytes());
             }
             TestRunnerMode::Dprintln => {
                 crate::dprintln!("{}", args);
@@ -182,8 +183,8 @@ impl<'a> TestRunner<'a> {
 
     fn quit(self) {
         match self.mode {
-            TestRunnerMode::File(fd) | TestRunnerMode::Fifo(fd) => {
-                close_psp_file(fd);
+            TestRunnerMode::File(file) | TestRunnerMode::Fifo(file) => {
+                drop(file);
                 quit_game();
             }
             TestRunnerMode::Dprintln => loop {
@@ -193,52 +194,36 @@ impl<'a> TestRunner<'a> {
     }
 }
 
-fn get_test_output_pipe() -> SceUid {
-    unsafe {
-        let fd = sys::sceIoOpen(
-            psp_filename(OUTPUT_FIFO),
-            sys::IoOpenFlags::APPEND | sys::IoOpenFlags::WR_ONLY,
-            0o777,
-        );
-        if fd.0 < 0 {
+fn get_test_output_pipe() -> File {
+    OpenOptions::new()
+        .append(true)
+        .open(&psp_filename(OUTPUT_FIFO))
+        .unwrap_or_else(|e| {
             panic!(
                 "Unable to open pipe \"{}\" for output! \
-                You must create it yourself with `mkfifo`.",
-                fd.0
-            );
-        }
-        fd
-    }
-}
-
-fn get_test_output_file() -> SceUid {
-    unsafe {
-        let fd = sys::sceIoOpen(
-            psp_filename(OUTPUT_FILENAME),
-            sys::IoOpenFlags::TRUNC | sys::IoOpenFlags::CREAT | sys::IoOpenFlags::RD_WR,
-            0o777,
-        );
-        if fd.0 < 0 {
-            panic!("Unable to open file \"{}\" for output!", OUTPUT_FILENAME);
-        }
-        fd
-    }
-}
-
-fn psp_filename(filename: &str) -> *const u8 {
-    format!("host0:/{}\0", filename).as_bytes().as_ptr()
+                You must create it yourself with `mkfifo`. ({})",
+                OUTPUT_FIFO, e
+            )
+        })
 }
 
-fn write_to_psp_output_fd(fd: SceUid, msg: &str) {
-    unsafe {
-        sys::sceIoWrite(fd, msg.as_bytes().as_ptr() as *const c_void, msg.len());
-    }
+fn get_test_output_file() -> File {
+    OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(&psp_filename(OUTPUT_FILENAME))
+        .unwrap_or_else(|e| {
+            panic!(
+                "Unable to open file \"{}\" for output! ({})",
+                OUTPUT_FILENAME, e
+            )
+        })
 }
 
-fn close_psp_file(fd: SceUid) {
-    unsafe {
-        sys::sceIoClose(fd);
-    }
+fn psp_filename(filename: &str) -> String {
+    format!("host0:/{}", filename)
 }
 
 fn quit_game() {
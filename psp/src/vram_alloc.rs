@@ -1,39 +1,233 @@
+//! VRAM allocation.
+//!
+//! VRAM is managed by a [`VramAllocator`], taken with [`get_vram_allocator`].
+//! Each allocation is a [`VramMemChunk`], which is freed when dropped, so
+//! textures can be loaded and unloaded individually:
+//!
+//! ```no_run
+//! use psp::sys::TexturePixelFormat;
+//! use psp::vram_alloc::get_vram_allocator;
+//!
+//! let allocator = get_vram_allocator().unwrap();
+//! let draw = allocator.alloc_framebuffer(512, 272, TexturePixelFormat::Psm8888)?;
+//! let display = allocator.alloc_framebuffer(512, 272, TexturePixelFormat::Psm8888)?;
+//!
+//! let texture = allocator.alloc_texture_pixels(128, 128, TexturePixelFormat::Psm5650)?;
+//! // Draw with the texture...
+//! drop(texture);
+//! # Ok::<(), psp::vram_alloc::VramAllocError>(())
+//! ```
+//!
+//! Frame buffers, which usually live as long as the program, are placed from
+//! the bottom of VRAM, and everything else from the top, so that freeing
+//! textures does not leave holes between the frame buffers.
+
 use crate::sys::TexturePixelFormat;
 use crate::sys::{sceGeEdramGetAddr, sceGeEdramGetSize};
+use core::cell::Cell;
+use core::fmt;
 use core::marker::PhantomData;
-use core::mem::size_of;
-use core::ptr::null_mut;
-use core::sync::atomic::{AtomicU32, Ordering};
+use core::mem::{align_of, size_of};
+use core::ops::{Deref, DerefMut};
+use core::ptr::{self, null_mut};
+use core::sync::atomic::{AtomicBool, Ordering};
+
+/// Maximum number of chunks allocated at the same time.
+pub const MAX_CHUNKS: usize = 256;
 
-type VramAllocator = SimpleVramAllocator;
+/// Alignment of frame buffers.
+const FRAMEBUFFER_ALIGN: u32 = 0x2000;
+
+/// Alignment of textures and other data. The GE needs 16 bytes for textures.
+const DATA_ALIGN: u32 = 16;
 
 #[derive(Debug)]
 pub struct VramAllocatorInUseError {}
 
-static mut VRAM_ALLOCATOR: VramAllocatorSingleton = VramAllocatorSingleton {
-    alloc: Some(VramAllocator::new()),
-};
+/// An allocation failed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VramAllocError {
+    /// No free block is large enough, though the total free space may be.
+    OutOfMemory,
+    /// [`MAX_CHUNKS`] chunks are already allocated.
+    TooManyChunks,
+    /// The size is zero or does not fit in VRAM.
+    InvalidSize,
+}
+
+impl fmt::Display for VramAllocError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::OutOfMemory => "out of VRAM",
+            Self::TooManyChunks => "too many VRAM chunks",
+            Self::InvalidSize => "invalid VRAM allocation size",
+        })
+    }
+}
 
+static ALLOCATOR_TAKEN: AtomicBool = AtomicBool::new(false);
+
+// Kept outside of the allocator, so that chunks which were leaked stay
+// allocated when it is taken again.
+static mut HEAP: Heap = Heap::new();
+
+/// Take the VRAM allocator.
+///
+/// Only one allocator exists at a time. It can be taken again once dropped.
 pub fn get_vram_allocator() -> Result<VramAllocator, VramAllocatorInUseError> {
-    let opt_alloc = unsafe { VRAM_ALLOCATOR.get_vram_alloc() };
-    opt_alloc.ok_or(VramAllocatorInUseError {})
+    if ALLOCATOR_TAKEN.swap(true, Ordering::Acquire) {
+        return Err(VramAllocatorInUseError {});
+    }
+
+    Ok(VramAllocator {
+        _not_sync: PhantomData,
+    })
 }
 
-pub struct VramAllocatorSingleton {
-    alloc: Option<VramAllocator>,
+#[derive(Debug, Clone, Copy)]
+struct Block {
+    start: u32,
+    len: u32,
 }
 
-impl VramAllocatorSingleton {
-    pub fn get_vram_alloc(&mut self) -> Option<VramAllocator> {
-        self.alloc.take()
+impl Block {
+    fn end(&self) -> u32 {
+        self.start + self.len
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Placement {
+    Bottom,
+    Top,
+}
+
+/// Allocated blocks, sorted by address.
+struct Heap {
+    blocks: [Block; MAX_CHUNKS],
+    count: usize,
+}
+
+impl Heap {
+    const fn new() -> Self {
+        Self {
+            blocks: [Block { start: 0, len: 0 }; MAX_CHUNKS],
+            count: 0,
+        }
+    }
+
+    fn blocks(&self) -> &[Block] {
+        &self.blocks[..self.count]
     }
+
+    /// The free space before block `i`, or after the last block.
+    fn gap(&self, i: usize, total: u32) -> (u32, u32) {
+        let start = if i == 0 { 0 } else { self.blocks[i - 1].end() };
+        let end = if i == self.count {
+            total
+        } else {
+            self.blocks[i].start
+        };
+
+        (start, end)
+    }
+
+    fn alloc(
+        &mut self,
+        total: u32,
+        len: u32,
+        align: u32,
+        placement: Placement,
+    ) -> Result<u32, VramAllocError> {
+        if len == 0 || len > total {
+            return Err(VramAllocError::InvalidSize);
+        }
+
+        if self.count == MAX_CHUNKS {
+            return Err(VramAllocError::TooManyChunks);
+        }
+
+        let fits = |i: usize| {
+            let (gap_start, gap_end) = self.gap(i, total);
+
+            let start = match placement {
+                Placement::Bottom => gap_start.checked_add(align - 1)? & !(align - 1),
+                Placement::Top => gap_end.checked_sub(len)? & !(align - 1),
+            };
+
+            if start >= gap_start && start.checked_add(len)? <= gap_end {
+                Some((i, start))
+            } else {
+                None
+            }
+        };
+
+        let found = match placement {
+            Placement::Bottom => (0..=self.count).find_map(fits),
+            Placement::Top => (0..=self.count).rev().find_map(fits),
+        };
+
+        let (i, start) = found.ok_or(VramAllocError::OutOfMemory)?;
+
+        self.blocks.copy_within(i..self.count, i + 1);
+        self.blocks[i] = Block { start, len };
+        self.count += 1;
+
+        Ok(start)
+    }
+
+    fn free(&mut self, start: u32) {
+        if let Ok(i) = self.blocks().binary_search_by_key(&start, |b| b.start) {
+            self.blocks.copy_within(i + 1..self.count, i);
+            self.count -= 1;
+        }
+    }
+
+    fn stats(&self, total: u32) -> VramStats {
+        let used = self.blocks().iter().map(|b| b.len).sum();
+        let largest_free = (0..=self.count)
+            .map(|i| {
+                let (start, end) = self.gap(i, total);
+                end - start
+            })
+            .max()
+            .unwrap_or(0);
+
+        VramStats {
+            total,
+            used,
+            free: total - used,
+            largest_free,
+            chunks: self.count,
+        }
+    }
+}
+
+/// VRAM usage, as returned by [`VramAllocator::stats`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VramStats {
+    /// Size of VRAM, in bytes.
+    pub total: u32,
+    /// Bytes allocated.
+    pub used: u32,
+    /// Bytes not allocated, including padding between chunks.
+    pub free: u32,
+    /// Size of the largest free block. When it is much smaller than `free`,
+    /// VRAM is fragmented.
+    pub largest_free: u32,
+    /// Number of chunks allocated.
+    pub chunks: usize,
 }
 
+/// A chunk of VRAM, freed when dropped.
+///
+/// The chunk cannot outlive the `&VramAllocator` it was allocated from. Use
+/// [`core::mem::forget`] to keep it allocated for the rest of the program.
+#[derive(Debug)]
 pub struct VramMemChunk<'a> {
     start: u32,
     len: u32,
-    // Needed since VramMemChunk has a lifetime, but doesn't contain references
-    vram: PhantomData<&'a mut ()>,
+    _allocator: PhantomData<&'a VramAllocator>,
 }
 
 impl VramMemChunk<'_> {
@@ -41,14 +235,17 @@ impl VramMemChunk<'_> {
         Self {
             start,
             len,
-            vram: PhantomData,
+            _allocator: PhantomData,
         }
     }
 
+    /// Pointer to the chunk as an offset from the start of VRAM, as taken by
+    /// the Gu functions for frame buffers.
     pub fn as_mut_ptr_from_zero(&self) -> *mut u8 {
         unsafe { vram_start_addr_zero().add(self.start as usize) }
     }
 
+    /// Pointer to the chunk in the address space of the CPU.
     pub fn as_mut_ptr_direct_to_vram(&self) -> *mut u8 {
         unsafe { vram_start_addr_direct().add(self.start as usize) }
     }
@@ -62,79 +259,164 @@ impl VramMemChunk<'_> {
     }
 }
 
-// A dead-simple VRAM bump allocator.
-// TODO: pin?
+impl Drop for VramMemChunk<'_> {
+    fn drop(&mut self) {
+        // The allocator is borrowed by the chunk, and is not `Sync`, so it is
+        // owned by this thread.
+        unsafe { (*ptr::addr_of_mut!(HEAP)).free(self.start) }
+    }
+}
+
+/// A value moved into VRAM with [`VramAllocator::move_to_vram`], dropped and
+/// freed with the box.
+pub struct VramBox<'a, T> {
+    chunk: VramMemChunk<'a>,
+    _value: PhantomData<T>,
+}
+
+impl<'a, T> VramBox<'a, T> {
+    /// The chunk holding the value.
+    pub fn chunk(&self) -> &VramMemChunk<'a> {
+        &self.chunk
+    }
+}
+
+impl<T> Deref for VramBox<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { &*(self.chunk.as_mut_ptr_direct_to_vram() as *const T) }
+    }
+}
+
+impl<T> DerefMut for VramBox<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *(self.chunk.as_mut_ptr_direct_to_vram() as *mut T) }
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for VramBox<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+impl<T> Drop for VramBox<'_, T> {
+    fn drop(&mut self) {
+        unsafe { ptr::drop_in_place(self.chunk.as_mut_ptr_direct_to_vram() as *mut T) }
+    }
+}
+
+/// The VRAM allocator.
+///
+/// Allocations take `&self`, and return chunks borrowing the allocator.
 #[derive(Debug)]
-pub struct SimpleVramAllocator {
-    offset: AtomicU32,
+pub struct VramAllocator {
+    // Chunks access the heap when dropped, so they must stay on one thread.
+    _not_sync: PhantomData<Cell<()>>,
 }
 
-impl SimpleVramAllocator {
-    const fn new() -> Self {
-        Self {
-            offset: AtomicU32::new(0),
-        }
+impl VramAllocator {
+    fn alloc_placed(
+        &self,
+        size: u32,
+        align: u32,
+        placement: Placement,
+    ) -> Result<VramMemChunk<'_>, VramAllocError> {
+        let heap = unsafe { &mut *ptr::addr_of_mut!(HEAP) };
+        let start = heap.alloc(self.total_mem(), size, align, placement)?;
+
+        Ok(VramMemChunk::new(start, size))
     }
 
-    /// Frees all previously allocated VRAM chunks.
+    /// Frees all chunks, including those which were leaked.
     ///
-    /// This resets the allocator's counter, but does not change the contents of
-    /// VRAM. Since this method requires `&mut Self`, it cannot overlap with any
-    /// previously allocated `VramMemChunk`s since they have the lifetime of the
-    /// `&Self` that allocated them.
+    /// This does not change the contents of VRAM. Since this method requires
+    /// `&mut Self`, it cannot overlap with any allocated `VramMemChunk`s since
+    /// they have the lifetime of the `&Self` that allocated them.
     pub fn free_all(&mut self) {
-        self.offset.store(0, Ordering::Relaxed);
+        unsafe { (*ptr::addr_of_mut!(HEAP)).count = 0 }
     }
 
-    // TODO: return a Result instead of panicking
-    /// Allocates `size` bytes of VRAM
-    ///
-    /// The returned VRAM chunk has the same lifetime as the
-    /// `SimpleVramAllocator` borrow (i.e. `&self`) that allocated it.
-    pub fn alloc(&self, size: u32) -> VramMemChunk<'_> {
-        let old_offset = self.offset.load(Ordering::Relaxed);
-        let new_offset = old_offset + size;
-        self.offset.store(new_offset, Ordering::Relaxed);
-
-        if new_offset > self.total_mem() {
-            panic!("Total VRAM size exceeded!");
-        }
+    /// Allocates `size` bytes of VRAM, aligned to 16 bytes.
+    pub fn alloc(&self, size: u32) -> Result<VramMemChunk<'_>, VramAllocError> {
+        self.alloc_aligned(size, DATA_ALIGN)
+    }
 
-        VramMemChunk::new(old_offset, size)
+    /// Allocates `size` bytes of VRAM, aligned to `align` bytes.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `align` is not a power of two.
+    pub fn alloc_aligned(&self, size: u32, align: u32) -> Result<VramMemChunk<'_>, VramAllocError> {
+        assert!(align.is_power_of_two(), "alignment must be a power of two");
+        self.alloc_placed(size, align, Placement::Top)
     }
 
-    // TODO: ensure 16-bit alignment?
-    pub fn alloc_sized<T: Sized>(&self, count: u32) -> VramMemChunk<'_> {
-        let size = size_of::<T>() as u32;
-        self.alloc(count * size)
+    /// Allocates space for `count` values of type `T`, aligned to at least
+    /// 16 bytes.
+    pub fn alloc_sized<T: Sized>(&self, count: u32) -> Result<VramMemChunk<'_>, VramAllocError> {
+        let size = (size_of::<T>() as u32)
+            .checked_mul(count)
+            .ok_or(VramAllocError::InvalidSize)?;
+
+        self.alloc_aligned(size, DATA_ALIGN.max(align_of::<T>() as u32))
     }
 
+    /// Allocates a texture, aligned to 16 bytes.
     pub fn alloc_texture_pixels(
         &self,
         width: u32,
         height: u32,
         psm: TexturePixelFormat,
-    ) -> VramMemChunk<'_> {
-        let size = get_memory_size(width, height, psm);
+    ) -> Result<VramMemChunk<'_>, VramAllocError> {
+        let size = get_memory_size(width, height, psm).ok_or(VramAllocError::InvalidSize)?;
         self.alloc(size)
     }
 
-    // TODO: write, or write_volatile?
-    // TODO: result instead of unwrap?
-    // TODO: Keep track of the allocated chunk
-    // TODO: determine unsafety of this
-    pub unsafe fn move_to_vram<T: Sized>(&mut self, obj: T) -> &mut T {
-        let chunk = self.alloc_sized::<T>(1);
-        let ptr = chunk.as_mut_ptr_direct_to_vram() as *mut T;
-        ptr.write(obj);
-        ptr.as_mut().unwrap()
+    /// Allocates a frame buffer or depth buffer, aligned to 8 KiB, from the
+    /// bottom of VRAM.
+    ///
+    /// `width` is the buffer width, 512 for the screen.
+    pub fn alloc_framebuffer(
+        &self,
+        width: u32,
+        height: u32,
+        psm: TexturePixelFormat,
+    ) -> Result<VramMemChunk<'_>, VramAllocError> {
+        let size = get_memory_size(width, height, psm).ok_or(VramAllocError::InvalidSize)?;
+        self.alloc_placed(size, FRAMEBUFFER_ALIGN, Placement::Bottom)
+    }
+
+    /// Moves `obj` into VRAM.
+    pub fn move_to_vram<T: Sized>(&self, obj: T) -> Result<VramBox<'_, T>, VramAllocError> {
+        let chunk = self.alloc_sized::<T>(1)?;
+
+        unsafe { (chunk.as_mut_ptr_direct_to_vram() as *mut T).write(obj) };
+
+        Ok(VramBox {
+            chunk,
+            _value: PhantomData,
+        })
+    }
+
+    /// Current VRAM usage.
+    pub fn stats(&self) -> VramStats {
+        unsafe { (*ptr::addr_of!(HEAP)).stats(self.total_mem()) }
     }
 
-    fn total_mem(&self) -> u32 {
+    /// Size of VRAM, in bytes.
+    pub fn total_mem(&self) -> u32 {
         total_vram_size()
     }
 }
 
+impl Drop for VramAllocator {
+    fn drop(&mut self) {
+        ALLOCATOR_TAKEN.store(false, Ordering::Release);
+    }
+}
+
 fn total_vram_size() -> u32 {
     unsafe { sceGeEdramGetSize() }
 }
@@ -150,18 +432,22 @@ fn vram_start_addr_direct() -> *mut u8 {
     unsafe { sceGeEdramGetAddr() }
 }
 
-fn get_memory_size(width: u32, height: u32, psm: TexturePixelFormat) -> u32 {
+fn get_memory_size(width: u32, height: u32, psm: TexturePixelFormat) -> Option<u32> {
+    let pixels = width.checked_mul(height)?;
+
     match psm {
-        TexturePixelFormat::PsmT4 => (width * height) >> 1,
-        TexturePixelFormat::PsmT8 => width * height,
+        TexturePixelFormat::PsmT4 => Some(pixels.div_ceil(2)),
+        TexturePixelFormat::PsmT8 => Some(pixels),
 
         TexturePixelFormat::Psm5650
         | TexturePixelFormat::Psm5551
         | TexturePixelFormat::Psm4444
-        | TexturePixelFormat::PsmT16 => 2 * width * height,
+        | TexturePixelFormat::PsmT16 => pixels.checked_mul(2),
 
-        TexturePixelFormat::Psm8888 | TexturePixelFormat::PsmT32 => 4 * width * height,
+        TexturePixelFormat::Psm8888 | TexturePixelFormat::PsmT32 => pixels.checked_mul(4),
 
-        _ => unimplemented!(),
+        // DXT textures are made of 4x4 blocks of 8 or 16 bytes.
+        TexturePixelFormat::PsmDxt1 => Some(pixels.div_ceil(2)),
+        TexturePixelFormat::PsmDxt3 | TexturePixelFormat::PsmDxt5 => Some(pixels),
     }
 }